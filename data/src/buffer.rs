@@ -8,7 +8,7 @@ use crate::{channel, config, message, Server};
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Buffer {
     Server(Server),
-    Channel(Server, String),
+    Channel(Server, channel::Name),
     Query(Server, Nick),
 }
 
@@ -24,7 +24,7 @@ impl Buffer {
     pub fn target(&self) -> Option<String> {
         match self {
             Buffer::Server(_) => None,
-            Buffer::Channel(_, channel) => Some(channel.clone()),
+            Buffer::Channel(_, channel) => Some(channel.to_string()),
             Buffer::Query(_, nick) => Some(nick.to_string()),
         }
     }
@@ -35,7 +35,7 @@ impl Buffer {
                 source: message::Source::Server(source),
             },
             Self::Channel(_, channel) => message::Target::Channel {
-                channel,
+                channel: channel.into(),
                 source: message::Source::Server(source),
             },
             Self::Query(_, nick) => message::Target::Query {
@@ -62,6 +62,9 @@ impl From<config::Buffer> for Settings {
 #[derive(Debug, Clone, Copy, Default, Deserialize)]
 pub struct TextInput {
     pub visibility: TextInputVisibility,
+    /// Convert markup (`*bold*`, `%C4`, ...) into formatting when sending
+    #[serde(default)]
+    pub markup: bool,
 }
 
 #[derive(Debug, Clone, Copy, Default, Deserialize)]
@@ -72,6 +75,16 @@ pub enum TextInputVisibility {
     Always,
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextFormatting {
+    /// Render formatting codes (bold, colors, ...) in messages
+    #[default]
+    Render,
+    /// Strip formatting codes from messages
+    Strip,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Timestamp {
     #[serde(default = "default_timestamp")]
@@ -1,6 +1,92 @@
+use std::fmt;
+use std::hash::Hash;
+
 use serde::{Deserialize, Serialize};
 
 use crate::config;
+use crate::isupport::CaseMap;
+
+/// A channel name as joined, identifying its buffer & history
+///
+/// Names are compared folded with the default casemapping, regardless of
+/// the server's, so `#Rust` and `#rust` are the same channel even before
+/// `CASEMAPPING` is received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "String")]
+#[serde(from = "String")]
+pub struct Name {
+    raw: String,
+    normalized: String,
+}
+
+impl Name {
+    /// The casemapped form of the name, used for identity
+    pub fn normalized(&self) -> &str {
+        &self.normalized
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized.eq(&other.normalized)
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.normalized.hash(state);
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.normalized.cmp(&other.normalized)
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.raw.fmt(f)
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        self.raw.as_ref()
+    }
+}
+
+impl From<String> for Name {
+    fn from(raw: String) -> Self {
+        let normalized = CaseMap::default().normalize(&raw);
+
+        Name { raw, normalized }
+    }
+}
+
+impl<'a> From<&'a str> for Name {
+    fn from(name: &'a str) -> Self {
+        Name::from(name.to_string())
+    }
+}
+
+impl From<Name> for String {
+    fn from(name: Name) -> Self {
+        name.raw
+    }
+}
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct Settings {
@@ -68,3 +154,16 @@ impl Topic {
         self.enabled = !self.enabled
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn name_identity() {
+        assert_eq!(Name::from("#Rust"), Name::from("#rust"));
+        assert_eq!(Name::from("#a[b]"), Name::from("#A{B}"));
+        assert_ne!(Name::from("#rust"), Name::from("#rust-offtopic"));
+        assert_eq!(Name::from("#Rust").to_string(), "#Rust");
+    }
+}
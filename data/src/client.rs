@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use futures::channel::mpsc;
 use irc::proto::{self, command, Command};
 use itertools::{Either, Itertools};
@@ -6,19 +6,28 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::time::{Duration, Instant};
 
+use crate::config::server::SaslFailure;
+use crate::isupport::{self, CaseMap, ISupport};
 use crate::message::server_time;
 use crate::time::Posix;
 use crate::user::{Nick, NickRef};
-use crate::{config, dcc, message, mode, Buffer, Server, User};
+use crate::{config, ctcp, dcc, environment, history, message, mode, stream, Buffer, Server, User};
 use crate::{file_transfer, server};
 
+mod sasl;
+
 const HIGHLIGHT_BLACKOUT_INTERVAL: Duration = Duration::from_secs(5);
+/// Max # messages requested per `CHATHISTORY` page
+const CHATHISTORY_LIMIT: u16 = 500;
+/// Lag from which a connection is degraded, unless `ping_timeout` is shorter
+const DEGRADED_LAG: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, Copy)]
 pub enum Status {
     Unavailable,
     Connected,
     Disconnected,
+    Offline,
 }
 
 impl Status {
@@ -30,6 +39,8 @@ impl Status {
 #[derive(Debug)]
 pub enum State {
     Disconnected,
+    /// Disconnected by choice, not connecting
+    Offline,
     Ready(Client),
 }
 
@@ -59,6 +70,11 @@ pub enum Broadcast {
         user_channels: Vec<String>,
         sent_time: DateTime<Utc>,
     },
+    SaslFailed {
+        error: String,
+        disconnect: bool,
+        sent_time: DateTime<Utc>,
+    },
 }
 
 #[derive(Debug)]
@@ -68,6 +84,12 @@ pub enum Event {
     Broadcast(Broadcast),
     Notification(message::Encoded, Nick, Notification),
     FileTransferRequest(file_transfer::ReceiveRequest),
+    /// Messages played back from a `chathistory` batch
+    ChatHistory(Vec<message::Encoded>, Nick),
+    /// Request the messages sent to the target while we were away
+    ChatHistoryRequest(history::Kind),
+    /// Request the messages sent to each of our queries while we were away
+    ChatHistoryQueries,
 }
 
 pub struct Client {
@@ -75,18 +97,34 @@ pub struct Client {
     config: config::Server,
     handle: server::Handle,
     alt_nick: Option<usize>,
-    resolved_nick: Option<String>,
+    default_nick: Nick,
+    resolved_nick: Option<Nick>,
+    resolved_username: Option<String>,
+    resolved_hostname: Option<String>,
     chanmap: BTreeMap<String, Channel>,
     channels: Vec<String>,
     users: HashMap<String, Vec<User>>,
     labels: HashMap<String, Context>,
     batches: HashMap<String, Batch>,
+    chathistory_requests: HashMap<String, Option<DateTime<Utc>>>,
     reroute_responses_to: Option<Buffer>,
     registration_step: RegistrationStep,
     listed_caps: Vec<String>,
+    /// Mechanisms the server supports, if it listed them
+    sasl_mechanisms: Option<Vec<String>>,
+    sasl_tried: Vec<sasl::Mechanism>,
+    sasl: Option<sasl::Session>,
     supports_labels: bool,
     supports_away_notify: bool,
+    supports_chathistory: bool,
     highlight_blackout: HighlightBlackout,
+    isupport: ISupport,
+    last_round_trip: Option<Duration>,
+    /// When the oldest unanswered ping was sent
+    ping_sent: Option<Instant>,
+    /// # of messages waiting to be sent to avoid flooding the server
+    queued: usize,
+    ctcp_replies: ctcp::ReplyLimit,
 }
 
 impl fmt::Debug for Client {
@@ -99,10 +137,10 @@ impl Client {
     pub fn new(
         server: Server,
         config: config::Server,
-        mut sender: mpsc::Sender<proto::Message>,
+        sender: mpsc::UnboundedSender<proto::Message>,
     ) -> Self {
         // Begin registration
-        let _ = sender.try_send(command!("CAP", "LS", "302"));
+        let _ = sender.unbounded_send(command!("CAP", "LS", "302"));
         let registration_step = RegistrationStep::List;
 
         // Identify
@@ -112,36 +150,49 @@ impl Client {
             let real = config.realname.as_ref().unwrap_or(nick);
 
             if let Some(pass) = config.password.as_ref() {
-                let _ = sender.try_send(command!("PASS", pass));
+                let _ = sender.unbounded_send(command!("PASS", pass));
             }
-            let _ = sender.try_send(command!("NICK", nick));
-            let _ = sender.try_send(command!("USER", user, real));
+            let _ = sender.unbounded_send(command!("NICK", nick));
+            let _ = sender.unbounded_send(command!("USER", user, real));
         }
 
         Self {
             server,
+            default_nick: Nick::from(config.nickname.as_str()),
             config,
             handle: sender,
             resolved_nick: None,
+            resolved_username: None,
+            resolved_hostname: None,
             alt_nick: None,
             chanmap: BTreeMap::default(),
             channels: vec![],
             users: HashMap::new(),
             labels: HashMap::new(),
             batches: HashMap::new(),
+            chathistory_requests: HashMap::new(),
             reroute_responses_to: None,
             registration_step,
             listed_caps: vec![],
+            sasl_mechanisms: None,
+            sasl_tried: vec![],
+            sasl: None,
             supports_labels: false,
             supports_away_notify: false,
+            supports_chathistory: false,
             highlight_blackout: HighlightBlackout::Blackout(Instant::now()),
+            isupport: ISupport::default(),
+            last_round_trip: None,
+            ping_sent: None,
+            queued: 0,
+            ctcp_replies: ctcp::ReplyLimit::default(),
         }
     }
 
-    pub async fn quit(mut self) {
+    pub async fn quit(self) {
         use tokio::time;
 
-        let _ = self.handle.try_send(command!("QUIT"));
+        let _ = self.handle.unbounded_send(command!("QUIT"));
 
         // Ensure message is sent before dropping
         time::sleep(Duration::from_secs(1)).await;
@@ -165,14 +216,16 @@ impl Client {
 
         self.reroute_responses_to = start_reroute(&message.command).then(|| buffer.clone());
 
-        if let Err(e) = self.handle.try_send(message.into()) {
+        if let Err(e) = self.handle.unbounded_send(message.into()) {
             log::warn!("Error sending message: {e}");
         }
     }
 
-    fn receive(&mut self, message: message::Encoded) -> Vec<Event> {
+    fn receive(&mut self, mut message: message::Encoded) -> Vec<Event> {
         log::trace!("Message received => {:?}", *message);
 
+        self.canonicalize_channel(&mut message);
+
         let stop_reroute = stop_reroute(&message.command);
 
         let events = self.handle(message, None).unwrap_or_default();
@@ -209,14 +262,24 @@ impl Client {
         });
 
         match &message.command {
-            Command::BATCH(batch, ..) => {
+            Command::BATCH(batch, params) => {
                 let mut chars = batch.chars();
                 let symbol = chars.next()?;
                 let reference = chars.collect::<String>();
 
                 match symbol {
                     '+' => {
-                        let batch = Batch::new(context);
+                        let mut batch = Batch::new(context);
+
+                        // Batches nested in a playback are part of it
+                        batch.chathistory = match params.first().map(String::as_str) {
+                            Some("chathistory") => params.get(1).cloned(),
+                            _ => batch_tag
+                                .as_ref()
+                                .and_then(|parent| self.batches.get(parent))
+                                .and_then(|parent| parent.chathistory.clone()),
+                        };
+
                         self.batches.insert(reference, batch);
                     }
                     '-' => {
@@ -227,6 +290,10 @@ impl Client {
                                 .and_then(|batch| self.batches.get_mut(batch))
                             {
                                 parent.events.extend(finished.events);
+                            } else if let Some(target) = finished.chathistory {
+                                return Some(
+                                    self.chathistory_played_back(&target, finished.events),
+                                );
                             } else {
                                 return Some(finished.events);
                             }
@@ -237,6 +304,21 @@ impl Client {
 
                 return None;
             }
+            // Played back messages are recorded as is, w/o updating any state
+            // or triggering notifications & CTCP replies
+            _ if batch_tag
+                .as_ref()
+                .and_then(|batch| self.batches.get(batch))
+                .is_some_and(|batch| batch.chathistory.is_some()) =>
+            {
+                let our_nick = self.nickname().to_owned();
+
+                if let Some(batch) = batch_tag.and_then(|batch| self.batches.get_mut(&batch)) {
+                    batch.events.push(Event::Single(message, our_nick));
+                }
+
+                return None;
+            }
             _ if batch_tag.is_some() => {
                 let events = self.handle(message, context)?;
 
@@ -296,14 +378,26 @@ impl Client {
                     if contains("userhost-in-names") {
                         requested.push("userhost-in-names");
                     }
+                    if contains("multi-prefix") {
+                        requested.push("multi-prefix");
+                    }
                     if contains("away-notify") {
                         requested.push("away-notify");
                     }
                     if contains("server-time") {
                         requested.push("server-time");
                     }
+                    // Needed for `msgid` tags, used to dedupe playback
+                    if contains("message-tags") {
+                        requested.push("message-tags");
+                    }
                     if contains("batch") {
                         requested.push("batch");
+
+                        // Playback is delivered in batches
+                        if contains("draft/chathistory") {
+                            requested.push("draft/chathistory");
+                        }
                     }
                     if contains("labeled-response") {
                         requested.push("labeled-response");
@@ -320,13 +414,13 @@ impl Client {
                     if !requested.is_empty() {
                         // Request
                         self.registration_step = RegistrationStep::Req;
-                        let _ = self
-                            .handle
-                            .try_send(command!("CAP", "REQ", requested.join(" ")));
+                        let _ =
+                            self.handle
+                                .unbounded_send(command!("CAP", "REQ", requested.join(" ")));
                     } else {
                         // If none requested, end negotiation
                         self.registration_step = RegistrationStep::End;
-                        let _ = self.handle.try_send(command!("CAP", "END"));
+                        let _ = self.handle.unbounded_send(command!("CAP", "END"));
                     }
                 }
             }
@@ -342,17 +436,24 @@ impl Client {
                 if caps.contains(&"away-notify") {
                     self.supports_away_notify = true;
                 }
+                if caps.contains(&"draft/chathistory") {
+                    self.supports_chathistory = true;
+                }
 
                 let supports_sasl = caps.iter().any(|cap| cap.contains("sasl"));
 
-                if let Some(sasl) = self.config.sasl.as_ref().filter(|_| supports_sasl) {
+                if supports_sasl && self.config.sasl.is_some() {
                     self.registration_step = RegistrationStep::Sasl;
-                    let _ = self
-                        .handle
-                        .try_send(command!("AUTHENTICATE", sasl.command()));
+                    self.sasl_mechanisms = self
+                        .listed_caps
+                        .iter()
+                        .find_map(|cap| cap.strip_prefix("sasl="))
+                        .map(sasl::mechanisms);
+
+                    return self.authenticate(server_time(&message));
                 } else {
                     self.registration_step = RegistrationStep::End;
-                    let _ = self.handle.try_send(command!("CAP", "END"));
+                    let _ = self.handle.unbounded_send(command!("CAP", "END"));
                 }
             }
             Command::CAP(_, sub, a, b) if sub == "NAK" => {
@@ -362,23 +463,81 @@ impl Client {
                 // End we didn't move to sasl or already ended
                 if self.registration_step < RegistrationStep::Sasl {
                     self.registration_step = RegistrationStep::End;
-                    let _ = self.handle.try_send(command!("CAP", "END"));
+                    let _ = self.handle.unbounded_send(command!("CAP", "END"));
                 }
             }
-            Command::AUTHENTICATE(param) if param == "+" => {
-                if let Some(sasl) = self.config.sasl.as_ref() {
-                    log::info!("[{}] sasl auth: {}", self.server, sasl.command());
+            Command::AUTHENTICATE(param) => {
+                let (Some(config), Some(session)) = (self.config.sasl.as_ref(), self.sasl.as_mut())
+                else {
+                    return None;
+                };
 
-                    let _ = self.handle.try_send(command!("AUTHENTICATE", sasl.param()));
-                    self.registration_step = RegistrationStep::End;
-                    let _ = self.handle.try_send(command!("CAP", "END"));
+                match session.receive(config, param) {
+                    Ok(responses) => {
+                        for response in responses {
+                            let _ = self
+                                .handle
+                                .unbounded_send(command!("AUTHENTICATE", response));
+                        }
+                    }
+                    Err(error) => {
+                        let _ = self.handle.unbounded_send(command!("AUTHENTICATE", "*"));
+
+                        return self.sasl_failed(error.to_string(), server_time(&message));
+                    }
+                }
+
+                return None;
+            }
+            Command::Numeric(RPL_SASLSUCCESS | ERR_SASLALREADY, _)
+                if self.registration_step == RegistrationStep::Sasl =>
+            {
+                log::info!("[{}] sasl auth succeeded", self.server);
+
+                self.sasl = None;
+                self.registration_step = RegistrationStep::End;
+                let _ = self.handle.unbounded_send(command!("CAP", "END"));
+            }
+            Command::Numeric(RPL_SASLMECHS, args)
+                if self.registration_step == RegistrationStep::Sasl =>
+            {
+                self.sasl_mechanisms = Some(sasl::mechanisms(args.get(1)?));
+
+                return None;
+            }
+            Command::Numeric(ERR_SASLFAIL | ERR_SASLTOOLONG, args)
+                if self.registration_step == RegistrationStep::Sasl =>
+            {
+                // Rejected before any challenge, so the server may accept another mechanism
+                let rejected = self
+                    .sasl
+                    .take()
+                    .is_some_and(|session| !session.has_started());
+                let can_retry = self.config.sasl.as_ref().is_some_and(|config| {
+                    sasl::select(config, self.sasl_mechanisms.as_deref(), &self.sasl_tried)
+                        .is_some()
+                });
+
+                if rejected && can_retry {
+                    return self.authenticate(server_time(&message));
                 }
+
+                let error = args.last().cloned().unwrap_or_default();
+
+                return self.sasl_failed(error, server_time(&message));
+            }
+            Command::Numeric(ERR_NICKLOCKED | ERR_SASLABORTED, args)
+                if self.registration_step == RegistrationStep::Sasl =>
+            {
+                let error = args.last().cloned().unwrap_or_default();
+
+                return self.sasl_failed(error, server_time(&message));
             }
             Command::Numeric(RPL_LOGGEDIN, _) => {
                 log::info!("[{}] logged in", self.server);
             }
             Command::PRIVMSG(channel, text) | Command::NOTICE(channel, text) => {
-                if let Some(user) = message.user() {
+                if let Some(user) = message.user(self.casemapping()) {
                     if let Some(command) = dcc::decode(text) {
                         match command {
                             dcc::Command::Send(request) => {
@@ -389,6 +548,7 @@ impl Client {
                                         dcc_send: request,
                                         server: self.server.clone(),
                                         server_handle: self.handle.clone(),
+                                        proxy: self.config.proxy.clone(),
                                     },
                                 )]);
                             }
@@ -397,10 +557,22 @@ impl Client {
                                 return None;
                             }
                         }
+                    } else if let Some(query) =
+                        ctcp::decode(text).filter(|query| query.command != ctcp::Command::Action)
+                    {
+                        if matches!(message.command, Command::PRIVMSG(..))
+                            && user.nickname() != self.nickname()
+                        {
+                            self.reply_to_ctcp(&user, &query);
+                        }
                     }
                     // Highlight notification
-                    else if message::reference_user(user.nickname(), self.nickname(), text)
-                        && self.highlight_blackout.allow_highlights()
+                    else if message::reference_user(
+                        user.nickname(),
+                        self.nickname(),
+                        text,
+                        self.casemapping(),
+                    ) && self.highlight_blackout.allow_highlights()
                     {
                         return Some(vec![Event::Notification(
                             message.clone(),
@@ -414,8 +586,8 @@ impl Client {
                 }
             }
             Command::INVITE(user, channel) => {
-                let user = User::from(Nick::from(user.as_str()));
-                let inviter = message.user()?;
+                let user = User::from(Nick::new(user.as_str(), self.casemapping()));
+                let inviter = message.user(self.casemapping())?;
                 let user_channels = self.user_channels(user.nickname());
 
                 return Some(vec![Event::Broadcast(Broadcast::Invite {
@@ -426,15 +598,15 @@ impl Client {
                 })]);
             }
             Command::NICK(nick) => {
-                let old_user = message.user()?;
+                let old_user = message.user(self.casemapping())?;
                 let ourself = self.nickname() == old_user.nickname();
 
+                let new_nick = Nick::new(nick.as_str(), self.casemapping());
+
                 if ourself {
-                    self.resolved_nick = Some(nick.clone());
+                    self.resolved_nick = Some(new_nick.clone());
                 }
 
-                let new_nick = Nick::from(nick.as_str());
-
                 self.chanmap.values_mut().for_each(|channel| {
                     if let Some(user) = channel.users.take(&old_user) {
                         channel.users.insert(user.with_nickname(new_nick.clone()));
@@ -468,20 +640,31 @@ impl Client {
                 }
 
                 if let Some(nick) = self.alt_nick.and_then(|i| self.config.alt_nicks.get(i)) {
-                    let _ = self.handle.try_send(command!("NICK", nick));
+                    let _ = self.handle.unbounded_send(command!("NICK", nick));
                 }
             }
             Command::Numeric(RPL_WELCOME, args) => {
                 // Updated actual nick
                 let nick = args.first()?;
-                self.resolved_nick = Some(nick.to_string());
+                self.resolved_nick = Some(Nick::new(nick.as_str(), self.casemapping()));
+
+                // Welcome text usually ends w/ our full `nick!user@host`
+                if let Some(user) = args
+                    .last()
+                    .and_then(|text| text.split_ascii_whitespace().last())
+                    .and_then(|hostmask| User::try_from(hostmask).ok())
+                    .filter(|user| user.nickname().as_ref() == nick.as_str())
+                {
+                    self.resolved_username = user.username().map(String::from);
+                    self.resolved_hostname = user.hostname().map(String::from);
+                }
 
                 // Send nick password & ghost
                 if let Some(nick_pass) = self.config.nick_password.as_ref() {
                     // Try ghost recovery if we couldn't claim our nick
                     if self.config.should_ghost && nick != &self.config.nickname {
                         for sequence in &self.config.ghost_sequence {
-                            let _ = self.handle.try_send(command!(
+                            let _ = self.handle.unbounded_send(command!(
                                 "PRIVMSG",
                                 "NickServ",
                                 format!("{sequence} {} {nick_pass}", &self.config.nickname)
@@ -492,31 +675,31 @@ impl Client {
                     let _ = if let Some(identify_syntax) = &self.config.nick_identify_syntax {
                         match identify_syntax {
                             config::server::IdentifySyntax::PasswordNick => {
-                                self.handle.try_send(command!(
+                                self.handle.unbounded_send(command!(
                                     "PRIVMSG",
                                     "NickServ",
                                     format!("IDENTIFY {nick_pass} {}", &self.config.nickname)
                                 ))
                             }
                             config::server::IdentifySyntax::NickPassword => {
-                                self.handle.try_send(command!(
+                                self.handle.unbounded_send(command!(
                                     "PRIVMSG",
                                     "NickServ",
                                     format!("IDENTIFY {} {nick_pass}", &self.config.nickname)
                                 ))
                             }
                         }
-                    } else if self.resolved_nick == Some(self.config.nickname.clone()) {
+                    } else if self.resolved_nick.as_ref() == Some(&self.default_nick) {
                         // Use nickname-less identification if possible, since it has
                         // no possible argument order issues.
-                        self.handle.try_send(command!(
+                        self.handle.unbounded_send(command!(
                             "PRIVMSG",
                             "NickServ",
                             format!("IDENTIFY {nick_pass}")
                         ))
                     } else {
                         // Default to most common syntax if unknown
-                        self.handle.try_send(command!(
+                        self.handle.unbounded_send(command!(
                             "PRIVMSG",
                             "NickServ",
                             format!("IDENTIFY {} {nick_pass}", &self.config.nickname)
@@ -526,26 +709,36 @@ impl Client {
 
                 // Send user modestring
                 if let Some(modestring) = self.config.umodes.as_ref() {
-                    let _ = self.handle.try_send(command!("MODE", nick, modestring));
+                    let _ = self
+                        .handle
+                        .unbounded_send(command!("MODE", nick, modestring));
                 }
 
                 // Loop on connect commands
                 for command in self.config.on_connect.iter() {
                     if let Ok(cmd) = crate::command::parse(command, None) {
                         if let Ok(command) = proto::Command::try_from(cmd) {
-                            let _ = self.handle.try_send(command.into());
+                            let _ = self.handle.unbounded_send(command.into());
                         };
                     };
                 }
 
                 // Send JOIN
                 for message in group_joins(&self.config) {
-                    let _ = self.handle.try_send(message);
+                    let _ = self.handle.unbounded_send(message);
+                }
+
+                // Channels request their playback once joined
+                if self.supports_chathistory {
+                    return Some(vec![
+                        Event::Single(message, self.nickname().to_owned()),
+                        Event::ChatHistoryQueries,
+                    ]);
                 }
             }
             // QUIT
             Command::QUIT(comment) => {
-                let user = message.user()?;
+                let user = message.user(self.casemapping())?;
 
                 self.chanmap.values_mut().for_each(|channel| {
                     channel.users.remove(&user);
@@ -561,57 +754,84 @@ impl Client {
                 })]);
             }
             Command::PART(channel, _) => {
-                let user = message.user()?;
+                let user = message.user(self.casemapping())?;
 
                 if user.nickname() == self.nickname() {
-                    self.chanmap.remove(channel);
-                } else if let Some(channel) = self.chanmap.get_mut(channel) {
+                    self.chanmap.remove(&self.casemapping().normalize(channel));
+                } else if let Some(channel) = self.channel_mut(channel) {
                     channel.users.remove(&user);
                 }
             }
             Command::JOIN(channel, _) => {
-                let user = message.user()?;
+                let user = message.user(self.casemapping())?;
 
                 if user.nickname() == self.nickname() {
-                    self.chanmap.insert(channel.clone(), Channel::default());
+                    if let Some(username) = user.username() {
+                        self.resolved_username = Some(username.to_string());
+                    }
+                    if let Some(hostname) = user.hostname() {
+                        self.resolved_hostname = Some(hostname.to_string());
+                    }
+
+                    let key = self.casemapping().normalize(channel);
 
-                    if let Some(state) = self.chanmap.get_mut(channel) {
+                    self.chanmap
+                        .insert(key.clone(), Channel::new(channel.clone()));
+
+                    if let Some(state) = self.chanmap.get_mut(&key) {
                         // Sends WHO to get away state on users.
-                        let _ = self.handle.try_send(command!("WHO", channel));
+                        let _ = self.handle.unbounded_send(command!("WHO", channel));
                         state.last_who = Some(WhoStatus::Requested(Instant::now()));
                         log::debug!("[{}] {channel} - WHO requested", self.server);
                     }
-                } else if let Some(channel) = self.chanmap.get_mut(channel) {
+
+                    // Requested before our join is recorded, so it isn't taken
+                    // as the latest message we have
+                    if self.supports_chathistory {
+                        let kind = history::Kind::Channel(channel.clone().into());
+
+                        return Some(vec![
+                            Event::ChatHistoryRequest(kind),
+                            Event::Single(message, self.nickname().to_owned()),
+                        ]);
+                    }
+                } else if let Some(channel) = self.channel_mut(channel) {
                     channel.users.insert(user);
                 }
             }
             Command::KICK(channel, victim, _) => {
-                if victim == self.nickname().as_ref() {
-                    self.chanmap.remove(channel);
-                } else if let Some(channel) = self.chanmap.get_mut(channel) {
-                    channel
-                        .users
-                        .remove(&User::from(Nick::from(victim.as_str())));
+                let victim = Nick::new(victim.as_str(), self.casemapping());
+
+                if self.nickname() == victim {
+                    self.chanmap.remove(&self.casemapping().normalize(channel));
+                } else if let Some(channel) = self.channel_mut(channel) {
+                    channel.users.remove(&User::from(victim));
                 }
             }
             Command::Numeric(RPL_WHOREPLY, args) => {
                 let target = args.get(1)?;
 
-                if proto::is_channel(target) {
-                    if let Some(channel) = self.chanmap.get_mut(target) {
+                if self.isupport.is_channel(target) {
+                    let casemapping = self.casemapping();
+                    let prefix = self.isupport.prefix();
+
+                    if let Some(channel) = self.chanmap.get_mut(&casemapping.normalize(target)) {
                         if matches!(channel.last_who, Some(WhoStatus::Requested(_)) | None) {
                             channel.last_who = Some(WhoStatus::Receiving);
                             log::debug!("[{}] {target} - WHO receiving...", self.server);
                         }
 
-                        // H = Here, G = gone (away)
-                        let flags = args.get(6)?.chars().collect::<Vec<char>>();
-                        let away = *(flags.first()?) == 'G';
+                        // H = Here, G = gone (away), followed by an optional
+                        // * (server operator) and the user's channel prefixes
+                        let flags = args.get(6)?;
+                        let away = flags.starts_with('G');
+                        let prefixes = flags.get(1..).unwrap_or_default();
 
-                        let lookup = User::from(Nick::from(args[5].clone()));
+                        let lookup = User::from(Nick::new(args[5].as_str(), casemapping));
 
                         if let Some(mut user) = channel.users.take(&lookup) {
                             user.update_away(away);
+                            user.set_access_levels(prefixes, prefix);
                             channel.users.insert(user);
                         }
 
@@ -625,8 +845,10 @@ impl Client {
             Command::Numeric(RPL_ENDOFWHO, args) => {
                 let target = args.get(1)?;
 
-                if proto::is_channel(target) {
-                    if let Some(channel) = self.chanmap.get_mut(target) {
+                if self.isupport.is_channel(target) {
+                    let key = self.casemapping().normalize(target);
+
+                    if let Some(channel) = self.chanmap.get_mut(&key) {
                         if matches!(channel.last_who, Some(WhoStatus::Receiving)) {
                             channel.last_who = Some(WhoStatus::Done(Instant::now()));
                             log::debug!("[{}] {target} - WHO done", self.server);
@@ -637,7 +859,7 @@ impl Client {
             }
             Command::AWAY(args) => {
                 let away = args.is_some();
-                let user = message.user()?;
+                let user = message.user(self.casemapping())?;
 
                 for channel in self.chanmap.values_mut() {
                     if let Some(mut user) = channel.users.take(&user) {
@@ -648,7 +870,9 @@ impl Client {
             }
             Command::Numeric(RPL_UNAWAY, args) => {
                 let nick = args.first()?.as_str();
-                let user = User::try_from(nick).ok()?;
+                let user = User::try_from(nick)
+                    .ok()?
+                    .with_casemapping(self.casemapping());
 
                 if user.nickname() == self.nickname() {
                     for channel in self.chanmap.values_mut() {
@@ -661,7 +885,9 @@ impl Client {
             }
             Command::Numeric(RPL_NOWAWAY, args) => {
                 let nick = args.first()?.as_str();
-                let user = User::try_from(nick).ok()?;
+                let user = User::try_from(nick)
+                    .ok()?
+                    .with_casemapping(self.casemapping());
 
                 if user.nickname() == self.nickname() {
                     for channel in self.chanmap.values_mut() {
@@ -672,15 +898,23 @@ impl Client {
                     }
                 }
             }
-            Command::MODE(target, Some(modes), args) if proto::is_channel(target) => {
-                let modes = mode::parse::<mode::Channel>(modes, args);
+            Command::MODE(target, Some(modes), args) if self.isupport.is_channel(target) => {
+                let casemapping = self.casemapping();
+                let prefix = self.isupport.prefix();
+                let modes =
+                    mode::parse::<mode::Channel>(modes, args, self.isupport.chanmodes(), prefix);
 
-                if let Some(channel) = self.chanmap.get_mut(target) {
+                if let Some(channel) = self.chanmap.get_mut(&casemapping.normalize(target)) {
                     for mode in modes {
-                        if let Some((op, lookup)) = mode
-                            .operation()
-                            .zip(mode.arg().map(|nick| User::from(Nick::from(nick))))
-                        {
+                        // Only modes listed in PREFIX change a user's access level
+                        if !prefix.iter().any(|p| p.mode == char::from(*mode.value())) {
+                            continue;
+                        }
+
+                        if let Some((op, lookup)) = mode.operation().zip(
+                            mode.arg()
+                                .map(|nick| User::from(Nick::new(nick, casemapping))),
+                        ) {
                             if let Some(mut user) = channel.users.take(&lookup) {
                                 user.update_access_level(op, *mode.value());
                                 channel.users.insert(user);
@@ -690,26 +924,40 @@ impl Client {
                 }
             }
             Command::Numeric(RPL_NAMREPLY, args) if args.len() > 3 => {
-                if let Some(channel) = self.chanmap.get_mut(&args[2]) {
+                let casemapping = self.casemapping();
+
+                if let Some(channel) = self.chanmap.get_mut(&casemapping.normalize(&args[2])) {
                     for user in args[3].split(' ') {
-                        if let Ok(user) = User::try_from(user) {
+                        if let Ok(user) = User::parse(user, self.isupport.prefix(), casemapping) {
                             channel.users.insert(user);
                         }
                     }
                 }
             }
+            Command::Numeric(RPL_ISUPPORT, args) => {
+                let casemapping = self.casemapping();
+
+                for operation in isupport::parse(args) {
+                    log::debug!("[{}] isupport: {operation:?}", self.server);
+                    self.isupport.apply(operation);
+                }
+
+                if self.casemapping() != casemapping {
+                    self.update_casemapping();
+                }
+            }
             Command::TOPIC(channel, topic) => {
-                if let Some(channel) = self.chanmap.get_mut(channel) {
+                let user = message.user(self.casemapping());
+
+                if let Some(channel) = self.channel_mut(channel) {
                     channel.topic.text = topic.to_owned();
 
-                    channel.topic.who = message
-                        .user()
-                        .map(|user| user.username().unwrap().to_string());
+                    channel.topic.who = user.map(|user| user.username().unwrap().to_string());
                     channel.topic.time = Some(server_time(&message));
                 }
             }
             Command::Numeric(RPL_TOPIC, args) => {
-                if let Some(channel) = self.chanmap.get_mut(&args[1]) {
+                if let Some(channel) = self.channel_mut(&args[1]) {
                     channel.topic.text = Some(args.get(2)?.to_owned());
                 }
                 // Exclude topic message from history to prevent spam during dev
@@ -717,7 +965,7 @@ impl Client {
                 return None;
             }
             Command::Numeric(RPL_TOPICWHOTIME, args) => {
-                if let Some(channel) = self.chanmap.get_mut(&args[1]) {
+                if let Some(channel) = self.channel_mut(&args[1]) {
                     channel.topic.who = Some(args.get(2)?.to_string());
                     channel.topic.time = Some(
                         args.get(3)?
@@ -737,8 +985,63 @@ impl Client {
         Some(vec![Event::Single(message, self.nickname().to_owned())])
     }
 
+    /// Starts authenticating w/ the most preferred mechanism the server
+    /// supports & which hasn't been tried yet
+    fn authenticate(&mut self, sent_time: DateTime<Utc>) -> Option<Vec<Event>> {
+        let config = self.config.sasl.as_ref()?;
+
+        match sasl::select(config, self.sasl_mechanisms.as_deref(), &self.sasl_tried) {
+            Some(mechanism) => {
+                log::info!("[{}] sasl auth: {mechanism}", self.server);
+
+                self.sasl_tried.push(mechanism);
+                self.sasl = Some(sasl::Session::new(mechanism));
+                let _ = self
+                    .handle
+                    .unbounded_send(command!("AUTHENTICATE", mechanism.name()));
+
+                None
+            }
+            None => {
+                let error = match &self.sasl_mechanisms {
+                    Some(supported) => {
+                        format!("the server only supports {}", supported.join(", "))
+                    }
+                    None => "no mechanism left to try".to_string(),
+                };
+
+                self.sasl_failed(error, sent_time)
+            }
+        }
+    }
+
+    /// Ends registration w/o authenticating or, as configured, leaves
+    /// disconnecting to [`Map::receive`], which stops the stream
+    fn sasl_failed(&mut self, error: String, sent_time: DateTime<Utc>) -> Option<Vec<Event>> {
+        log::warn!("[{}] sasl auth failed: {error}", self.server);
+
+        self.sasl = None;
+        self.registration_step = RegistrationStep::End;
+
+        let disconnect = self.config.sasl_failure == SaslFailure::Disconnect;
+
+        if !disconnect {
+            let _ = self.handle.unbounded_send(command!("CAP", "END"));
+        }
+
+        Some(vec![Event::Broadcast(Broadcast::SaslFailed {
+            error,
+            disconnect,
+            sent_time,
+        })])
+    }
+
     fn sync(&mut self) {
-        self.channels = self.chanmap.keys().cloned().collect();
+        self.channels = self
+            .chanmap
+            .values()
+            .map(|channel| channel.name.clone())
+            .collect();
         self.users = self
             .chanmap
             .iter()
@@ -755,19 +1058,150 @@ impl Client {
         &self.channels
     }
 
+    /// Requests the messages sent to `target` after `since`, or the latest
+    /// ones if we have none stored
+    pub fn request_chathistory(&mut self, target: &str, since: Option<DateTime<Utc>>) {
+        if !self.supports_chathistory {
+            return;
+        }
+
+        let limit = self.chathistory_limit().to_string();
+
+        let command = match since {
+            Some(since) => command!(
+                "CHATHISTORY",
+                "AFTER",
+                target,
+                format!(
+                    "timestamp={}",
+                    since.to_rfc3339_opts(SecondsFormat::Millis, true)
+                ),
+                limit
+            ),
+            None => command!("CHATHISTORY", "LATEST", target, "*", limit),
+        };
+
+        log::debug!("[{}] {target} - chathistory requested", self.server);
+
+        self.chathistory_requests
+            .insert(self.casemapping().normalize(target), since);
+        let _ = self.handle.unbounded_send(command);
+    }
+
+    fn chathistory_limit(&self) -> u16 {
+        self.isupport
+            .chathistory_limit()
+            .map_or(CHATHISTORY_LIMIT, |limit| limit.min(CHATHISTORY_LIMIT))
+    }
+
+    /// Collects the messages of a finished playback, requesting the next
+    /// page if the server returned a full one
+    fn chathistory_played_back(&mut self, target: &str, events: Vec<Event>) -> Vec<Event> {
+        let messages = events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Single(message, _) => Some(message),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        log::debug!(
+            "[{}] {target} - chathistory received: {} messages",
+            self.server,
+            messages.len()
+        );
+
+        let request = self
+            .chathistory_requests
+            .remove(&self.casemapping().normalize(target));
+
+        // Only paging forward continues, `LATEST` already returned the
+        // most recent messages
+        if let (Some(Some(_)), Some(last)) = (request, messages.last()) {
+            if messages.len() >= usize::from(self.chathistory_limit()) {
+                self.request_chathistory(target, Some(server_time(last)));
+            }
+        }
+
+        vec![Event::ChatHistory(messages, self.nickname().to_owned())]
+    }
+
+    fn channel(&self, channel: &str) -> Option<&Channel> {
+        self.chanmap.get(&self.casemapping().normalize(channel))
+    }
+
+    fn channel_mut(&mut self, channel: &str) -> Option<&mut Channel> {
+        self.chanmap.get_mut(&self.casemapping().normalize(channel))
+    }
+
+    /// Rewrite the channel a message refers to into the name we joined it
+    /// with, so names differing only by case end up in the same buffer
+    fn canonicalize_channel(&self, message: &mut message::Encoded) {
+        use irc::proto::command::Numeric::*;
+
+        let channel = match &mut message.command {
+            Command::PRIVMSG(channel, _)
+            | Command::NOTICE(channel, _)
+            | Command::JOIN(channel, _)
+            | Command::PART(channel, _)
+            | Command::TOPIC(channel, _)
+            | Command::KICK(channel, _, _)
+            | Command::MODE(channel, _, _) => Some(channel),
+            Command::Numeric(
+                RPL_TOPIC | RPL_TOPICWHOTIME | RPL_CHANNELMODEIS | RPL_WHOREPLY | RPL_ENDOFWHO
+                | RPL_ENDOFNAMES,
+                args,
+            ) => args.get_mut(1),
+            Command::Numeric(RPL_NAMREPLY, args) => args.get_mut(2),
+            _ => None,
+        };
+
+        if let Some(channel) = channel {
+            if let Some(state) = self.channel(channel) {
+                channel.clone_from(&state.name);
+            }
+        }
+    }
+
+    /// Casemapping changed, so re-normalize everything keyed by name
+    fn update_casemapping(&mut self) {
+        let casemapping = self.casemapping();
+
+        log::debug!("[{}] casemapping: {casemapping:?}", self.server);
+
+        self.default_nick = self.default_nick.clone().with_casemapping(casemapping);
+        self.resolved_nick = self
+            .resolved_nick
+            .take()
+            .map(|nick| nick.with_casemapping(casemapping));
+        self.chanmap = std::mem::take(&mut self.chanmap)
+            .into_values()
+            .map(|mut channel| {
+                channel.users = channel
+                    .users
+                    .into_iter()
+                    .map(|user| user.with_casemapping(casemapping))
+                    .collect();
+
+                (casemapping.normalize(&channel.name), channel)
+            })
+            .collect();
+    }
+
     fn topic<'a>(&'a self, channel: &str) -> Option<&'a Topic> {
-        self.chanmap.get(channel).map(|channel| &channel.topic)
+        self.channel(channel).map(|channel| &channel.topic)
     }
 
     fn resolve_user_attributes<'a>(&'a self, channel: &str, user: &User) -> Option<&'a User> {
-        self.chanmap
-            .get(channel)
-            .and_then(|channel| channel.users.get(user))
+        let user = user.clone().with_casemapping(self.casemapping());
+
+        self.channel(channel)
+            .and_then(|channel| channel.users.get(&user))
     }
 
     pub fn users<'a>(&'a self, channel: &str) -> &'a [User] {
         self.users
-            .get(channel)
+            .get(&self.casemapping().normalize(channel))
             .map(Vec::as_slice)
             .unwrap_or_default()
     }
@@ -784,15 +1218,104 @@ impl Client {
             .collect()
     }
 
+    fn reply_to_ctcp(&mut self, user: &User, query: &ctcp::Message) {
+        if !self.config.ctcp.replies_to(&query.command) {
+            return;
+        }
+
+        if !self.ctcp_replies.allow(Instant::now()) {
+            log::debug!(
+                "[{}] not replying to CTCP {} from {}, too many queries",
+                self.server,
+                query.command,
+                user.nickname()
+            );
+            return;
+        }
+
+        let params = match &query.command {
+            ctcp::Command::ClientInfo => Some(
+                [ctcp::Command::Action, ctcp::Command::Dcc]
+                    .iter()
+                    .chain(
+                        ctcp::Command::REPLIES
+                            .iter()
+                            .filter(|command| self.config.ctcp.replies_to(command)),
+                    )
+                    .map(ctcp::Command::as_str)
+                    .join(" "),
+            ),
+            ctcp::Command::Ping => query.params.map(String::from),
+            ctcp::Command::Source => Some(environment::SOURCE_WEBSITE.to_string()),
+            ctcp::Command::Time => Some(Utc::now().to_rfc2822()),
+            ctcp::Command::Version => Some(format!("Halloy {}", environment::formatted_version())),
+            ctcp::Command::Action | ctcp::Command::Dcc | ctcp::Command::Unknown(_) => return,
+        };
+
+        log::debug!(
+            "[{}] replying to CTCP {} from {}",
+            self.server,
+            query.command,
+            user.nickname()
+        );
+
+        let _ = self.handle.unbounded_send(ctcp::response_message(
+            &query.command,
+            user.nickname().to_string(),
+            params,
+        ));
+    }
+
+    pub fn isupport(&self) -> &ISupport {
+        &self.isupport
+    }
+
+    pub fn casemapping(&self) -> CaseMap {
+        self.isupport.casemapping()
+    }
+
     pub fn nickname(&self) -> NickRef {
         // TODO: Fallback nicks
-        NickRef::from(
-            self.resolved_nick
-                .as_deref()
-                .unwrap_or(&self.config.nickname),
+        self.resolved_nick
+            .as_ref()
+            .unwrap_or(&self.default_nick)
+            .as_nickref()
+    }
+
+    /// Our own user, including the `user@host` the server relays our
+    /// messages with, if it's been seen yet
+    pub fn our_user(&self) -> User {
+        User::from(self.nickname().to_owned()).with_hostmask(
+            self.resolved_username.clone(),
+            self.resolved_hostname.clone(),
         )
     }
 
+    pub fn pinged(&mut self) {
+        self.ping_sent.get_or_insert_with(Instant::now);
+    }
+
+    pub fn ponged(&mut self, round_trip: Duration) {
+        self.last_round_trip = Some(round_trip);
+        self.ping_sent = None;
+    }
+
+    pub fn lag(&self) -> Option<Lag> {
+        Lag::new(
+            self.last_round_trip,
+            self.ping_sent.map(|sent| sent.elapsed()),
+            Duration::from_secs(self.config.ping_timeout),
+        )
+    }
+
+    pub fn queued(&mut self, queued: usize) {
+        self.queued = queued;
+    }
+
+    pub fn queue_len(&self) -> usize {
+        self.queued
+    }
+
     pub fn tick(&mut self, now: Instant) {
         match self.highlight_blackout {
             HighlightBlackout::Blackout(instant) => {
@@ -821,7 +1344,7 @@ impl Client {
             };
 
             if let Some(request) = request {
-                let _ = self.handle.try_send(command!("WHO", channel));
+                let _ = self.handle.unbounded_send(command!("WHO", channel));
                 state.last_who = Some(WhoStatus::Requested(Instant::now()));
                 log::debug!(
                     "[{}] {channel} - WHO {}",
@@ -836,6 +1359,41 @@ impl Client {
     }
 }
 
+/// Round-trip time to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lag {
+    pub duration: Duration,
+    /// Lagging enough for the connection to likely time out
+    pub is_degraded: bool,
+}
+
+impl Lag {
+    /// Lag of the last round trip, or of the unanswered ping once it has
+    /// been waited on for longer
+    fn new(
+        last_round_trip: Option<Duration>,
+        waiting: Option<Duration>,
+        ping_timeout: Duration,
+    ) -> Option<Self> {
+        let duration = last_round_trip.into_iter().chain(waiting).max()?;
+
+        Some(Self {
+            duration,
+            is_degraded: duration >= DEGRADED_LAG.min(ping_timeout / 2),
+        })
+    }
+}
+
+impl fmt::Display for Lag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.duration < Duration::from_secs(1) {
+            write!(f, "{}ms", self.duration.as_millis())
+        } else {
+            write!(f, "{:.1}s", self.duration.as_secs_f32())
+        }
+    }
+}
+
 #[derive(Debug)]
 enum HighlightBlackout {
     Blackout(Instant),
@@ -852,30 +1410,79 @@ impl HighlightBlackout {
 }
 
 #[derive(Debug, Default)]
-pub struct Map(BTreeMap<Server, State>);
+pub struct Map {
+    states: BTreeMap<Server, State>,
+    controllers: BTreeMap<Server, stream::Controller>,
+}
 
 impl Map {
+    pub fn set_controller(&mut self, server: Server, controller: stream::Controller) {
+        self.controllers.insert(server, controller);
+    }
+
+    /// Connects to `server` right away, dropping the current connection
+    pub fn connect(&mut self, server: &Server) {
+        if let Some(controller) = self.controllers.get(server) {
+            controller.connect();
+
+            if let Some(state @ State::Offline) = self.states.get_mut(server) {
+                *state = State::Disconnected;
+            }
+        }
+    }
+
+    /// Disconnects from `server` until reconnected
+    pub fn disconnect(&self, server: &Server) {
+        if let Some(controller) = self.controllers.get(server) {
+            controller.disconnect();
+        }
+    }
+
+    /// Reconnects to `server` w/ its edited `config`
+    pub fn configure(&self, server: &Server, config: config::Server) {
+        if let Some(controller) = self.controllers.get(server) {
+            controller.configure(config);
+        }
+    }
+
+    /// Applies the edited `config` of `server` w/o reconnecting
+    pub fn update_config(&mut self, server: &Server, config: config::Server) {
+        if let Some(State::Ready(client)) = self.states.get_mut(server) {
+            client.config = config.clone();
+        }
+
+        if let Some(controller) = self.controllers.get(server) {
+            controller.update(config);
+        }
+    }
+
     pub fn disconnected(&mut self, server: Server) {
-        self.0.insert(server, State::Disconnected);
+        self.states.insert(server, State::Disconnected);
+    }
+
+    pub fn offline(&mut self, server: Server) {
+        self.states.insert(server, State::Offline);
     }
 
     pub fn ready(&mut self, server: Server, client: Client) {
-        self.0.insert(server, State::Ready(client));
+        self.states.insert(server, State::Ready(client));
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.states.is_empty()
     }
 
     pub fn remove(&mut self, server: &Server) -> Option<Client> {
-        self.0.remove(server).and_then(|state| match state {
-            State::Disconnected => None,
+        self.controllers.remove(server);
+
+        self.states.remove(server).and_then(|state| match state {
+            State::Disconnected | State::Offline => None,
             State::Ready(client) => Some(client),
         })
     }
 
     pub fn client(&self, server: &Server) -> Option<&Client> {
-        if let Some(State::Ready(client)) = self.0.get(server) {
+        if let Some(State::Ready(client)) = self.states.get(server) {
             Some(client)
         } else {
             None
@@ -883,7 +1490,7 @@ impl Map {
     }
 
     pub fn client_mut(&mut self, server: &Server) -> Option<&mut Client> {
-        if let Some(State::Ready(client)) = self.0.get_mut(server) {
+        if let Some(State::Ready(client)) = self.states.get_mut(server) {
             Some(client)
         } else {
             None
@@ -894,14 +1501,34 @@ impl Map {
         self.client(server).map(Client::nickname)
     }
 
+    pub fn our_user(&self, server: &Server) -> Option<User> {
+        self.client(server).map(Client::our_user)
+    }
+
     pub fn receive(&mut self, server: &Server, message: message::Encoded) -> Vec<Event> {
-        self.client_mut(server)
+        let events = self
+            .client_mut(server)
             .map(|client| client.receive(message))
-            .unwrap_or_default()
+            .unwrap_or_default();
+
+        // Quitting alone would reconnect, only to fail authenticating again
+        if events.iter().any(|event| {
+            matches!(
+                event,
+                Event::Broadcast(Broadcast::SaslFailed {
+                    disconnect: true,
+                    ..
+                })
+            )
+        }) {
+            self.disconnect(server);
+        }
+
+        events
     }
 
     pub fn sync(&mut self, server: &Server) {
-        if let Some(State::Ready(client)) = self.0.get_mut(server) {
+        if let Some(State::Ready(client)) = self.states.get_mut(server) {
             client.sync();
         }
     }
@@ -912,6 +1539,23 @@ impl Map {
         }
     }
 
+    pub fn request_chathistory(
+        &mut self,
+        server: &Server,
+        kind: &history::Kind,
+        since: Option<DateTime<Utc>>,
+    ) {
+        let target = match kind {
+            history::Kind::Server => return,
+            history::Kind::Channel(channel) => channel.as_str(),
+            history::Kind::Query(nick) => nick.as_ref(),
+        };
+
+        if let Some(client) = self.client_mut(server) {
+            client.request_chathistory(target, since);
+        }
+    }
+
     pub fn resolve_user_attributes<'a>(
         &'a self,
         server: &Server,
@@ -946,12 +1590,33 @@ impl Map {
             .unwrap_or_default()
     }
 
+    pub fn get_isupport<'a>(&'a self, server: &Server) -> Option<&'a ISupport> {
+        self.client(server).map(|client| client.isupport())
+    }
+
+    pub fn get_casemapping(&self, server: &Server) -> CaseMap {
+        self.client(server)
+            .map(Client::casemapping)
+            .unwrap_or_default()
+    }
+
+    pub fn get_chantypes<'a>(&'a self, server: &Server) -> &'a [char] {
+        self.client(server)
+            .map(|client| client.isupport().chantypes())
+            .unwrap_or(isupport::DEFAULT_CHANTYPES)
+    }
+
     pub fn get_server_handle(&self, server: &Server) -> Option<&server::Handle> {
         self.client(server).map(|client| &client.handle)
     }
 
+    pub fn get_server_proxy(&self, server: &Server) -> Option<&config::server::Proxy> {
+        self.client(server)
+            .and_then(|client| client.config.proxy.as_ref())
+    }
+
     pub fn connected_servers(&self) -> impl Iterator<Item = &Server> {
-        self.0.iter().filter_map(|(server, state)| {
+        self.states.iter().filter_map(|(server, state)| {
             if let State::Ready(_) = state {
                 Some(server)
             } else {
@@ -961,21 +1626,50 @@ impl Map {
     }
 
     pub fn iter(&self) -> std::collections::btree_map::Iter<Server, State> {
-        self.0.iter()
+        self.states.iter()
+    }
+
+    pub fn pinged(&mut self, server: &Server) {
+        if let Some(client) = self.client_mut(server) {
+            client.pinged();
+        }
+    }
+
+    pub fn ponged(&mut self, server: &Server, round_trip: Duration) {
+        if let Some(client) = self.client_mut(server) {
+            client.ponged(round_trip);
+        }
+    }
+
+    /// Lag of the connection to `server`, once a ping has been sent
+    pub fn lag(&self, server: &Server) -> Option<Lag> {
+        self.client(server).and_then(Client::lag)
+    }
+
+    pub fn queued(&mut self, server: &Server, queued: usize) {
+        if let Some(client) = self.client_mut(server) {
+            client.queued(queued);
+        }
+    }
+
+    /// # of messages waiting to be sent to `server`
+    pub fn queue_len(&self, server: &Server) -> usize {
+        self.client(server).map_or(0, Client::queue_len)
     }
 
     pub fn status(&self, server: &Server) -> Status {
-        self.0
+        self.states
             .get(server)
             .map(|s| match s {
                 State::Disconnected => Status::Disconnected,
+                State::Offline => Status::Offline,
                 State::Ready(_) => Status::Connected,
             })
             .unwrap_or(Status::Unavailable)
     }
 
     pub fn tick(&mut self, now: Instant) {
-        self.0.values_mut().for_each(|client| {
+        self.states.values_mut().for_each(|client| {
             if let State::Ready(client) = client {
                 client.tick(now);
             }
@@ -1014,6 +1708,8 @@ impl Context {
 pub struct Batch {
     context: Option<Context>,
     events: Vec<Event>,
+    /// Target of the `chathistory` batch this is part of
+    chathistory: Option<String>,
 }
 
 impl Batch {
@@ -1021,6 +1717,7 @@ impl Batch {
         Self {
             context,
             events: vec![],
+            chathistory: None,
         }
     }
 }
@@ -1069,11 +1766,21 @@ enum RegistrationStep {
 
 #[derive(Debug, Default)]
 pub struct Channel {
+    pub name: String,
     pub users: HashSet<User>,
     pub last_who: Option<WhoStatus>,
     pub topic: Topic,
 }
 
+impl Channel {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            ..Self::default()
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct Topic {
     pub text: Option<String>,
@@ -1137,3 +1844,34 @@ fn group_joins(config: &config::Server) -> impl Iterator<Item = proto::Message>
 
     joins_without_keys.chain(joins_with_keys)
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::Lag;
+
+    #[test]
+    fn lag() {
+        let ms = Duration::from_millis;
+        let timeout = Duration::from_secs(20);
+
+        assert_eq!(Lag::new(None, None, timeout), None);
+
+        let lag = Lag::new(Some(ms(120)), Some(ms(10)), timeout).unwrap();
+        assert_eq!(lag.duration, ms(120));
+        assert!(!lag.is_degraded);
+        assert_eq!(lag.to_string(), "120ms");
+
+        // Degraded while waiting on the pong, before timing out
+        let lag = Lag::new(Some(ms(120)), Some(ms(6000)), timeout).unwrap();
+        assert!(lag.is_degraded);
+        assert_eq!(lag.to_string(), "6.0s");
+
+        assert!(
+            Lag::new(Some(ms(1500)), None, Duration::from_secs(2))
+                .unwrap()
+                .is_degraded
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! SASL authentication, w/ the mechanism picked from those the server
+//! supports & payloads sent in chunks of 400 bytes.
+
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine;
+use hmac::digest::core_api::BlockSizeUser;
+use hmac::digest::Digest;
+use hmac::{Mac, SimpleHmac};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Sha256, Sha512};
+
+use crate::config::server::Sasl;
+
+/// Maximum length of an AUTHENTICATE param, longer payloads are split
+const CHUNK_SIZE: usize = 400;
+/// Maximum PBKDF2 iterations a server can ask for, as the password is
+/// salted while handling its challenge
+const MAX_ITERATIONS: u32 = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mechanism {
+    Plain,
+    External,
+    ScramSha256,
+    ScramSha512,
+}
+
+impl Mechanism {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mechanism::Plain => "PLAIN",
+            Mechanism::External => "EXTERNAL",
+            Mechanism::ScramSha256 => "SCRAM-SHA-256",
+            Mechanism::ScramSha512 => "SCRAM-SHA-512",
+        }
+    }
+
+    /// Mechanisms which can authenticate w/ `config`, most preferred first
+    fn candidates(config: &Sasl) -> &'static [Mechanism] {
+        match config {
+            Sasl::Plain { .. } => &[Mechanism::Plain],
+            Sasl::External { .. } => &[Mechanism::External],
+            Sasl::Scram { .. } => &[Mechanism::ScramSha512, Mechanism::ScramSha256],
+        }
+    }
+}
+
+impl fmt::Display for Mechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The most preferred mechanism for `config` which hasn't been `tried`
+/// & is `supported` by the server. Servers which don't list their
+/// mechanisms are assumed to support all of them.
+pub fn select(
+    config: &Sasl,
+    supported: Option<&[String]>,
+    tried: &[Mechanism],
+) -> Option<Mechanism> {
+    Mechanism::candidates(config)
+        .iter()
+        .copied()
+        .filter(|mechanism| !tried.contains(mechanism))
+        .find(|mechanism| match supported {
+            Some(supported) => supported
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(mechanism.name())),
+            None => true,
+        })
+}
+
+/// Mechanisms listed in the value of the `sasl` capability or the
+/// RPL_SASLMECHS numeric
+pub fn mechanisms(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_uppercase)
+        .collect()
+}
+
+/// An authentication exchange w/ the server
+#[derive(Debug)]
+pub struct Session {
+    mechanism: Mechanism,
+    state: State,
+    /// Chunks of the challenge being received
+    received: String,
+}
+
+#[derive(Debug)]
+enum State {
+    Initial,
+    Scram(Scram),
+    Done,
+}
+
+impl Session {
+    pub fn new(mechanism: Mechanism) -> Self {
+        Self {
+            mechanism,
+            state: State::Initial,
+            received: String::new(),
+        }
+    }
+
+    /// Whether the server accepted the mechanism & sent a challenge
+    pub fn has_started(&self) -> bool {
+        !matches!(self.state, State::Initial)
+    }
+
+    /// Handles the param of an AUTHENTICATE from the server, returning the
+    /// params to reply with once the whole challenge is received
+    pub fn receive(&mut self, config: &Sasl, param: &str) -> Result<Vec<String>, Error> {
+        if param != "+" {
+            self.received.push_str(param);
+
+            if param.len() == CHUNK_SIZE {
+                return Ok(vec![]);
+            }
+        }
+
+        let challenge = BASE64.decode(std::mem::take(&mut self.received))?;
+        let response = self.respond(config, &challenge)?;
+
+        Ok(chunks(&response))
+    }
+
+    fn respond(&mut self, config: &Sasl, challenge: &[u8]) -> Result<Vec<u8>, Error> {
+        let state = std::mem::replace(&mut self.state, State::Done);
+
+        match (self.mechanism, state) {
+            (Mechanism::External, State::Initial) => Ok(vec![]),
+            (Mechanism::Plain, State::Initial) => {
+                let username = config.username().unwrap_or_default();
+                let password = config.password().ok_or(Error::MissingPassword)?;
+
+                Ok(format!("{username}\x00{username}\x00{password}").into_bytes())
+            }
+            (Mechanism::ScramSha256 | Mechanism::ScramSha512, State::Initial) => {
+                let nonce = rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(24)
+                    .map(char::from)
+                    .collect();
+                let scram = Scram::new(config.username().unwrap_or_default(), nonce);
+                let response = scram.client_first();
+
+                self.state = State::Scram(scram);
+
+                Ok(response.into_bytes())
+            }
+            (mechanism, State::Scram(mut scram)) => {
+                let challenge =
+                    String::from_utf8(challenge.to_vec()).map_err(|_| Error::InvalidChallenge)?;
+
+                match scram.step {
+                    ScramStep::ServerFirst => {
+                        let password = config.password().ok_or(Error::MissingPassword)?;
+                        let response = match mechanism {
+                            Mechanism::ScramSha512 => {
+                                scram.client_final::<Sha512>(password, &challenge)?
+                            }
+                            _ => scram.client_final::<Sha256>(password, &challenge)?,
+                        };
+
+                        self.state = State::Scram(scram);
+
+                        Ok(response.into_bytes())
+                    }
+                    ScramStep::ServerFinal(ref signature) => {
+                        verify_server_final(signature, &challenge)?;
+
+                        Ok(vec![])
+                    }
+                }
+            }
+            (_, State::Done) => Err(Error::UnexpectedChallenge),
+        }
+    }
+}
+
+/// AUTHENTICATE params of `payload`, base64 encoded & split in chunks. A
+/// payload which is empty or ends on a full chunk is terminated w/ "+".
+pub fn chunks(payload: &[u8]) -> Vec<String> {
+    let encoded = BASE64.encode(payload);
+
+    let mut chunks = encoded
+        .as_bytes()
+        .chunks(CHUNK_SIZE)
+        // Base64 is ASCII
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>();
+
+    if chunks.last().map_or(0, String::len) % CHUNK_SIZE == 0 {
+        chunks.push("+".to_string());
+    }
+
+    chunks
+}
+
+/// A SCRAM exchange, as described in RFC 5802
+#[derive(Debug)]
+struct Scram {
+    username: String,
+    nonce: String,
+    step: ScramStep,
+}
+
+#[derive(Debug)]
+enum ScramStep {
+    ServerFirst,
+    /// Awaiting the server's signature, to verify it knows the password
+    ServerFinal(Vec<u8>),
+}
+
+/// No channel binding or authorization identity
+const GS2_HEADER: &str = "n,,";
+
+impl Scram {
+    fn new(username: &str, nonce: String) -> Self {
+        Self {
+            username: username.replace('=', "=3D").replace(',', "=2C"),
+            nonce,
+            step: ScramStep::ServerFirst,
+        }
+    }
+
+    fn client_first_bare(&self) -> String {
+        format!("n={},r={}", self.username, self.nonce)
+    }
+
+    fn client_first(&self) -> String {
+        format!("{GS2_HEADER}{}", self.client_first_bare())
+    }
+
+    fn client_final<D>(&mut self, password: &str, server_first: &str) -> Result<String, Error>
+    where
+        D: Digest + BlockSizeUser + Clone + Sync,
+    {
+        let attribute = |name: char| {
+            server_first
+                .split(',')
+                .find_map(|attribute| attribute.strip_prefix(name)?.strip_prefix('='))
+        };
+
+        if let Some(error) = attribute('e') {
+            return Err(Error::Server(error.to_string()));
+        }
+
+        let nonce = attribute('r').ok_or(Error::InvalidChallenge)?;
+        let salt = BASE64.decode(attribute('s').ok_or(Error::InvalidChallenge)?)?;
+        let iterations = attribute('i')
+            .and_then(|i| i.parse::<u32>().ok())
+            .filter(|i| (1..=MAX_ITERATIONS).contains(i))
+            .ok_or(Error::InvalidChallenge)?;
+
+        // The server's nonce must extend ours
+        if !nonce.starts_with(&self.nonce) || nonce.len() == self.nonce.len() {
+            return Err(Error::InvalidChallenge);
+        }
+
+        let mut salted_password = vec![0; <D as Digest>::output_size()];
+        pbkdf2::pbkdf2::<SimpleHmac<D>>(
+            password.as_bytes(),
+            &salt,
+            iterations,
+            &mut salted_password,
+        )
+        .map_err(|_| Error::InvalidChallenge)?;
+
+        let client_key = hmac::<D>(&salted_password, b"Client Key");
+        let stored_key = D::digest(&client_key);
+        let server_key = hmac::<D>(&salted_password, b"Server Key");
+
+        let client_final_without_proof = format!("c={},r={nonce}", BASE64.encode(GS2_HEADER));
+        let auth_message = format!(
+            "{},{server_first},{client_final_without_proof}",
+            self.client_first_bare()
+        );
+
+        let client_signature = hmac::<D>(&stored_key, auth_message.as_bytes());
+        let proof = client_key
+            .iter()
+            .zip(client_signature)
+            .map(|(key, signature)| key ^ signature)
+            .collect::<Vec<_>>();
+
+        self.step = ScramStep::ServerFinal(hmac::<D>(&server_key, auth_message.as_bytes()));
+
+        Ok(format!(
+            "{client_final_without_proof},p={}",
+            BASE64.encode(proof)
+        ))
+    }
+}
+
+fn verify_server_final(signature: &[u8], server_final: &str) -> Result<(), Error> {
+    for attribute in server_final.split(',') {
+        if let Some(error) = attribute.strip_prefix("e=") {
+            return Err(Error::Server(error.to_string()));
+        }
+
+        if let Some(verifier) = attribute.strip_prefix("v=") {
+            return if BASE64.decode(verifier)? == signature {
+                Ok(())
+            } else {
+                Err(Error::ServerSignature)
+            };
+        }
+    }
+
+    Err(Error::InvalidChallenge)
+}
+
+fn hmac<D>(key: &[u8], data: &[u8]) -> Vec<u8>
+where
+    D: Digest + BlockSizeUser + Clone,
+{
+    let mut mac = <SimpleHmac<D> as Mac>::new_from_slice(key).expect("any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no password is set")]
+    MissingPassword,
+    #[error("unexpected challenge from server")]
+    UnexpectedChallenge,
+    #[error("invalid challenge from server")]
+    InvalidChallenge,
+    #[error("invalid base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("server failed to prove it knows the password")]
+    ServerSignature,
+    #[error("{0}")]
+    Server(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scram_sha_256() {
+        // Test vector from RFC 7677
+        let mut scram = Scram::new("user", "rOprNGfwEbeRWgbNEkqO".into());
+
+        assert_eq!(scram.client_first(), "n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+
+        let client_final = scram
+            .client_final::<Sha256>(
+                "pencil",
+                "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+            )
+            .unwrap();
+
+        assert_eq!(
+            client_final,
+            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+        );
+
+        let ScramStep::ServerFinal(signature) = &scram.step else {
+            panic!("expected server final");
+        };
+
+        assert!(
+            verify_server_final(signature, "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+                .is_ok()
+        );
+        assert!(matches!(
+            verify_server_final(signature, "v=AAAA"),
+            Err(Error::ServerSignature)
+        ));
+
+        let mut scram = Scram::new("user", "rOprNGfwEbeRWgbNEkqO".into());
+        assert!(matches!(
+            scram.client_final::<Sha256>(
+                "pencil",
+                "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=100000000",
+            ),
+            Err(Error::InvalidChallenge)
+        ));
+    }
+
+    #[test]
+    fn chunked_payloads() {
+        assert_eq!(chunks(b""), vec!["+"]);
+        assert_eq!(chunks(b"abc"), vec!["YWJj"]);
+
+        // 300 bytes encode to exactly 400
+        let full = chunks(&[0; 300]);
+        assert_eq!(full.len(), 2);
+        assert_eq!(full[0].len(), 400);
+        assert_eq!(full[1], "+");
+
+        let split = chunks(&[0; 400]);
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].len(), 400);
+        assert!(split[1].len() < 400);
+    }
+
+    #[test]
+    fn select_mechanisms() {
+        let scram = Sasl::Scram {
+            username: "user".into(),
+            password: Some("pencil".into()),
+            password_file: None,
+        };
+        let supported = mechanisms("PLAIN,scram-sha-256");
+
+        assert_eq!(
+            select(&scram, Some(&supported), &[]),
+            Some(Mechanism::ScramSha256)
+        );
+        assert_eq!(select(&scram, None, &[]), Some(Mechanism::ScramSha512));
+        assert_eq!(
+            select(&scram, None, &[Mechanism::ScramSha512]),
+            Some(Mechanism::ScramSha256)
+        );
+        assert_eq!(
+            select(
+                &Sasl::External {
+                    cert: "cert.pem".into(),
+                    key: None
+                },
+                Some(&supported),
+                &[]
+            ),
+            None
+        );
+    }
+}
@@ -3,7 +3,8 @@ use std::str::FromStr;
 use irc::proto;
 use itertools::Itertools;
 
-use crate::Buffer;
+use crate::time::Posix;
+use crate::{ctcp, Buffer};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Kind {
@@ -12,12 +13,14 @@ pub enum Kind {
     Nick,
     Quit,
     Msg,
+    Notice,
     Me,
     Whois,
     Part,
     Topic,
     Kick,
     Mode,
+    Ctcp,
     Raw,
 }
 
@@ -31,12 +34,14 @@ impl FromStr for Kind {
             "nick" => Ok(Kind::Nick),
             "quit" => Ok(Kind::Quit),
             "msg" => Ok(Kind::Msg),
+            "notice" => Ok(Kind::Notice),
             "me" => Ok(Kind::Me),
             "whois" => Ok(Kind::Whois),
             "part" => Ok(Kind::Part),
             "topic" => Ok(Kind::Topic),
             "kick" => Ok(Kind::Kick),
             "mode" => Ok(Kind::Mode),
+            "ctcp" => Ok(Kind::Ctcp),
             "raw" => Ok(Kind::Raw),
             _ => Err(()),
         }
@@ -50,12 +55,14 @@ pub enum Command {
     Nick(String),
     Quit(Option<String>),
     Msg(String, String),
+    Notice(String, String),
     Me(String, String),
     Whois(Option<String>, String),
     Part(String, Option<String>),
     Topic(String, Option<String>),
     Kick(String, String, Option<String>),
     Mode(String, Option<String>, Vec<String>),
+    Ctcp(ctcp::Command, String, Option<String>),
     Raw(String, Vec<String>),
     Unknown(String, Vec<String>),
 }
@@ -90,6 +97,9 @@ pub fn parse(s: &str, buffer: Option<&Buffer>) -> Result<Command, Error> {
             Kind::Msg => {
                 validated::<2, 0, true>(args, |[target, msg], []| Command::Msg(target, msg))
             }
+            Kind::Notice => {
+                validated::<2, 0, true>(args, |[target, msg], []| Command::Notice(target, msg))
+            }
             Kind::Me => {
                 if let Some(target) = buffer.and_then(|b| b.target()) {
                     validated::<1, 0, true>(args, |[text], _| Command::Me(target, text))
@@ -120,6 +130,19 @@ pub fn parse(s: &str, buffer: Option<&Buffer>) -> Result<Command, Error> {
                     users.iter().map(|s| s.to_string()).collect(),
                 ))
             }
+            Kind::Ctcp => validated::<2, 1, true>(args, |[target, command], [params]| {
+                let command = ctcp::Command::from(command.as_str());
+
+                // Include the time sent so the reply shows the round trip time
+                let params = match command {
+                    ctcp::Command::Ping => {
+                        params.or_else(|| Some(Posix::now().as_nanos().to_string()))
+                    }
+                    _ => params,
+                };
+
+                Command::Ctcp(command, target, params)
+            }),
             Kind::Raw => {
                 let (cmd, args) = args.split_first().ok_or(Error::MissingCommand)?;
 
@@ -182,14 +205,18 @@ impl TryFrom<Command> for proto::Command {
             Command::Nick(nick) => proto::Command::NICK(nick),
             Command::Quit(comment) => proto::Command::QUIT(comment),
             Command::Msg(target, msg) => proto::Command::PRIVMSG(target, msg),
+            Command::Notice(target, msg) => proto::Command::NOTICE(target, msg),
             Command::Me(target, text) => {
-                proto::Command::PRIVMSG(target, format!("\u{1}ACTION {text}\u{1}"))
+                proto::Command::PRIVMSG(target, ctcp::encode(&ctcp::Command::Action, Some(text)))
             }
             Command::Whois(channel, user) => proto::Command::WHOIS(channel, user),
             Command::Part(chanlist, reason) => proto::Command::PART(chanlist, reason),
             Command::Topic(channel, topic) => proto::Command::TOPIC(channel, topic),
             Command::Kick(channel, user, comment) => proto::Command::KICK(channel, user, comment),
             Command::Mode(channel, mode, users) => proto::Command::MODE(channel, mode, users),
+            Command::Ctcp(command, target, params) => {
+                proto::Command::PRIVMSG(target, ctcp::encode(&command, params))
+            }
             Command::Raw(command, args) => proto::Command::Unknown(command, args),
             Command::Unknown(command, args) => proto::Command::new(&command, args),
         })
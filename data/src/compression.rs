@@ -1,7 +1,8 @@
 use std::io;
 use std::io::prelude::*;
+use std::io::BufReader;
 
-use flate2::read::GzDecoder;
+use flate2::read::{GzDecoder, MultiGzDecoder};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use serde::de::DeserializeOwned;
@@ -23,6 +24,31 @@ pub fn decompress<T: DeserializeOwned>(data: &[u8]) -> Result<T, Error> {
     serde_json::from_slice(&bytes).map_err(Error::Decode)
 }
 
+/// Compresses `values` as newline delimited JSON, in a gzip member which can
+/// be appended to those already in a file
+pub fn compress_lines<T: Serialize>(values: &[T]) -> Result<Vec<u8>, Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+
+    for value in values {
+        let line = serde_json::to_vec(value).map_err(Error::Encode)?;
+        encoder.write_all(&line).map_err(Error::Compression)?;
+        encoder.write_all(b"\n").map_err(Error::Compression)?;
+    }
+
+    encoder.finish().map_err(Error::Compression)
+}
+
+/// Decompresses newline delimited JSON from all gzip members in `data`.
+/// Reading stops at a corrupt member, such as one cut short by a crash,
+/// and lines which fail to decode are skipped.
+pub fn decompress_lines<T: DeserializeOwned>(data: &[u8]) -> Vec<T> {
+    BufReader::new(MultiGzDecoder::new(data))
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("compression failed")]
@@ -34,3 +60,23 @@ pub enum Error {
     #[error("decoding failed")]
     Decode(serde_json::Error),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn appended_lines() {
+        let mut data = compress_lines(&["first", "second"]).unwrap();
+        data.extend(compress_lines(&["third"]).unwrap());
+
+        let partial = compress_lines(&["fourth"]).unwrap();
+        // Cut short while being written
+        data.extend(&partial[..partial.len() / 2]);
+
+        assert_eq!(
+            decompress_lines::<String>(&data),
+            ["first", "second", "third"]
+        );
+    }
+}
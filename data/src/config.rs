@@ -1,13 +1,19 @@
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use futures::channel::mpsc;
+use futures::never::Never;
+use futures::SinkExt;
 use rand::Rng;
 use serde::Deserialize;
 use thiserror::Error;
+use tokio::time;
 
 pub use self::buffer::Buffer;
 pub use self::channel::Channel;
 pub use self::file_transfer::FileTransfer;
+pub use self::history::History;
 pub use self::keys::Keyboard;
 pub use self::notification::{Notification, Notifications};
 pub use self::server::Server;
@@ -19,7 +25,9 @@ use crate::{environment, Theme};
 
 pub mod buffer;
 pub mod channel;
+pub mod edit;
 pub mod file_transfer;
+pub mod history;
 mod keys;
 pub mod notification;
 pub mod server;
@@ -27,6 +35,8 @@ pub mod sidebar;
 
 const CONFIG_TEMPLATE: &str = include_str!("../../config.toml");
 const DEFAULT_THEME_FILE_NAME: &str = "ferra.toml";
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Debug, Clone, Default)]
 pub struct Config {
@@ -39,6 +49,7 @@ pub struct Config {
     pub keyboard: Keyboard,
     pub notifications: Notifications,
     pub file_transfer: FileTransfer,
+    pub history: History,
     pub tooltips: bool,
 }
 
@@ -131,6 +142,8 @@ impl Config {
             pub notifications: Notifications,
             #[serde(default)]
             pub file_transfer: FileTransfer,
+            #[serde(default)]
+            pub history: History,
             #[serde(default = "default_tooltip")]
             pub tooltips: bool,
         }
@@ -148,6 +161,7 @@ impl Config {
             keyboard,
             notifications,
             file_transfer,
+            history,
             tooltips,
         } = toml::from_str(content.as_ref()).map_err(|e| Error::Parse(e.to_string()))?;
 
@@ -165,6 +179,7 @@ impl Config {
             keyboard,
             notifications,
             file_transfer,
+            history,
             tooltips,
         })
     }
@@ -244,6 +259,34 @@ impl Config {
     }
 }
 
+/// Sends the config each time the config file changes, or the error it
+/// failed to load with
+pub async fn watch(mut sender: mpsc::Sender<Result<Config, Error>>) -> Never {
+    let modified = || {
+        fs::metadata(Config::path())
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    let mut last_modified = modified();
+    let mut interval = time::interval(WATCH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let modified = modified();
+
+        if modified != last_modified {
+            last_modified = modified;
+
+            // Nothing to load once removed
+            if modified.is_some() {
+                let _ = sender.send(Config::load()).await;
+            }
+        }
+    }
+}
+
 pub fn create_themes_dir() {
     const CONTENT: &[u8] = include_bytes!("../../assets/themes/ferra.toml");
 
@@ -3,7 +3,7 @@ use serde::Deserialize;
 
 use super::Channel;
 use crate::{
-    buffer::{Color, Nickname, TextInput, Timestamp},
+    buffer::{Color, Nickname, TextFormatting, TextInput, Timestamp},
     message::source,
 };
 
@@ -19,6 +19,8 @@ pub struct Buffer {
     pub channel: Channel,
     #[serde(default)]
     pub server_messages: ServerMessages,
+    #[serde(default)]
+    pub text_formatting: TextFormatting,
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -40,6 +42,7 @@ impl ServerMessages {
             source::server::Kind::Part => Some(self.part),
             source::server::Kind::Quit => Some(self.quit),
             source::server::Kind::Join => Some(self.join),
+            source::server::Kind::CtcpQuery | source::server::Kind::CtcpReply => None,
         }
     }
 }
@@ -83,6 +86,7 @@ impl Default for Buffer {
             text_input: Default::default(),
             channel: Channel::default(),
             server_messages: Default::default(),
+            text_formatting: TextFormatting::default(),
         }
     }
 }
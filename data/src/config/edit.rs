@@ -0,0 +1,436 @@
+//! Edits of the servers in the config file, made from the app.
+//!
+//! Only the keys whose values were changed are written, so comments,
+//! formatting & other settings of the file are kept as they are.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use tokio::fs;
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+
+use crate::{config, server, Config};
+
+/// The settings of a server which can be edited from the app
+#[derive(Debug, Clone, Default)]
+pub struct Server {
+    pub name: String,
+    pub nickname: String,
+    pub server: String,
+    pub port: u16,
+    pub use_tls: bool,
+    pub sasl: Option<Sasl>,
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Sasl {
+    /// Passwords are only written when set, so a `password_file` is kept
+    /// when left empty
+    Plain {
+        username: String,
+        password: String,
+    },
+    Scram {
+        username: String,
+        password: String,
+    },
+    /// Certificate set in the config file, which is kept as is
+    External,
+}
+
+impl Server {
+    pub fn new(name: &server::Server, config: &config::Server) -> Self {
+        // Passwords read from a file aren't shown, so they aren't written
+        // to the config file
+        let password = |password: &Option<String>, password_file: &Option<String>| {
+            if password_file.is_some() {
+                String::new()
+            } else {
+                password.clone().unwrap_or_default()
+            }
+        };
+
+        let sasl = config.sasl.as_ref().map(|sasl| match sasl {
+            config::server::Sasl::Plain {
+                username,
+                password: pass,
+                password_file,
+            } => Sasl::Plain {
+                username: username.clone(),
+                password: password(pass, password_file),
+            },
+            config::server::Sasl::Scram {
+                username,
+                password: pass,
+                password_file,
+            } => Sasl::Scram {
+                username: username.clone(),
+                password: password(pass, password_file),
+            },
+            config::server::Sasl::External { .. } => Sasl::External,
+        });
+
+        Self {
+            name: name.to_string(),
+            nickname: config.nickname.clone(),
+            server: config.server.clone(),
+            port: config.port,
+            use_tls: config.use_tls,
+            sasl,
+            channels: config.channels.clone(),
+        }
+    }
+}
+
+/// Writes `edit` to the config file, adding the server if `is_new`, and
+/// returns its config as loaded from the file
+pub async fn save(edit: Server, is_new: bool) -> Result<config::Server, Error> {
+    let path = Config::path();
+
+    let content = fs::read_to_string(&path).await?;
+    let content = apply(&content, &edit, is_new)?;
+
+    // Only written once the edited config is known to load
+    let config = load(&content, &edit.name)?;
+
+    fs::write(&path, content).await?;
+
+    Ok(config)
+}
+
+fn apply(content: &str, edit: &Server, is_new: bool) -> Result<String, Error> {
+    if edit.name.trim().is_empty() {
+        return Err(Error::Empty("name"));
+    }
+    if edit.nickname.trim().is_empty() {
+        return Err(Error::Empty("nickname"));
+    }
+    if edit.server.trim().is_empty() {
+        return Err(Error::Empty("server"));
+    }
+
+    let mut document = content.parse::<DocumentMut>()?;
+
+    let servers = document
+        .entry("servers")
+        .or_insert_with(implicit_table)
+        .as_table_like_mut()
+        .ok_or_else(|| Error::NotTable("servers".into()))?;
+
+    if is_new && servers.contains_key(&edit.name) {
+        return Err(Error::Exists(edit.name.clone()));
+    }
+
+    let server = servers
+        .entry(&edit.name)
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| Error::NotTable(format!("servers.{}", edit.name)))?;
+
+    // Every key is written for a new server, or if the server doesn't load
+    let current = if is_new {
+        None
+    } else {
+        parse(content, &edit.name).ok()
+    };
+    let unchanged =
+        |is_same: &dyn Fn(&config::Server) -> bool| current.as_ref().is_some_and(is_same);
+
+    let nickname = edit.nickname.trim();
+    let hostname = edit.server.trim();
+    let channels = edit
+        .channels
+        .iter()
+        .map(|channel| channel.trim())
+        .filter(|channel| !channel.is_empty())
+        .collect::<Vec<_>>();
+
+    if !unchanged(&|config| config.nickname == nickname) {
+        set(server, "nickname", nickname);
+    }
+    if !unchanged(&|config| config.server == hostname) {
+        set(server, "server", hostname);
+    }
+    if !unchanged(&|config| config.port == edit.port) {
+        set(server, "port", i64::from(edit.port));
+    }
+    if !unchanged(&|config| config.use_tls == edit.use_tls) {
+        set(server, "use_tls", edit.use_tls);
+    }
+    if !unchanged(&|config| config.channels == channels) {
+        set(
+            server,
+            "channels",
+            channels.iter().copied().collect::<toml_edit::Array>(),
+        );
+    }
+
+    let sasl = match &edit.sasl {
+        None => {
+            server.remove("sasl");
+            None
+        }
+        Some(Sasl::External) => None,
+        Some(Sasl::Plain { username, password }) => Some(("plain", username, password)),
+        Some(Sasl::Scram { username, password }) => Some(("scram", username, password)),
+    };
+
+    if let Some((mechanism, username, password)) = sasl {
+        let sasl = server
+            .entry("sasl")
+            .or_insert_with(implicit_table)
+            .as_table_like_mut()
+            .ok_or_else(|| Error::NotTable(format!("servers.{}.sasl", edit.name)))?;
+
+        // An empty password keeps the mechanism's current one, if it has one
+        let has_password = sasl
+            .get(mechanism)
+            .and_then(Item::as_table_like)
+            .is_some_and(|table| {
+                table.contains_key("password") || table.contains_key("password_file")
+            });
+        if password.is_empty() && !has_password {
+            return Err(Error::Empty("password"));
+        }
+
+        // Only one mechanism can be configured
+        let others = sasl
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| key != mechanism)
+            .collect::<Vec<_>>();
+        for key in others {
+            sasl.remove(&key);
+        }
+
+        let sasl = sasl
+            .entry(mechanism)
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| Error::NotTable(format!("servers.{}.sasl.{mechanism}", edit.name)))?;
+
+        let username = username.trim();
+        if sasl.get("username").and_then(Item::as_str) != Some(username) {
+            set(sasl, "username", username);
+        }
+
+        if !password.is_empty() && sasl.get("password").and_then(Item::as_str) != Some(password) {
+            set(sasl, "password", password.as_str());
+            sasl.remove("password_file");
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+fn load(content: &str, name: &str) -> Result<config::Server, Error> {
+    let mut config = parse(content, name)?;
+
+    server::read_password_files(&mut config)?;
+
+    Ok(config)
+}
+
+/// The config of server `name` in `content`, w/o its password files read
+fn parse(content: &str, name: &str) -> Result<config::Server, Error> {
+    #[derive(Deserialize)]
+    struct Configuration {
+        servers: BTreeMap<String, config::Server>,
+    }
+
+    let Configuration { mut servers } =
+        toml::from_str(content).map_err(|e| config::Error::Parse(e.to_string()))?;
+
+    servers
+        .remove(name)
+        .ok_or_else(|| Error::NotTable(format!("servers.{name}")))
+}
+
+/// Sets `key`, keeping the comments & whitespace around its current value
+fn set(table: &mut dyn TableLike, key: &str, value: impl Into<Value>) {
+    let mut value = value.into();
+
+    match table.get_mut(key) {
+        Some(item) => {
+            if let Some(current) = item.as_value() {
+                *value.decor_mut() = current.decor().clone();
+            }
+
+            *item = Item::Value(value);
+        }
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
+
+/// A table which only has a header when it has keys of its own
+fn implicit_table() -> Item {
+    let mut table = Table::new();
+    table.set_implicit(true);
+
+    Item::Table(table)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0} can't be empty")]
+    Empty(&'static str),
+    #[error("a server named {0} already exists")]
+    Exists(String),
+    #[error("{0} isn't a table in the config file")]
+    NotTable(String),
+    #[error("config file is invalid: {0}")]
+    Toml(#[from] toml_edit::TomlError),
+    #[error(transparent)]
+    Config(#[from] config::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r##"# Halloy config
+theme = "ferra"
+
+[servers.liberachat]
+# Shown to others
+nickname = "halloy"  # my nick
+server = "irc.libera.chat"
+port = 6697
+channels = ["#halloy"]
+
+[servers.liberachat.sasl.plain]
+username = "halloy"
+password_file = "/run/secrets/libera"
+"##;
+
+    #[test]
+    fn edit_server() {
+        let edit = Server {
+            name: "liberachat".into(),
+            nickname: "halloy-user".into(),
+            server: "irc.libera.chat".into(),
+            port: 6697,
+            use_tls: true,
+            sasl: Some(Sasl::Plain {
+                username: "halloy-user".into(),
+                password: String::new(),
+            }),
+            channels: vec!["#halloy".into(), " #rust ".into()],
+        };
+
+        assert_eq!(
+            apply(CONFIG, &edit, false).unwrap(),
+            r##"# Halloy config
+theme = "ferra"
+
+[servers.liberachat]
+# Shown to others
+nickname = "halloy-user"  # my nick
+server = "irc.libera.chat"
+port = 6697
+channels = ["#halloy", "#rust"]
+
+[servers.liberachat.sasl.plain]
+username = "halloy-user"
+password_file = "/run/secrets/libera"
+"##
+        );
+
+        assert!(matches!(
+            apply(CONFIG, &edit, true),
+            Err(Error::Exists(name)) if name == "liberachat"
+        ));
+    }
+
+    #[test]
+    fn unchanged_server() {
+        let config = parse(CONFIG, "liberachat").unwrap();
+        let edit = Server::new(&server::Server::from("liberachat"), &config);
+
+        assert_eq!(apply(CONFIG, &edit, false).unwrap(), CONFIG);
+    }
+
+    #[test]
+    fn switch_sasl_mechanism() {
+        let mut edit = Server {
+            name: "liberachat".into(),
+            nickname: "halloy".into(),
+            server: "irc.libera.chat".into(),
+            port: 6697,
+            use_tls: true,
+            sasl: Some(Sasl::Scram {
+                username: "halloy".into(),
+                password: String::new(),
+            }),
+            channels: vec!["#halloy".into()],
+        };
+
+        // The password file of PLAIN isn't kept for SCRAM
+        assert!(matches!(
+            apply(CONFIG, &edit, false),
+            Err(Error::Empty("password"))
+        ));
+
+        edit.sasl = Some(Sasl::Scram {
+            username: "halloy".into(),
+            password: "secret".into(),
+        });
+
+        assert!(apply(CONFIG, &edit, false).unwrap().ends_with(
+            r##"
+[servers.liberachat.sasl.scram]
+username = "halloy"
+password = "secret"
+"##
+        ));
+    }
+
+    #[test]
+    fn add_server() {
+        let edit = Server {
+            name: "oftc".into(),
+            nickname: "halloy".into(),
+            server: "irc.oftc.net".into(),
+            port: 6697,
+            use_tls: true,
+            sasl: Some(Sasl::Scram {
+                username: "halloy".into(),
+                password: "secret".into(),
+            }),
+            channels: vec![],
+        };
+
+        let content = apply(CONFIG, &edit, true).unwrap();
+
+        assert!(content.starts_with(CONFIG));
+        assert!(content.ends_with(
+            r##"
+[servers.oftc]
+nickname = "halloy"
+server = "irc.oftc.net"
+port = 6697
+use_tls = true
+channels = []
+
+[servers.oftc.sasl.scram]
+username = "halloy"
+password = "secret"
+"##
+        ));
+
+        let config = load(&content, "oftc").unwrap();
+        assert_eq!(config.server, "irc.oftc.net");
+        assert_eq!(
+            config
+                .sasl
+                .as_ref()
+                .and_then(config::server::Sasl::password),
+            Some("secret")
+        );
+    }
+}
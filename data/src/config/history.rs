@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::environment;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct History {
+    /// Days to keep history for. History is kept forever if unset.
+    #[serde(default)]
+    pub max_age: Option<u32>,
+    #[serde(default)]
+    pub text_log: TextLog,
+}
+
+/// Plain-text logs, written alongside the history
+#[derive(Debug, Clone, Deserialize)]
+pub struct TextLog {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Directory to write logs to. Defaults to `logs` in the data directory.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// Line written for each message, with `{time}`, `{source}`, `{nick}`
+    /// and `{text}` replaced
+    #[serde(default = "default_template")]
+    pub template: String,
+    /// Format of `{time}`, using chrono's strftime syntax
+    #[serde(default = "default_timestamp")]
+    pub timestamp: String,
+}
+
+impl TextLog {
+    pub fn directory(&self) -> PathBuf {
+        self.directory
+            .clone()
+            .unwrap_or_else(|| environment::data_dir().join("logs"))
+    }
+}
+
+impl Default for TextLog {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            template: default_template(),
+            timestamp: default_timestamp(),
+        }
+    }
+}
+
+fn default_template() -> String {
+    "[{time}] {source} {text}".to_string()
+}
+
+fn default_timestamp() -> String {
+    "%H:%M:%S".to_string()
+}
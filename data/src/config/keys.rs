@@ -26,6 +26,22 @@ pub struct Keyboard {
     pub toggle_nick_list: KeyBind,
     #[serde(default = "KeyBind::command_bar")]
     pub command_bar: KeyBind,
+    #[serde(default = "KeyBind::format_bold")]
+    pub format_bold: KeyBind,
+    #[serde(default = "KeyBind::format_italic")]
+    pub format_italic: KeyBind,
+    #[serde(default = "KeyBind::format_underline")]
+    pub format_underline: KeyBind,
+    #[serde(default = "KeyBind::format_strikethrough")]
+    pub format_strikethrough: KeyBind,
+    #[serde(default = "KeyBind::format_monospace")]
+    pub format_monospace: KeyBind,
+    #[serde(default = "KeyBind::format_reverse")]
+    pub format_reverse: KeyBind,
+    #[serde(default = "KeyBind::format_color")]
+    pub format_color: KeyBind,
+    #[serde(default = "KeyBind::format_reset")]
+    pub format_reset: KeyBind,
 }
 
 impl Default for Keyboard {
@@ -42,12 +58,21 @@ impl Default for Keyboard {
             cycle_previous_buffer: KeyBind::cycle_previous_buffer(),
             toggle_nick_list: KeyBind::toggle_nick_list(),
             command_bar: KeyBind::command_bar(),
+            format_bold: KeyBind::format_bold(),
+            format_italic: KeyBind::format_italic(),
+            format_underline: KeyBind::format_underline(),
+            format_strikethrough: KeyBind::format_strikethrough(),
+            format_monospace: KeyBind::format_monospace(),
+            format_reverse: KeyBind::format_reverse(),
+            format_color: KeyBind::format_color(),
+            format_reset: KeyBind::format_reset(),
         }
     }
 }
 
 impl Keyboard {
     pub fn shortcuts(&self) -> Vec<Shortcut> {
+        use crate::formatting::Modifier;
         use crate::shortcut::Command::*;
 
         vec![
@@ -62,6 +87,17 @@ impl Keyboard {
             shortcut(self.cycle_previous_buffer.clone(), CyclePreviousBuffer),
             shortcut(self.toggle_nick_list.clone(), ToggleNicklist),
             shortcut(self.command_bar.clone(), CommandBar),
+            shortcut(self.format_bold.clone(), Format(Modifier::Bold)),
+            shortcut(self.format_italic.clone(), Format(Modifier::Italic)),
+            shortcut(self.format_underline.clone(), Format(Modifier::Underline)),
+            shortcut(
+                self.format_strikethrough.clone(),
+                Format(Modifier::Strikethrough),
+            ),
+            shortcut(self.format_monospace.clone(), Format(Modifier::Monospace)),
+            shortcut(self.format_reverse.clone(), Format(Modifier::Reverse)),
+            shortcut(self.format_color.clone(), Format(Modifier::Color)),
+            shortcut(self.format_reset.clone(), Format(Modifier::Reset)),
         ]
     }
 }
@@ -2,10 +2,13 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
-use irc::connection;
-use serde::{Deserialize, Deserializer};
+use irc::codec::Encoding;
+use irc::{connection, Codec};
+use serde::{de, Deserialize, Deserializer};
 
-#[derive(Debug, Clone, Default, Deserialize)]
+use crate::ctcp;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
 pub struct Server {
     /// The client's nickname.
     pub nickname: String,
@@ -27,6 +30,9 @@ pub struct Server {
     /// The port to connect on.
     #[serde(default = "default_port")]
     pub port: u16,
+    /// Other addresses of the network, tried in turn when connecting fails.
+    #[serde(default)]
+    pub addresses: Vec<Address>,
     /// The password to connect to the server.
     pub password: Option<String>,
     /// The file with the password to connect to the server.
@@ -37,6 +43,9 @@ pub struct Server {
     /// A mapping of channel names to keys for join-on-connect.
     #[serde(default)]
     pub channel_keys: HashMap<String, String>,
+    /// Whether to connect to the server when the application starts.
+    #[serde(default = "default_bool_true")]
+    pub autoconnect: bool,
     /// The amount of inactivity in seconds before the client will ping the server.
     #[serde(default = "default_ping_time")]
     pub ping_time: u64,
@@ -44,8 +53,12 @@ pub struct Server {
     #[serde(default = "default_ping_timeout")]
     pub ping_timeout: u64,
     /// The amount of time in seconds before attempting to reconnect to the server when disconnected.
+    /// Doubled after each failed attempt, up to `reconnect_delay_max`.
     #[serde(default = "default_reconnect_delay")]
     pub reconnect_delay: u64,
+    /// The maximum amount of time in seconds between attempts to reconnect to the server.
+    #[serde(default = "default_reconnect_delay_max")]
+    pub reconnect_delay_max: u64,
     /// Whether the client should use NickServ GHOST to reclaim its primary nickname if it is in
     /// use. This has no effect if `nick_password` is not set.
     #[serde(default)]
@@ -61,14 +74,19 @@ pub struct Server {
     /// Whether or not to use TLS.
     /// Clients will automatically panic if this is enabled without TLS support.
     #[serde(default = "default_use_tls")]
-    use_tls: bool,
+    pub use_tls: bool,
     /// On `true`, all certificate validations are skipped. Defaults to `false`.
     #[serde(default)]
     dangerously_accept_invalid_certs: bool,
     /// The path to the root TLS certificate for this server in PEM format.
     root_cert_path: Option<PathBuf>,
+    /// Proxy to connect to the server through.
+    pub proxy: Option<Proxy>,
     /// Sasl authentication
     pub sasl: Option<Sasl>,
+    /// What to do when SASL authentication fails.
+    #[serde(default)]
+    pub sasl_failure: SaslFailure,
     /// Commands which are executed once connected.
     #[serde(default)]
     pub on_connect: Vec<String>,
@@ -84,14 +102,60 @@ pub struct Server {
         deserialize_with = "deserialize_duration_from_u64"
     )]
     pub who_retry_interval: Duration,
+    /// The amount of messages which can be sent at once before being paced by `send_rate`.
+    #[serde(default = "default_send_burst")]
+    pub send_burst: u16,
+    /// The amount of time in milliseconds between messages once `send_burst` is used up.
+    #[serde(
+        default = "default_send_rate",
+        deserialize_with = "deserialize_duration_from_millis"
+    )]
+    pub send_rate: Duration,
+    /// Automatic replies to CTCP queries.
+    #[serde(default)]
+    pub ctcp: Ctcp,
+    /// Encoding used to decode incoming messages which aren't valid UTF-8.
+    #[serde(
+        default = "default_encoding",
+        deserialize_with = "deserialize_encoding"
+    )]
+    pub encoding: Encoding,
+    /// Encoding used for outgoing messages.
+    #[serde(default, deserialize_with = "deserialize_encoding")]
+    pub outgoing_encoding: Encoding,
 }
 
 impl Server {
-    pub fn connection(&self) -> connection::Config {
-        let security = if self.use_tls {
+    /// Number of addresses to connect to, `server` & the other `addresses`
+    pub fn address_count(&self) -> usize {
+        1 + self.addresses.len()
+    }
+
+    /// Connection to the address at `index`, `server` being the first.
+    /// Settings not set for the address are those of `server`.
+    pub fn connection(&self, index: usize) -> connection::Config {
+        let address = index
+            .checked_sub(1)
+            .and_then(|index| self.addresses.get(index));
+
+        let server = address.map_or(self.server.as_str(), |address| &address.server);
+        let port = address
+            .and_then(|address| address.port)
+            .unwrap_or(self.port);
+        let use_tls = address
+            .and_then(|address| address.use_tls)
+            .unwrap_or(self.use_tls);
+        let accept_invalid_certs = address
+            .and_then(|address| address.dangerously_accept_invalid_certs)
+            .unwrap_or(self.dangerously_accept_invalid_certs);
+        let root_cert_path = address
+            .and_then(|address| address.root_cert_path.as_ref())
+            .or(self.root_cert_path.as_ref());
+
+        let security = if use_tls {
             connection::Security::Secured {
-                accept_invalid_certs: self.dangerously_accept_invalid_certs,
-                root_cert_path: self.root_cert_path.as_ref(),
+                accept_invalid_certs,
+                root_cert_path,
                 client_cert_path: self.sasl.as_ref().and_then(Sasl::external_cert),
                 client_key_path: self.sasl.as_ref().and_then(Sasl::external_key),
             }
@@ -100,21 +164,64 @@ impl Server {
         };
 
         connection::Config {
-            server: &self.server,
-            port: self.port,
+            server,
+            port,
             security,
+            proxy: self.proxy.as_ref().map(Proxy::connection),
         }
     }
+
+    /// Whether `other` connects or registers differently, which needs a
+    /// reconnect. Other settings apply to the current connection.
+    pub fn is_connection_changed(&self, other: &Self) -> bool {
+        self.nickname != other.nickname
+            || self.username != other.username
+            || self.realname != other.realname
+            || self.server != other.server
+            || self.port != other.port
+            || self.addresses != other.addresses
+            || self.password != other.password
+            || self.use_tls != other.use_tls
+            || self.dangerously_accept_invalid_certs != other.dangerously_accept_invalid_certs
+            || self.root_cert_path != other.root_cert_path
+            || self.proxy != other.proxy
+            || self.sasl != other.sasl
+            || self.ping_time != other.ping_time
+            || self.ping_timeout != other.ping_timeout
+            || self.send_burst != other.send_burst
+            || self.send_rate != other.send_rate
+            || self.encoding != other.encoding
+            || self.outgoing_encoding != other.outgoing_encoding
+    }
+
+    pub fn codec(&self) -> Codec {
+        Codec::new(self.encoding, self.outgoing_encoding)
+    }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// Another address of a network, w/ the settings of `server` by default
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Address {
+    /// The server to connect to.
+    pub server: String,
+    /// The port to connect on.
+    pub port: Option<u16>,
+    /// Whether or not to use TLS.
+    pub use_tls: Option<bool>,
+    /// On `true`, all certificate validations are skipped.
+    pub dangerously_accept_invalid_certs: Option<bool>,
+    /// The path to the root TLS certificate for this server in PEM format.
+    pub root_cert_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum IdentifySyntax {
     NickPassword,
     PasswordNick,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Sasl {
     Plain {
@@ -131,27 +238,87 @@ pub enum Sasl {
         /// The path to PEM encoded PKCS#8 private key corresponding to the user certificate for external auth
         key: Option<PathBuf>,
     },
+    /// SCRAM-SHA-512 or SCRAM-SHA-256, whichever the server supports
+    Scram {
+        /// Account name
+        username: String,
+        /// Account password,
+        password: Option<String>,
+        /// Account password file
+        password_file: Option<String>,
+    },
 }
 
-impl Sasl {
-    pub fn command(&self) -> &'static str {
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Proxy {
+    /// SOCKS5 proxy, which resolves the server's hostname itself
+    Socks5 {
+        host: String,
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    /// HTTP proxy supporting the CONNECT method
+    Http {
+        host: String,
+        port: u16,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+impl Proxy {
+    pub fn connection(&self) -> connection::Proxy<'_> {
         match self {
-            Sasl::Plain { .. } => "PLAIN",
-            Sasl::External { .. } => "EXTERNAL",
+            Proxy::Socks5 {
+                host,
+                port,
+                username,
+                password,
+            } => connection::Proxy::Socks5 {
+                host,
+                port: *port,
+                username: username.as_deref(),
+                password: password.as_deref(),
+            },
+            Proxy::Http {
+                host,
+                port,
+                username,
+                password,
+            } => connection::Proxy::Http {
+                host,
+                port: *port,
+                username: username.as_deref(),
+                password: password.as_deref(),
+            },
         }
     }
+}
 
-    pub fn param(&self) -> String {
-        match self {
-            Sasl::Plain { username, password, .. } => {
-                use base64::engine::Engine;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SaslFailure {
+    /// Continue registering without being authenticated
+    #[default]
+    Continue,
+    /// Disconnect from the server, w/o reconnecting
+    Disconnect,
+}
 
-                let password = password.as_ref().expect("SASL password must exist at this point!");
+impl Sasl {
+    pub fn username(&self) -> Option<&str> {
+        match self {
+            Sasl::Plain { username, .. } | Sasl::Scram { username, .. } => Some(username),
+            Sasl::External { .. } => None,
+        }
+    }
 
-                base64::engine::general_purpose::STANDARD
-                    .encode(format!("{username}\x00{username}\x00{password}"))
-            }
-            Sasl::External { .. } => "+".into(),
+    pub fn password(&self) -> Option<&str> {
+        match self {
+            Sasl::Plain { password, .. } | Sasl::Scram { password, .. } => password.as_deref(),
+            Sasl::External { .. } => None,
         }
     }
 
@@ -172,6 +339,50 @@ impl Sasl {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Ctcp {
+    /// Reply to CTCP CLIENTINFO queries
+    #[serde(default = "default_bool_true")]
+    pub clientinfo: bool,
+    /// Reply to CTCP PING queries
+    #[serde(default = "default_bool_true")]
+    pub ping: bool,
+    /// Reply to CTCP SOURCE queries
+    #[serde(default = "default_bool_true")]
+    pub source: bool,
+    /// Reply to CTCP TIME queries
+    #[serde(default = "default_bool_true")]
+    pub time: bool,
+    /// Reply to CTCP VERSION queries
+    #[serde(default = "default_bool_true")]
+    pub version: bool,
+}
+
+impl Ctcp {
+    pub fn replies_to(&self, command: &ctcp::Command) -> bool {
+        match command {
+            ctcp::Command::ClientInfo => self.clientinfo,
+            ctcp::Command::Ping => self.ping,
+            ctcp::Command::Source => self.source,
+            ctcp::Command::Time => self.time,
+            ctcp::Command::Version => self.version,
+            ctcp::Command::Action | ctcp::Command::Dcc | ctcp::Command::Unknown(_) => false,
+        }
+    }
+}
+
+impl Default for Ctcp {
+    fn default() -> Self {
+        Self {
+            clientinfo: true,
+            ping: true,
+            source: true,
+            time: true,
+            version: true,
+        }
+    }
+}
+
 fn deserialize_duration_from_u64<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
@@ -180,6 +391,27 @@ where
     Ok(Duration::from_secs(seconds.clamp(5, 3600)))
 }
 
+fn deserialize_duration_from_millis<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let millis: u64 = Deserialize::deserialize(deserializer)?;
+    Ok(Duration::from_millis(millis))
+}
+
+fn deserialize_encoding<'de, D>(deserializer: D) -> Result<Encoding, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let label: String = Deserialize::deserialize(deserializer)?;
+    Encoding::for_label(&label)
+        .ok_or_else(|| de::Error::custom(format!("unsupported encoding: {label}")))
+}
+
+fn default_bool_true() -> bool {
+    true
+}
+
 fn default_use_tls() -> bool {
     true
 }
@@ -200,6 +432,10 @@ fn default_reconnect_delay() -> u64 {
     10
 }
 
+fn default_reconnect_delay_max() -> u64 {
+    600
+}
+
 fn default_ghost_sequence() -> Vec<String> {
     vec!["GHOST".into()]
 }
@@ -211,3 +447,15 @@ fn default_who_poll_interval() -> Duration {
 fn default_who_retry_interval() -> Duration {
     Duration::from_secs(10)
 }
+
+fn default_send_burst() -> u16 {
+    5
+}
+
+fn default_send_rate() -> Duration {
+    Duration::from_millis(2000)
+}
+
+fn default_encoding() -> Encoding {
+    Encoding::WINDOWS_1252
+}
@@ -0,0 +1,197 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use irc::proto::{self, command};
+
+/// Max # automatic replies sent per `REPLY_INTERVAL`, to any number of users
+const REPLY_BURST: usize = 4;
+const REPLY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Action,
+    ClientInfo,
+    Dcc,
+    Ping,
+    Source,
+    Time,
+    Version,
+    Unknown(String),
+}
+
+impl Command {
+    /// Commands we reply to, in addition to `ACTION` & `DCC`
+    pub const REPLIES: [Command; 5] = [
+        Command::ClientInfo,
+        Command::Ping,
+        Command::Source,
+        Command::Time,
+        Command::Version,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Command::Action => "ACTION",
+            Command::ClientInfo => "CLIENTINFO",
+            Command::Dcc => "DCC",
+            Command::Ping => "PING",
+            Command::Source => "SOURCE",
+            Command::Time => "TIME",
+            Command::Version => "VERSION",
+            Command::Unknown(command) => command,
+        }
+    }
+}
+
+impl From<&str> for Command {
+    fn from(command: &str) -> Self {
+        match command.to_uppercase().as_str() {
+            "ACTION" => Command::Action,
+            "CLIENTINFO" => Command::ClientInfo,
+            "DCC" => Command::Dcc,
+            "PING" => Command::Ping,
+            "SOURCE" => Command::Source,
+            "TIME" => Command::Time,
+            "VERSION" => Command::Version,
+            _ => Command::Unknown(command.to_uppercase()),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+/// A CTCP message, sent as a query in a PRIVMSG or as a reply in a NOTICE
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message<'a> {
+    pub command: Command,
+    pub params: Option<&'a str>,
+}
+
+/// Decodes the CTCP message delimited by `\x01` in `text`. The
+/// trailing delimiter is optional, as some clients omit it.
+pub fn decode(text: &str) -> Option<Message<'_>> {
+    let text = text.strip_prefix('\u{1}')?;
+    let text = text.strip_suffix('\u{1}').unwrap_or(text);
+
+    let (command, params) = match text.split_once(' ') {
+        Some((command, params)) => (command, Some(params)),
+        None => (text, None),
+    };
+
+    if command.is_empty() {
+        return None;
+    }
+
+    Some(Message {
+        command: Command::from(command),
+        params,
+    })
+}
+
+pub fn encode(command: &Command, params: Option<impl fmt::Display>) -> String {
+    match params {
+        Some(params) => format!("\u{1}{command} {params}\u{1}"),
+        None => format!("\u{1}{command}\u{1}"),
+    }
+}
+
+pub fn query_message(
+    command: &Command,
+    target: String,
+    params: Option<impl fmt::Display>,
+) -> proto::Message {
+    command!("PRIVMSG", target, encode(command, params))
+}
+
+pub fn response_message(
+    command: &Command,
+    target: String,
+    params: Option<impl fmt::Display>,
+) -> proto::Message {
+    command!("NOTICE", target, encode(command, params))
+}
+
+/// Limits how often we automatically reply to queries, so a flood of them
+/// can't get us disconnected for flooding the server in turn
+#[derive(Debug, Default)]
+pub struct ReplyLimit {
+    sent: VecDeque<Instant>,
+}
+
+impl ReplyLimit {
+    /// Whether a reply can be sent at `now`, counting it if so
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.saturating_duration_since(*sent) >= REPLY_INTERVAL)
+        {
+            self.sent.pop_front();
+        }
+
+        if self.sent.len() >= REPLY_BURST {
+            return false;
+        }
+
+        self.sent.push_back(now);
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_encode() {
+        let tests = [
+            ("\u{1}VERSION\u{1}", Command::Version, None),
+            (
+                "\u{1}ping 1712345678\u{1}",
+                Command::Ping,
+                Some("1712345678"),
+            ),
+            (
+                "\u{1}ACTION waves hello",
+                Command::Action,
+                Some("waves hello"),
+            ),
+            (
+                "\u{1}FINGER me\u{1}",
+                Command::Unknown("FINGER".into()),
+                Some("me"),
+            ),
+        ];
+
+        for (text, command, params) in tests {
+            let message = decode(text).unwrap();
+            assert_eq!(message.command, command);
+            assert_eq!(message.params, params);
+
+            let encoded = encode(&message.command, message.params);
+            assert_eq!(decode(&encoded), Some(message));
+        }
+
+        assert_eq!(decode("VERSION"), None);
+        assert_eq!(decode("\u{1}\u{1}"), None);
+    }
+
+    #[test]
+    fn reply_limit() {
+        let mut limit = ReplyLimit::default();
+        let start = Instant::now();
+
+        for _ in 0..REPLY_BURST {
+            assert!(limit.allow(start));
+        }
+        assert!(!limit.allow(start));
+        assert!(!limit.allow(start + REPLY_INTERVAL / 2));
+        assert!(limit.allow(start + REPLY_INTERVAL));
+    }
+}
@@ -3,17 +3,19 @@ use std::{
     num::NonZeroU16,
 };
 
-use irc::proto::{self, command};
+use irc::proto;
 
-pub fn decode(content: &str) -> Option<Command> {
-    let payload = ctcp_payload(content)?;
+use crate::ctcp;
 
-    let mut args = payload.split_ascii_whitespace();
+pub fn decode(content: &str) -> Option<Command> {
+    let message = ctcp::decode(content)?;
 
-    if args.next()? != "DCC" {
+    if message.command != ctcp::Command::Dcc {
         return None;
     }
 
+    let mut args = message.params?.split_ascii_whitespace();
+
     match args.next()?.to_lowercase().as_str() {
         "send" => Send::decode(args).map(Command::Send),
         cmd => Some(Command::Unsupported(cmd.to_string())),
@@ -102,10 +104,10 @@ impl Send {
                 let host = encode_host(host);
                 let port = port.map(NonZeroU16::get).unwrap_or(0);
 
-                command!(
-                    "PRIVMSG",
+                ctcp::query_message(
+                    &ctcp::Command::Dcc,
                     target.to_string(),
-                    format!("\u{1}DCC SEND {filename} {host} {port} {size} {token}\u{1}")
+                    Some(format!("SEND {filename} {host} {port} {size} {token}")),
                 )
             }
             Self::Direct {
@@ -116,10 +118,10 @@ impl Send {
             } => {
                 let host = encode_host(host);
 
-                command!(
-                    "PRIVMSG",
+                ctcp::query_message(
+                    &ctcp::Command::Dcc,
                     target.to_string(),
-                    format!("\u{1}DCC SEND {filename} {host} {port} {size}\u{1}")
+                    Some(format!("SEND {filename} {host} {port} {size}")),
                 )
             }
         }
@@ -139,11 +141,3 @@ fn encode_host(host: IpAddr) -> String {
         IpAddr::V6(v6) => v6.to_string(),
     }
 }
-
-fn ctcp_payload(content: &str) -> Option<&str> {
-    if content.starts_with('\u{1}') && content.ends_with('\u{1}') && content.len() > 2 {
-        Some(&content[1..content.len() - 1])
-    } else {
-        None
-    }
-}
@@ -5,6 +5,7 @@ pub const VERSION: &str = env!("VERSION");
 pub const GIT_HASH: Option<&str> = option_env!("GIT_HASH");
 pub const CONFIG_FILE_NAME: &str = "config.toml";
 pub const APPLICATION_ID: &str = "org.squidowl.halloy";
+pub const SOURCE_WEBSITE: &str = "https://github.com/squidowl/halloy";
 pub const WIKI_WEBSITE: &str = "https://halloy.squidowl.org";
 pub const MIGRATION_WEBSITE: &str = "https://halloy.squidowl.org/guides/migrating-from-yaml.html";
 pub const RELEASE_WEBSITE: &str = "https://github.com/squidowl/halloy/releases/latest";
@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 
+use crate::config::server::Proxy;
 use crate::user::Nick;
 use crate::{dcc, server, Server};
 
@@ -96,6 +97,8 @@ pub struct ReceiveRequest {
     pub dcc_send: dcc::Send,
     pub server: Server,
     pub server_handle: server::Handle,
+    /// Proxy of the server, which direct connections go through
+    pub proxy: Option<Proxy>,
 }
 
 #[derive(Debug)]
@@ -104,4 +107,6 @@ pub struct SendRequest {
     pub path: PathBuf,
     pub server: Server,
     pub server_handle: server::Handle,
+    /// Proxy of the server, which direct connections go through
+    pub proxy: Option<Proxy>,
 }
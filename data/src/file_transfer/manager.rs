@@ -59,6 +59,11 @@ impl Manager {
         }
     }
 
+    /// Applies `config` to the transfers started from now on
+    pub fn configure(&mut self, config: config::FileTransfer) {
+        self.config = config;
+    }
+
     fn get_random_id(&self) -> Id {
         let mut rng = rand::thread_rng();
 
@@ -84,6 +89,7 @@ impl Manager {
             path,
             server,
             server_handle,
+            proxy,
         } = request;
 
         let reverse = self.config.passive;
@@ -116,7 +122,7 @@ impl Manager {
             },
         };
 
-        let task = Task::send(id, path, filename, to, reverse, server_handle);
+        let task = Task::send(id, path, filename, to, reverse, server_handle, proxy);
         let (handle, stream) = task.spawn(self.server(), Duration::from_secs(self.config.timeout));
 
         self.items.insert(
@@ -136,6 +142,7 @@ impl Manager {
             dcc_send,
             server,
             server_handle,
+            proxy,
         } = request;
 
         // Check if this is the response to a reverse send we sent
@@ -183,7 +190,7 @@ impl Manager {
             status: Status::PendingApproval,
         };
 
-        let task = Task::receive(id, dcc_send, from, server_handle);
+        let task = Task::receive(id, dcc_send, from, server_handle, proxy);
         let (handle, stream) = task.spawn(self.server(), Duration::from_secs(self.config.timeout));
 
         self.items.insert(
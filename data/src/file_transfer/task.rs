@@ -23,6 +23,7 @@ use tokio::{
 use tokio_stream::StreamExt;
 
 use super::Id;
+use crate::config::server::Proxy;
 use crate::{dcc, server, user::Nick};
 
 /// 16 KiB
@@ -61,6 +62,7 @@ pub enum Task {
         dcc_send: dcc::Send,
         server_handle: server::Handle,
         remote_user: Nick,
+        proxy: Option<Proxy>,
     },
     Send {
         id: Id,
@@ -69,6 +71,7 @@ pub enum Task {
         remote_user: Nick,
         reverse: bool,
         server_handle: server::Handle,
+        proxy: Option<Proxy>,
     },
 }
 
@@ -78,12 +81,14 @@ impl Task {
         dcc_send: dcc::Send,
         remote_user: Nick,
         server_handle: server::Handle,
+        proxy: Option<Proxy>,
     ) -> Self {
         Self::Receive {
             id,
             dcc_send,
             remote_user,
             server_handle,
+            proxy,
         }
     }
 
@@ -94,6 +99,7 @@ impl Task {
         remote_user: Nick,
         reverse: bool,
         server_handle: server::Handle,
+        proxy: Option<Proxy>,
     ) -> Self {
         Self::Send {
             id,
@@ -102,6 +108,7 @@ impl Task {
             remote_user,
             reverse,
             server_handle,
+            proxy,
         }
     }
 
@@ -122,6 +129,7 @@ impl Task {
                     dcc_send,
                     remote_user,
                     server_handle,
+                    proxy,
                 } => {
                     if let Err(error) = receive(
                         id,
@@ -131,6 +139,7 @@ impl Task {
                         action_receiver,
                         update_sender,
                         server,
+                        proxy,
                         timeout,
                     )
                     .await
@@ -145,6 +154,7 @@ impl Task {
                     remote_user,
                     reverse,
                     server_handle,
+                    proxy,
                 } => {
                     if let Err(error) = send(
                         id,
@@ -156,6 +166,7 @@ impl Task {
                         action_receiver,
                         update_sender,
                         server,
+                        proxy,
                         timeout,
                     )
                     .await
@@ -213,6 +224,7 @@ async fn receive(
     mut action: Receiver<Action>,
     mut update: Sender<Update>,
     server: Option<Server>,
+    proxy: Option<Proxy>,
     timeout: Duration,
 ) -> Result<(), Error> {
     // Wait for approval
@@ -234,6 +246,11 @@ async fn receive(
             token,
             ..
         } => {
+            // Listening would reveal our address to the remote
+            if proxy.is_some() {
+                return Err(Error::ReverseReceiveProxy);
+            }
+
             let server = server.ok_or(Error::ReverseReceiveNoServerConfig)?;
 
             let _ = update.send(Update::Queued(id)).await;
@@ -281,6 +298,7 @@ async fn receive(
                 server: &host.to_string(),
                 port: port.get(),
                 security: connection::Security::Unsecured,
+                proxy: proxy.as_ref().map(Proxy::connection),
             },
             BytesCodec::new(),
         )
@@ -355,6 +373,7 @@ async fn send(
     mut action: Receiver<Action>,
     mut update: Sender<Update>,
     server: Option<Server>,
+    proxy: Option<Proxy>,
     timeout: Duration,
 ) -> Result<(), Error> {
     let mut file = File::open(path).await?;
@@ -394,11 +413,17 @@ async fn send(
                 server: &host.to_string(),
                 port: port.get(),
                 security: connection::Security::Unsecured,
+                proxy: proxy.as_ref().map(Proxy::connection),
             },
             BytesCodec::new(),
         )
         .await?
     } else {
+        // Listening would reveal our address to the remote
+        if proxy.is_some() {
+            return Err(Error::NonPassiveSendProxy);
+        }
+
         let server = server.ok_or(Error::NonPassiveSendNoServerConfig)?;
 
         let _ = update.send(Update::Queued(id)).await;
@@ -511,6 +536,10 @@ enum Error {
     ReverseReceiveNoServerConfig,
     #[error("[file_transfer.server] must be configured to send a file when passive is disabled")]
     NonPassiveSendNoServerConfig,
+    #[error("sender requested passive send, which can't be received through the server's proxy")]
+    ReverseReceiveProxy,
+    #[error("[file_transfer] passive must be enabled to send a file through the server's proxy")]
+    NonPassiveSendProxy,
     #[error("connection error: {0}")]
     Connection(#[from] connection::Error),
     #[error("io error: {0}")]
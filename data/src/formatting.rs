@@ -0,0 +1,193 @@
+pub use irc::proto::formatting::*;
+
+/// A formatting code which can be inserted into the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Monospace,
+    Reverse,
+    Color,
+    Reset,
+}
+
+impl Modifier {
+    /// Markup escape which [`encode`] converts to this modifier's control code
+    pub fn markup(&self) -> &'static str {
+        match self {
+            Modifier::Bold => "%B",
+            Modifier::Italic => "%I",
+            Modifier::Underline => "%U",
+            Modifier::Strikethrough => "%S",
+            Modifier::Monospace => "%M",
+            Modifier::Reverse => "%R",
+            Modifier::Color => "%C",
+            Modifier::Reset => "%O",
+        }
+    }
+
+    /// Whether the modifier's code turns its formatting on & off, rather
+    /// than setting it
+    pub fn is_toggle(&self) -> bool {
+        !matches!(self, Modifier::Color | Modifier::Reset)
+    }
+
+    fn from_escape(c: char) -> Option<Self> {
+        Some(match c {
+            'B' => Modifier::Bold,
+            'I' => Modifier::Italic,
+            'U' => Modifier::Underline,
+            'S' => Modifier::Strikethrough,
+            'M' => Modifier::Monospace,
+            'R' => Modifier::Reverse,
+            'C' => Modifier::Color,
+            'O' => Modifier::Reset,
+            _ => return None,
+        })
+    }
+
+    fn from_delimiter(c: char) -> Option<Self> {
+        Some(match c {
+            '*' => Modifier::Bold,
+            '_' => Modifier::Italic,
+            '~' => Modifier::Strikethrough,
+            '`' => Modifier::Monospace,
+            _ => return None,
+        })
+    }
+
+    fn code(&self) -> char {
+        match self {
+            Modifier::Bold => BOLD,
+            Modifier::Italic => ITALIC,
+            Modifier::Underline => UNDERLINE,
+            Modifier::Strikethrough => STRIKETHROUGH,
+            Modifier::Monospace => MONOSPACE,
+            Modifier::Reverse => REVERSE,
+            Modifier::Color => COLOR,
+            Modifier::Reset => RESET,
+        }
+    }
+}
+
+/// Converts markup into IRC formatting codes.
+///
+/// Text wrapped in `*`, `_`, `~` or `` ` `` is made bold, italic,
+/// strikethrough or monospace respectively, while the escapes returned
+/// by [`Modifier::markup`] insert a single code (`%C` is followed by the
+/// color numbers, e.g. `%C4,1`). A `%` before a markup character or
+/// another `%` keeps it as is.
+pub fn encode(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+
+    encode_chars(&chars)
+}
+
+fn encode_chars(chars: &[char]) -> String {
+    let mut encoded = String::with_capacity(chars.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '%' {
+            let next = chars.get(i + 1).copied();
+
+            if let Some(modifier) = next.and_then(Modifier::from_escape) {
+                encoded.push(modifier.code());
+                i += 2;
+            } else if let Some(next) =
+                next.filter(|&c| c == '%' || Modifier::from_delimiter(c).is_some())
+            {
+                encoded.push(next);
+                i += 2;
+            } else {
+                encoded.push(c);
+                i += 1;
+            }
+        } else if let Some((modifier, end)) = delimited(chars, i) {
+            encoded.push(modifier.code());
+            encoded.push_str(&encode_chars(&chars[i + 1..end]));
+            encoded.push(modifier.code());
+            i = end + 1;
+        } else {
+            encoded.push(c);
+            i += 1;
+        }
+    }
+
+    encoded
+}
+
+/// Finds the closing delimiter of the span opened at `start`. Delimiters
+/// have to be at word boundaries, so `snake_case` & URLs are left alone.
+fn delimited(chars: &[char], start: usize) -> Option<(Modifier, usize)> {
+    let delimiter = chars[start];
+    let modifier = Modifier::from_delimiter(delimiter)?;
+
+    let opens = match start.checked_sub(1).map(|i| chars[i]) {
+        Some(c) => {
+            c.is_whitespace() || "([{\"'".contains(c) || Modifier::from_delimiter(c).is_some()
+        }
+        None => true,
+    };
+    let next = chars.get(start + 1)?;
+
+    if !opens || next.is_whitespace() || *next == delimiter {
+        return None;
+    }
+
+    let mut i = start + 2;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '%' {
+            i += 2;
+            continue;
+        }
+
+        let closes = c == delimiter
+            && !chars[i - 1].is_whitespace()
+            && !matches!(chars.get(i + 1), Some(c) if c.is_alphanumeric());
+
+        if closes {
+            return Some((modifier, i));
+        }
+
+        i += 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_markup() {
+        let tests = [
+            ("plain text", "plain text"),
+            ("a *bold* move", "a \x02bold\x02 move"),
+            ("_italic_, ~struck~!", "\x1ditalic\x1d, \x1estruck\x1e!"),
+            ("*bold _and italic_*", "\x02bold \x1dand italic\x1d\x02"),
+            ("`mono`", "\x11mono\x11"),
+            ("%C4,1red%C %Bbold%O", "\x034,1red\x03 \x02bold\x0f"),
+            ("100% sure, %%B %*", "100% sure, %B *"),
+            ("%*not bold*", "*not bold*"),
+            ("snake_case_name", "snake_case_name"),
+            ("2*3*4", "2*3*4"),
+            ("https://example.com/_path_", "https://example.com/_path_"),
+            ("* not bold *", "* not bold *"),
+            ("**", "**"),
+            ("unclosed *bold", "unclosed *bold"),
+        ];
+
+        for (markup, expected) in tests {
+            assert_eq!(encode(markup), expected, "{markup:?}");
+        }
+    }
+}
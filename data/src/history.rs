@@ -1,31 +1,37 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::{fmt, io};
 
+use chrono::{DateTime, Days, NaiveDate, Utc};
 use futures::future::BoxFuture;
 use futures::{Future, FutureExt};
+use itertools::Itertools;
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use tokio::time::Instant;
 
 pub use self::manager::{Manager, Resource};
+use crate::isupport::CaseMap;
 use crate::time::Posix;
 use crate::user::Nick;
-use crate::{compression, environment, message, server, Message};
+use crate::{channel, compression, environment, message, search, server, Message};
 
+pub mod export;
+pub mod import;
 pub mod manager;
+pub mod text;
 
-// TODO: Make this configurable?
-/// Max # messages to persist
-const MAX_MESSAGES: usize = 10_000;
-/// # messages to tuncate after hitting [`MAX_MESSAGES`]
-const TRUNC_COUNT: usize = 500;
+/// Min # messages to load at once, from the latest segments when opening a
+/// buffer & older ones when scrolling back
+const PAGE_SIZE: usize = 500;
+const SEGMENT_EXTENSION: &str = ".jsonl.gz";
 /// Duration to wait after receiving last message before flushing
 const FLUSH_AFTER_LAST_RECEIVED: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Kind {
     Server,
-    Channel(String),
+    Channel(channel::Name),
     Query(Nick),
 }
 
@@ -43,75 +49,496 @@ impl From<message::Target> for Kind {
     fn from(target: message::Target) -> Self {
         match target {
             message::Target::Server { .. } => Kind::Server,
-            message::Target::Channel { channel, .. } => Kind::Channel(channel),
+            message::Target::Channel { channel, .. } => Kind::Channel(channel.into()),
             message::Target::Query { nick, .. } => Kind::Query(nick),
         }
     }
 }
 
-pub async fn load(server: &server::Server, kind: &Kind) -> Result<Vec<Message>, Error> {
-    let path = path(server, kind).await?;
+/// A page of history, loaded from its most recent segments
+#[derive(Debug)]
+pub struct Page {
+    pub messages: Vec<Message>,
+    /// Days of the segments which weren't loaded, oldest first
+    pub older: Vec<NaiveDate>,
+}
+
+/// Loads the latest page of history, or back to `since` if it's older,
+/// migrating history written to a single file first
+pub async fn load(
+    server: &server::Server,
+    kind: &Kind,
+    since: Option<NaiveDate>,
+) -> Result<Page, Error> {
+    migrate(server, kind).await?;
+
+    let dir = dir(server, kind).await?;
+    let segments = segments(&dir).await?;
 
-    Ok(read_all(&path).await.unwrap_or_default())
+    load_page(&dir, segments, since).await
 }
 
-pub async fn overwrite(
+/// Loads the page preceding those already loaded, from the `older` segments
+pub async fn load_older(
     server: &server::Server,
     kind: &Kind,
-    messages: &[Message],
+    older: Vec<NaiveDate>,
+    since: Option<NaiveDate>,
+) -> Result<Page, Error> {
+    let dir = dir(server, kind).await?;
+
+    load_page(&dir, older, since).await
+}
+
+/// Appends `messages` to the segments of the days they were sent on
+pub async fn append(
+    server: &server::Server,
+    kind: &Kind,
+    messages: Vec<Message>,
 ) -> Result<(), Error> {
     if messages.is_empty() {
         return Ok(());
     }
 
-    let latest = &messages[messages.len().saturating_sub(MAX_MESSAGES)..];
+    let dir = dir(server, kind).await?;
+    let buffer = Resource {
+        server: server.clone(),
+        kind: kind.clone(),
+    }
+    .buffer();
 
-    let path = path(server, kind).await?;
-    let compressed = compression::compress(&latest)?;
+    let by_day = messages
+        .into_iter()
+        .into_group_map_by(|message| message.server_time.date_naive());
 
-    fs::write(path, &compressed).await?;
+    let mut index = Vec::with_capacity(by_day.len());
 
-    Ok(())
+    for (day, messages) in by_day {
+        let compressed = compression::compress_lines(&messages)?;
+
+        append_to(&segment_path(&dir, day), &compressed).await?;
+
+        index.push(search::Entry::new(buffer.clone(), day, &messages));
+    }
+
+    search::index(&dir, index).await
 }
 
-pub async fn append(
+/// Appends the `messages` which aren't already in the segments of the days
+/// they were sent on, such as when the same logs are imported again,
+/// returning how many were appended
+pub async fn append_new(
     server: &server::Server,
     kind: &Kind,
     messages: Vec<Message>,
+) -> Result<usize, Error> {
+    let dir = dir(server, kind).await?;
+
+    let by_day = messages
+        .into_iter()
+        .into_group_map_by(|message| message.server_time.date_naive());
+
+    let mut new = vec![];
+
+    for (day, messages) in by_day {
+        let stored = read_segment_if_exists(&segment_path(&dir, day)).await?;
+
+        // Imported messages have no `msgid`, so they're matched by their
+        // server time, source & text
+        new.extend(dedupe(&stored, messages, Message::is_replayed));
+    }
+
+    let count = new.len();
+
+    append(server, kind, new).await?;
+
+    Ok(count)
+}
+
+/// Appends the messages of a history whose segments weren't loaded, so
+/// those played back since `connected_at` may already be on disk
+async fn append_unloaded(
+    server: &server::Server,
+    kind: &Kind,
+    messages: Vec<Message>,
+    connected_at: Option<DateTime<Utc>>,
 ) -> Result<(), Error> {
-    if messages.is_empty() {
-        return Ok(());
+    let (played_back, live) = messages
+        .into_iter()
+        .partition::<Vec<_>, _>(|message| is_played_back(message, connected_at));
+
+    append_new(server, kind, played_back).await?;
+    append(server, kind, live).await
+}
+
+/// Server time of the latest message stored on disk
+pub async fn latest_server_time(
+    server: &server::Server,
+    kind: &Kind,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    let page = load(server, kind, None).await?;
+
+    Ok(latest(&page.messages))
+}
+
+/// Deletes the segments of every history older than `max_age` days,
+/// returning how many were deleted
+pub async fn prune(max_age: u32) -> Result<usize, Error> {
+    let cutoff = Utc::now()
+        .date_naive()
+        .checked_sub_days(Days::new(u64::from(max_age)))
+        .unwrap_or(NaiveDate::MIN);
+
+    let mut deleted = 0;
+    let mut entries = fs::read_dir(root_dir().await?).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+
+        let dir = entry.path();
+
+        for day in segments(&dir).await? {
+            if day < cutoff {
+                fs::remove_file(segment_path(&dir, day)).await?;
+                search::remove_index(&dir, day).await?;
+                deleted += 1;
+            }
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Messages are ordered by server time, ties broken by the order we
+/// received them in
+fn sort_key(message: &Message) -> (DateTime<Utc>, Posix) {
+    (message.server_time, message.received_at)
+}
+
+/// Sorts the `new` messages, dropping those already in `messages` or
+/// repeated in `new`, as decided by `is_duplicate`
+pub(crate) fn dedupe(
+    messages: &[Message],
+    mut new: Vec<Message>,
+    is_duplicate: fn(&Message, &Message) -> bool,
+) -> Vec<Message> {
+    new.sort_by_key(sort_key);
+
+    let mut deduped: Vec<Message> = Vec::with_capacity(new.len());
+
+    for message in new {
+        if !contains(messages, &message, is_duplicate)
+            && !contains(&deduped, &message, is_duplicate)
+        {
+            deduped.push(message);
+        }
+    }
+
+    deduped
+}
+
+/// Whether `message` was sent before connecting at `connected_at`, so
+/// it's played back, such as by a bouncer, & may repeat one already stored
+fn is_played_back(message: &Message, connected_at: Option<DateTime<Utc>>) -> bool {
+    connected_at.is_some_and(|connected_at| message.server_time < connected_at)
+}
+
+/// Like [`dedupe`], matching messages played back since `connected_at` by
+/// [`Message::is_replayed`] as they may have no `msgid`
+pub(crate) fn dedupe_received(
+    messages: &[Message],
+    new: Vec<Message>,
+    connected_at: Option<DateTime<Utc>>,
+) -> Vec<Message> {
+    let (played_back, live) = new
+        .into_iter()
+        .partition::<Vec<_>, _>(|message| is_played_back(message, connected_at));
+
+    let mut deduped = dedupe(messages, played_back, Message::is_replayed);
+    deduped.extend(dedupe(messages, live, Message::is_duplicate));
+    deduped.sort_by_key(sort_key);
+
+    deduped
+}
+
+/// Merges two sorted lists of messages
+fn merge_sorted(messages: &mut Vec<Message>, new: Vec<Message>) {
+    let is_after = match (messages.last(), new.first()) {
+        (Some(last), Some(first)) => sort_key(first) >= sort_key(last),
+        _ => true,
+    };
+
+    // Fast path when appending, such as for messages just received
+    if is_after {
+        messages.extend(new);
+    } else {
+        *messages = std::mem::take(messages)
+            .into_iter()
+            .merge_by(new, |a, b| sort_key(a) <= sort_key(b))
+            .collect();
+    }
+}
+
+/// Index at which `message` is inserted to keep `messages` in order
+fn insert_position(messages: &[Message], message: &Message) -> usize {
+    let key = sort_key(message);
+
+    messages.partition_point(|stored| sort_key(stored) <= key)
+}
+
+fn contains(
+    messages: &[Message],
+    message: &Message,
+    is_duplicate: fn(&Message, &Message) -> bool,
+) -> bool {
+    // Duplicates share their server time
+    let start = messages.partition_point(|stored| stored.server_time < message.server_time);
+
+    messages[start..]
+        .iter()
+        .take_while(|stored| stored.server_time == message.server_time)
+        .any(|stored| is_duplicate(stored, message))
+}
+
+/// Server time of the latest message the server sent us, ignoring our own
+/// status messages
+fn latest(messages: &[Message]) -> Option<DateTime<Utc>> {
+    messages
+        .iter()
+        .rev()
+        .find(|message| !matches!(message.target.source(), message::Source::Internal(_)))
+        .map(|message| message.server_time)
+}
+
+/// Reads segments, newest first, until a page worth of messages and the
+/// segments back to `since` are loaded
+async fn load_page(
+    dir: &Path,
+    mut segments: Vec<NaiveDate>,
+    since: Option<NaiveDate>,
+) -> Result<Page, Error> {
+    let mut messages = vec![];
+
+    while let Some(&day) = segments.last() {
+        let is_wanted = messages.len() < PAGE_SIZE || since.is_some_and(|since| day >= since);
+
+        if !is_wanted {
+            break;
+        }
+
+        segments.pop();
+
+        let mut segment = read_segment(&segment_path(dir, day)).await?;
+        segment.extend(messages);
+        messages = segment;
+    }
+
+    Ok(Page {
+        messages,
+        older: segments,
+    })
+}
+
+pub(crate) async fn read_segment(path: &Path) -> Result<Vec<Message>, Error> {
+    let bytes = fs::read(path).await?;
+
+    // Played back messages can be appended to older segments & repeat
+    // messages already stored. Only those w/ a `msgid` are told apart from
+    // repeats of the same text.
+    Ok(dedupe(
+        &[],
+        compression::decompress_lines(&bytes),
+        Message::is_duplicate,
+    ))
+}
+
+/// Reads the segment at `path`, or no messages if there's none, such as
+/// when it was pruned after being indexed
+pub(crate) async fn read_segment_if_exists(path: &Path) -> Result<Vec<Message>, Error> {
+    match read_segment(path).await {
+        Err(Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        result => result,
+    }
+}
+
+/// Days which have a segment, oldest first
+pub(crate) async fn segments(dir: &Path) -> Result<Vec<NaiveDate>, Error> {
+    let mut segments = vec![];
+    let mut entries = fs::read_dir(dir).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(day) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_suffix(SEGMENT_EXTENSION))
+            .and_then(|day| day.parse::<NaiveDate>().ok())
+        {
+            segments.push(day);
+        }
+    }
+
+    segments.sort();
+
+    Ok(segments)
+}
+
+pub(crate) fn segment_path(dir: &Path, day: NaiveDate) -> PathBuf {
+    dir.join(format!("{day}{SEGMENT_EXTENSION}"))
+}
+
+/// Moves history written to a single file, before it was segmented, into
+/// segments
+async fn migrate(server: &server::Server, kind: &Kind) -> Result<(), Error> {
+    let root = root_dir().await?;
+
+    for name in [legacy_name(server, kind), name(server, kind)] {
+        let path = root.join(format!("{}.json.gz", seahash::hash(name.as_bytes())));
+
+        if !path.exists() {
+            continue;
+        }
+
+        let messages = match read_all(&path).await {
+            Ok(messages) => messages,
+            Err(error) => {
+                log::warn!("failed to migrate history for {kind} on {server}: {error}");
+                continue;
+            }
+        };
+
+        append(server, kind, messages).await?;
+        fs::remove_file(&path).await?;
+
+        log::info!("migrated history for {kind} on {server}");
+    }
+
+    Ok(())
+}
+
+/// Migrates the history written to single files in the root directory,
+/// returning the paths of those which don't belong to any of `servers`
+pub(crate) async fn migrate_files(servers: &[server::Server]) -> Result<Vec<PathBuf>, Error> {
+    let mut entries = fs::read_dir(root_dir().await?).await?;
+    let mut files = vec![];
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_file() {
+            files.push(entry.path());
+        }
     }
 
-    let mut all_messages = load(server, kind).await?;
-    all_messages.extend(messages);
+    let mut skipped = vec![];
+
+    for path in files {
+        if !migrate_file(&path, servers).await? {
+            skipped.push(path);
+        }
+    }
 
-    overwrite(server, kind, &all_messages).await
+    Ok(skipped)
 }
 
-async fn read_all(path: &PathBuf) -> Result<Vec<Message>, Error> {
+/// Migrates the history written to the single file at `path`, which is
+/// named after a hash of its buffer. The buffer of its messages is hashed
+/// w/ each of `servers` to find which it belongs to, returning whether one
+/// did.
+async fn migrate_file(path: &Path, servers: &[server::Server]) -> Result<bool, Error> {
+    let Some(hash) = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".json.gz"))
+    else {
+        return Ok(false);
+    };
+
+    let kind = match read_all(path).await {
+        Ok(messages) => match messages.into_iter().next() {
+            Some(message) => Kind::from(message.target),
+            None => return Ok(false),
+        },
+        Err(error) => {
+            log::warn!("failed to read history at {}: {error}", path.display());
+            return Ok(false);
+        }
+    };
+
+    let server = servers.iter().find(|server| {
+        [legacy_name(server, &kind), name(server, &kind)]
+            .iter()
+            .any(|name| seahash::hash(name.as_bytes()).to_string() == hash)
+    });
+
+    match server {
+        Some(server) => {
+            migrate(server, &kind).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub(crate) async fn append_to(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+
+    file.write_all(bytes).await?;
+
+    Ok(())
+}
+
+async fn read_all(path: &Path) -> Result<Vec<Message>, Error> {
     let bytes = fs::read(path).await?;
     Ok(compression::decompress(&bytes)?)
 }
 
-async fn path(server: &server::Server, kind: &Kind) -> Result<PathBuf, Error> {
-    let data_dir = environment::data_dir();
+fn name(server: &server::Server, kind: &Kind) -> String {
+    // Names are folded with the default casemapping, regardless of the
+    // server's, so the path doesn't depend on when `CASEMAPPING` was received
+    let casemapping = CaseMap::default();
 
     // TODO: Is this stable enough? What if user's nickname changes
-    let name = match kind {
+    match kind {
+        Kind::Server => format!("{server}"),
+        Kind::Channel(channel) => format!("{server}channel{}", channel.normalized()),
+        Kind::Query(nick) => format!("{server}nickname{}", casemapping.normalize(nick.as_ref())),
+    }
+}
+
+/// Name used before channel & nickname were normalized
+fn legacy_name(server: &server::Server, kind: &Kind) -> String {
+    match kind {
         Kind::Server => format!("{server}"),
         Kind::Channel(channel) => format!("{server}channel{channel}"),
         Kind::Query(nick) => format!("{server}nickname{}", nick),
-    };
-    let hashed_name = seahash::hash(name.as_bytes());
+    }
+}
+
+/// Directory holding the segments of a history
+pub(crate) async fn dir(server: &server::Server, kind: &Kind) -> Result<PathBuf, Error> {
+    let hashed_name = seahash::hash(name(server, kind).as_bytes());
+
+    let dir = root_dir().await?.join(hashed_name.to_string());
+
+    if !dir.exists() {
+        fs::create_dir_all(&dir).await?;
+    }
+
+    Ok(dir)
+}
 
-    let parent = data_dir.join("history");
+pub(crate) async fn root_dir() -> Result<PathBuf, Error> {
+    let dir = environment::data_dir().join("history");
 
-    if !parent.exists() {
-        fs::create_dir_all(&parent).await?;
+    if !dir.exists() {
+        fs::create_dir_all(&dir).await?;
     }
 
-    Ok(parent.join(format!("{hashed_name}.json.gz")))
+    Ok(dir)
 }
 
 #[derive(Debug)]
@@ -128,6 +555,10 @@ pub enum History {
         server: server::Server,
         kind: Kind,
         messages: Vec<Message>,
+        /// Messages which haven't been appended to disk yet
+        unflushed: Vec<Message>,
+        /// Days of the segments which haven't been loaded yet
+        older: Vec<NaiveDate>,
         last_received_at: Option<Instant>,
         opened_at: Posix,
     },
@@ -145,7 +576,20 @@ impl History {
         }
     }
 
-    fn add_message(&mut self, message: Message) {
+    /// Inserts `message` in server time order, unless it's already stored.
+    /// Messages played back since `connected_at` w/o a `msgid` are matched
+    /// by their server time, source & text.
+    fn add_message(
+        &mut self,
+        message: Message,
+        connected_at: Option<DateTime<Utc>>,
+    ) -> Option<&Message> {
+        let is_duplicate = if is_played_back(&message, connected_at) {
+            Message::is_replayed
+        } else {
+            Message::is_duplicate
+        };
+
         match self {
             History::Partial {
                 messages,
@@ -153,25 +597,166 @@ impl History {
                 unread_message_count,
                 ..
             } => {
+                if contains(messages, &message, is_duplicate) {
+                    return None;
+                }
+
                 if message.triggers_unread() {
                     *unread_message_count += 1;
                 }
 
-                messages.push(message);
+                let position = insert_position(messages, &message);
+                messages.insert(position, message);
                 *last_received_at = Some(Instant::now());
+
+                messages.get(position)
             }
             History::Full {
                 messages,
+                unflushed,
                 last_received_at,
                 ..
             } => {
-                messages.push(message);
+                if contains(messages, &message, is_duplicate) {
+                    return None;
+                }
+
+                unflushed.push(message.clone());
+                let position = insert_position(messages, &message);
+                messages.insert(position, message);
                 *last_received_at = Some(Instant::now());
+
+                messages.get(position)
+            }
+        }
+    }
+
+    /// Merges a batch of messages, such as a playback, in server time order,
+    /// returning those which weren't already stored. Messages w/o a `msgid`
+    /// are matched by their server time, source & text, as a playback
+    /// repeats those already received.
+    fn add_messages(&mut self, new: Vec<Message>) -> Vec<Message> {
+        match self {
+            History::Partial {
+                messages,
+                last_received_at,
+                unread_message_count,
+                ..
+            } => {
+                let new = dedupe(messages, new, Message::is_replayed);
+
+                *unread_message_count += new
+                    .iter()
+                    .filter(|message| message.triggers_unread())
+                    .count();
+
+                if !new.is_empty() {
+                    merge_sorted(messages, new.clone());
+                    *last_received_at = Some(Instant::now());
+                }
+
+                new
+            }
+            History::Full {
+                messages,
+                unflushed,
+                last_received_at,
+                ..
+            } => {
+                let new = dedupe(messages, new, Message::is_replayed);
+
+                if !new.is_empty() {
+                    unflushed.extend(new.iter().cloned());
+                    merge_sorted(messages, new.clone());
+                    *last_received_at = Some(Instant::now());
+                }
+
+                new
             }
         }
     }
 
-    fn flush(&mut self, now: Instant) -> Option<BoxFuture<'static, Result<(), Error>>> {
+    /// Messages which haven't been written to disk yet
+    fn unflushed(&self) -> Vec<Message> {
+        match self {
+            History::Partial { messages, .. } => messages.clone(),
+            History::Full { unflushed, .. } => unflushed.clone(),
+        }
+    }
+
+    /// Merges imported messages, returning those older than the messages
+    /// loaded, which are appended to disk instead & loaded when scrolling
+    /// back
+    fn import(&mut self, new: Vec<Message>) -> Vec<Message> {
+        let oldest = match self {
+            History::Partial { .. } => return new,
+            History::Full { messages, .. } => messages.first().map(sort_key),
+        };
+
+        let (older, newer) = new
+            .into_iter()
+            .partition(|message| oldest.is_some_and(|oldest| sort_key(message) < oldest));
+
+        self.add_messages(newer);
+
+        older
+    }
+
+    /// Adds the days of segments written to disk by an import, to be loaded
+    /// when scrolling back
+    fn add_older_days(&mut self, days: Vec<NaiveDate>) {
+        if let History::Full { older, .. } = self {
+            older.extend(days);
+            older.sort();
+            older.dedup();
+        }
+    }
+
+    /// Takes the days of the segments to load when scrolling back, or to
+    /// load back to `since`, if any are needed
+    fn take_older(&mut self, since: Option<NaiveDate>) -> Option<Vec<NaiveDate>> {
+        match self {
+            History::Partial { .. } => None,
+            History::Full { older, .. } => {
+                let is_needed = match (older.last(), since) {
+                    (None, _) => false,
+                    (Some(latest), Some(since)) => *latest >= since,
+                    (Some(_), None) => true,
+                };
+
+                is_needed.then(|| std::mem::take(older))
+            }
+        }
+    }
+
+    /// Prepends a page of older messages loaded from disk
+    fn add_older(&mut self, page: Page) {
+        if let History::Full {
+            messages, older, ..
+        } = self
+        {
+            let mut page_messages = dedupe(messages, page.messages, Message::is_duplicate);
+            merge_sorted(&mut page_messages, std::mem::take(messages));
+
+            *messages = page_messages;
+            *older = page.older;
+        }
+    }
+
+    /// Server time of the latest message in memory, `None` if a partial
+    /// history has to look on disk
+    fn latest_server_time(&self) -> Option<Option<DateTime<Utc>>> {
+        match self {
+            History::Partial { messages, .. } => latest(messages).map(Some),
+            History::Full { messages, .. } => Some(latest(messages)),
+        }
+    }
+
+    fn flush(
+        &mut self,
+        now: Instant,
+        connected_at: Option<DateTime<Utc>>,
+    ) -> Option<BoxFuture<'static, Result<(), Error>>> {
         match self {
             History::Partial {
                 server,
@@ -189,7 +774,12 @@ impl History {
                         let messages = std::mem::take(messages);
                         *last_received_at = None;
 
-                        return Some(async move { append(&server, &kind, messages).await }.boxed());
+                        return Some(
+                            async move {
+                                append_unloaded(&server, &kind, messages, connected_at).await
+                            }
+                            .boxed(),
+                        );
                     }
                 }
 
@@ -198,27 +788,20 @@ impl History {
             History::Full {
                 server,
                 kind,
-                messages,
+                unflushed,
                 last_received_at,
                 ..
             } => {
                 if let Some(last_received) = *last_received_at {
                     let since = now.duration_since(last_received);
 
-                    if since >= FLUSH_AFTER_LAST_RECEIVED && !messages.is_empty() {
+                    if since >= FLUSH_AFTER_LAST_RECEIVED && !unflushed.is_empty() {
                         let server = server.clone();
                         let kind = kind.clone();
+                        let messages = std::mem::take(unflushed);
                         *last_received_at = None;
 
-                        if messages.len() > MAX_MESSAGES {
-                            messages.drain(0..messages.len() - (MAX_MESSAGES - TRUNC_COUNT));
-                        }
-
-                        let messages = messages.clone();
-
-                        return Some(
-                            async move { overwrite(&server, &kind, &messages).await }.boxed(),
-                        );
+                        return Some(async move { append(&server, &kind, messages).await }.boxed());
                     }
                 }
 
@@ -233,34 +816,34 @@ impl History {
             History::Full {
                 server,
                 kind,
-                messages,
+                unflushed,
                 ..
             } => {
                 let server = server.clone();
                 let kind = kind.clone();
-                let messages = std::mem::take(messages);
+                let messages = std::mem::take(unflushed);
 
                 *self = Self::partial(server.clone(), kind.clone(), Posix::now());
 
-                Some(async move { overwrite(&server, &kind, &messages).await })
+                Some(async move { append(&server, &kind, messages).await })
             }
         }
     }
 
-    async fn close(self) -> Result<(), Error> {
+    async fn close(self, connected_at: Option<DateTime<Utc>>) -> Result<(), Error> {
         match self {
             History::Partial {
                 server,
                 kind,
                 messages,
                 ..
-            } => append(&server, &kind, messages).await,
+            } => append_unloaded(&server, &kind, messages, connected_at).await,
             History::Full {
                 server,
                 kind,
-                messages,
+                unflushed,
                 ..
-            } => overwrite(&server, &kind, &messages).await,
+            } => append(&server, &kind, unflushed).await,
         }
     }
 }
@@ -268,6 +851,8 @@ impl History {
 #[derive(Debug)]
 pub struct View<'a> {
     pub total: usize,
+    /// Whether older messages can be loaded from disk
+    pub has_older: bool,
     pub old_messages: Vec<&'a Message>,
     pub new_messages: Vec<&'a Message>,
 }
@@ -279,3 +864,86 @@ pub enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::message::Source;
+
+    fn message(seconds: i64, text: &str) -> Message {
+        Message {
+            server_time: Utc.timestamp_opt(seconds, 0).unwrap(),
+            ..Message::test(Source::Server(None), text)
+        }
+    }
+
+    #[test]
+    fn add_messages_in_order() {
+        let mut history = History::partial(
+            server::Server::from("server"),
+            Kind::Channel("#halloy".into()),
+            Posix::now(),
+        );
+
+        history.add_message(message(1, "first"), None);
+        history.add_message(message(4, "fourth"), None);
+        // Delayed
+        history.add_message(message(3, "third"), None);
+        // Ties keep the order they're received in
+        history.add_message(message(6, "sixth"), None);
+        history.add_message(message(6, "seventh"), None);
+
+        history.add_messages(vec![
+            message(5, "fifth"),
+            message(2, "second"),
+            message(4, "fourth"),
+            message(2, "second"),
+        ]);
+
+        let History::Partial { messages, .. } = &history else {
+            unreachable!()
+        };
+        let texts = messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            texts,
+            ["first", "second", "third", "fourth", "fifth", "sixth", "seventh"]
+        );
+        assert_eq!(
+            history.latest_server_time(),
+            Some(Some(Utc.timestamp_opt(6, 0).unwrap()))
+        );
+    }
+
+    #[test]
+    fn live_repeats_are_kept() {
+        let mut history = History::partial(
+            server::Server::from("server"),
+            Kind::Channel("#halloy".into()),
+            Posix::now(),
+        );
+        let with_id = |id: &str| Message {
+            id: Some(id.into()),
+            ..message(2, "hi")
+        };
+
+        // The same text sent twice within a second
+        assert!(history.add_message(message(1, "lol"), None).is_some());
+        assert!(history.add_message(message(1, "lol"), None).is_some());
+
+        assert!(history.add_message(with_id("a"), None).is_some());
+        assert!(history.add_message(with_id("a"), None).is_none());
+
+        // Played back, the fingerprint matches what was received
+        assert!(history
+            .add_messages(vec![message(1, "lol"), with_id("a"), message(3, "new")])
+            .iter()
+            .map(|message| message.text.as_str())
+            .eq(["new"]));
+    }
+}
@@ -0,0 +1,401 @@
+//! Exports of a buffer's history, to share a conversation.
+//!
+//! History is written as plain text, formatted like the text logs, as JSON
+//! lines of the stored messages, or as a standalone HTML page w/ the nick
+//! colors of the theme & the messages' formatting.
+
+use std::fmt::{self, Write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{Days, Local, NaiveDate};
+use iced_core::Color;
+use tokio::fs;
+
+use crate::buffer::Nickname;
+use crate::config::history::TextLog;
+use crate::history::{self, text, Kind};
+use crate::message::{source, Source};
+use crate::theme::{self, Colors};
+use crate::{formatting, server, Message};
+
+/// Format of timestamps, which are written w/ their day as exports can span
+/// several days
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    JsonLines,
+    Html,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Html, Format::Text, Format::JsonLines];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::JsonLines => "jsonl",
+            Format::Html => "html",
+        }
+    }
+
+    /// The format of the file at `path`, by its extension, defaulting to text
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_deref() {
+            Some("jsonl" | "json") => Format::JsonLines,
+            Some("html" | "htm") => Format::Html,
+            _ => Format::Text,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "Text"),
+            Format::JsonLines => write!(f, "JSON lines"),
+            Format::Html => write!(f, "HTML"),
+        }
+    }
+}
+
+/// Days of history to export, by local date
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Range {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl Range {
+    /// The last `days` days, including today
+    pub fn last_days(days: u64) -> Self {
+        let today = Local::now().date_naive();
+
+        Self {
+            since: today.checked_sub_days(Days::new(days.saturating_sub(1))),
+            until: None,
+        }
+    }
+
+    fn contains(&self, day: NaiveDate) -> bool {
+        let is_since = match self.since {
+            Some(since) => day >= since,
+            None => true,
+        };
+        let is_until = match self.until {
+            Some(until) => day <= until,
+            None => true,
+        };
+
+        is_since && is_until
+    }
+
+    /// Whether the segment of the (UTC) `day` can have messages sent on a
+    /// day in range, in any timezone
+    fn overlaps_segment(&self, day: NaiveDate) -> bool {
+        let is_since = match self.since {
+            Some(since) => day.checked_add_days(Days::new(1)).unwrap_or(day) >= since,
+            None => true,
+        };
+        let is_until = match self.until {
+            Some(until) => day.checked_sub_days(Days::new(1)).unwrap_or(day) <= until,
+            None => true,
+        };
+
+        is_since && is_until
+    }
+}
+
+/// How exported history looks, from the config & theme
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub text_log: TextLog,
+    pub nickname: Nickname,
+    pub colors: Colors,
+}
+
+/// Writes the history of `kind` in `range` to `path`, in the format of its
+/// extension, returning how many messages were written. `unflushed` are
+/// the messages which haven't been written to disk yet.
+pub async fn export(
+    server: server::Server,
+    kind: Kind,
+    range: Range,
+    unflushed: Vec<Message>,
+    path: PathBuf,
+    style: Style,
+) -> Result<usize, Error> {
+    let mut messages = unflushed;
+
+    let dir = history::dir(&server, &kind).await?;
+
+    for day in history::segments(&dir).await? {
+        if range.overlaps_segment(day) {
+            messages.extend(history::read_segment(&history::segment_path(&dir, day)).await?);
+        }
+    }
+
+    let messages = history::dedupe(&[], messages, Message::is_duplicate)
+        .into_iter()
+        .filter(|message| range.contains(message.server_time.with_timezone(&Local).date_naive()))
+        .collect::<Vec<_>>();
+
+    let content = match Format::from_path(&path) {
+        Format::Text => to_text(&messages, &style),
+        Format::JsonLines => to_json_lines(&messages)?,
+        Format::Html => to_html(&server, &kind, &messages, &style),
+    };
+
+    fs::write(&path, content).await?;
+
+    Ok(messages.len())
+}
+
+fn to_text(messages: &[Message], style: &Style) -> String {
+    let text_log = TextLog {
+        timestamp: TIMESTAMP_FORMAT.to_string(),
+        ..style.text_log.clone()
+    };
+
+    messages
+        .iter()
+        .map(|message| text::line(&text_log, message))
+        .collect()
+}
+
+fn to_json_lines(messages: &[Message]) -> Result<String, Error> {
+    let mut content = String::new();
+
+    for message in messages {
+        content.push_str(&serde_json::to_string(message)?);
+        content.push('\n');
+    }
+
+    Ok(content)
+}
+
+fn to_html(server: &server::Server, kind: &Kind, messages: &[Message], style: &Style) -> String {
+    let colors = &style.colors;
+    let title = match kind {
+        Kind::Server => server.to_string(),
+        Kind::Channel(channel) => format!("{channel} on {server}"),
+        Kind::Query(nick) => format!("{nick} on {server}"),
+    };
+
+    let mut html = String::new();
+
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n\
+         body {{ background: {}; color: {}; font-family: monospace; font-size: 13px; }}\n\
+         .message {{ white-space: pre-wrap; padding: 1px 0; }}\n\
+         .time {{ color: {}; }}\n\
+         .server {{ color: {}; }}\n\
+         .action {{ color: {}; }}\n\
+         .success {{ color: {}; }}\n\
+         .error {{ color: {}; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(&title),
+        css(colors.background.base),
+        css(colors.text.base),
+        css(colors.text.low_alpha),
+        css(colors.info.base),
+        css(colors.accent.base),
+        css(colors.success.base),
+        css(colors.error.base),
+        escape(&title),
+    );
+
+    for message in messages {
+        let time = message
+            .server_time
+            .with_timezone(&Local)
+            .format(TIMESTAMP_FORMAT);
+
+        let (class, nick) = match message.target.source() {
+            Source::User(user) => {
+                let color = match user.color_seed(&style.nickname.color) {
+                    Some(seed) => theme::randomize_color(colors.action.base, &seed),
+                    None => colors.text.base,
+                };
+
+                let nick = format!(
+                    "<span class=\"nick\" style=\"color: {}\">{}</span> ",
+                    css(color),
+                    escape(&style.nickname.brackets.format(user))
+                );
+
+                ("user", nick)
+            }
+            Source::Server(_) => ("server", String::new()),
+            Source::Action => ("action", String::new()),
+            Source::Internal(source::Internal::Status(source::Status::Success)) => {
+                ("success", String::new())
+            }
+            Source::Internal(source::Internal::Status(source::Status::Error)) => {
+                ("error", String::new())
+            }
+        };
+
+        let _ = writeln!(
+            html,
+            "<div class=\"message\"><span class=\"time\">{time}</span> {nick}<span class=\"{class}\">{}</span></div>",
+            formatted(&message.text, colors)
+        );
+    }
+
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+/// `text` w/ its IRC formatting as styled spans
+fn formatted(text: &str, colors: &Colors) -> String {
+    if !formatting::is_formatted(text) {
+        return escape(text);
+    }
+
+    formatting::parse(text)
+        .into_iter()
+        .map(|span| {
+            let formatting = &span.formatting;
+            let (foreground, background) = theme::formatting_colors(formatting, colors);
+
+            let mut css_style = String::new();
+
+            if formatting.bold {
+                css_style.push_str("font-weight: bold; ");
+            }
+            if formatting.italic {
+                css_style.push_str("font-style: italic; ");
+            }
+            match (formatting.underline, formatting.strikethrough) {
+                (true, true) => css_style.push_str("text-decoration: underline line-through; "),
+                (true, false) => css_style.push_str("text-decoration: underline; "),
+                (false, true) => css_style.push_str("text-decoration: line-through; "),
+                (false, false) => {}
+            }
+            if let Some(color) = foreground {
+                let _ = write!(css_style, "color: {}; ", css(color));
+            }
+            if let Some(color) = background {
+                let _ = write!(css_style, "background: {}; ", css(color));
+            }
+
+            if css_style.is_empty() {
+                escape(&span.text)
+            } else {
+                format!(
+                    "<span style=\"{}\">{}</span>",
+                    css_style.trim_end(),
+                    escape(&span.text)
+                )
+            }
+        })
+        .collect()
+}
+
+fn css(color: Color) -> String {
+    let [r, g, b, _] = color.into_rgba8();
+
+    if color.a < 1.0 {
+        format!("rgba({r}, {g}, {b}, {:.2})", color.a)
+    } else {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    History(#[from] history::Error),
+    #[error("encoding failed: {0}")]
+    Encode(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::user::Nick;
+    use crate::{buffer, Theme, User};
+
+    #[test]
+    fn html_messages() {
+        let style = Style {
+            text_log: TextLog::default(),
+            nickname: Nickname {
+                color: buffer::Color::Solid,
+                brackets: buffer::Brackets {
+                    left: "<".into(),
+                    right: ">".into(),
+                },
+            },
+            colors: Theme::default().colors,
+        };
+        let message = Message::test(
+            Source::User(User::from(Nick::from("bob"))),
+            "\x02<b>\x02 & more",
+        );
+
+        let html = to_html(
+            &server::Server::from("libera"),
+            &Kind::Channel("#halloy".into()),
+            &[message],
+            &style,
+        );
+
+        assert!(html.contains("<title>#halloy on libera</title>"));
+        assert!(html.contains(&format!(
+            "<span class=\"nick\" style=\"color: {}\">&lt;bob&gt;</span> ",
+            css(style.colors.text.base)
+        )));
+        assert!(html.contains(
+            "<span class=\"user\"><span style=\"font-weight: bold;\">&lt;b&gt;</span> &amp; more</span>"
+        ));
+    }
+
+    #[test]
+    fn range_of_days() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let range = Range {
+            since: Some(day(2)),
+            until: Some(day(4)),
+        };
+
+        assert!(!range.contains(day(1)));
+        assert!(range.contains(day(2)) && range.contains(day(4)));
+        assert!(range.overlaps_segment(day(1)) && range.overlaps_segment(day(5)));
+        assert!(!range.overlaps_segment(day(6)));
+        assert_eq!(
+            Format::from_path(Path::new("transcript.HTML")),
+            Format::Html
+        );
+    }
+}
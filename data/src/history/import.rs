@@ -0,0 +1,570 @@
+//! Importers for the logs of other IRC clients.
+//!
+//! Logs of WeeChat, irssi, HexChat & ZNC are parsed into messages, w/ their
+//! local timestamps, and merged into the history of a buffer.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+};
+use tokio::fs;
+
+use crate::history::{self, Kind};
+use crate::message::{self, source, Direction, Source, Target};
+use crate::time::Posix;
+use crate::user::Nick;
+use crate::{server, Message, User};
+
+/// Log formats which can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Weechat,
+    Irssi,
+    Hexchat,
+    Znc,
+}
+
+impl Format {
+    /// Detects the format of a log from its first line
+    pub fn detect(content: &str) -> Option<Self> {
+        let line = content.lines().find(|line| !line.trim().is_empty())?;
+
+        if line.starts_with("--- Log opened ") {
+            Some(Format::Irssi)
+        } else if line.starts_with("**** BEGIN LOGGING AT ") {
+            Some(Format::Hexchat)
+        } else if weechat_line(line).is_some() {
+            Some(Format::Weechat)
+        } else if znc_line(line).is_some() {
+            Some(Format::Znc)
+        } else {
+            None
+        }
+    }
+}
+
+/// What happened on a line of a log
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Message {
+        nick: String,
+        text: String,
+    },
+    Action {
+        nick: String,
+        text: String,
+    },
+    Join {
+        nick: String,
+    },
+    Part {
+        nick: String,
+        reason: Option<String>,
+    },
+    Quit {
+        nick: String,
+        reason: Option<String>,
+    },
+    Other(String),
+}
+
+/// Reads the messages of the logs at `paths`, as messages of the buffer of
+/// `kind`
+pub async fn read(paths: Vec<PathBuf>, kind: Kind) -> Result<Vec<Message>, Error> {
+    let mut messages = vec![];
+
+    for path in paths {
+        let bytes = fs::read(&path).await?;
+        // Older logs are often not UTF-8
+        let content = String::from_utf8_lossy(&bytes);
+
+        let events = match Format::detect(&content) {
+            Some(Format::Weechat) => weechat(&content),
+            Some(Format::Irssi) => irssi(&content),
+            Some(Format::Hexchat) => hexchat(&content),
+            Some(Format::Znc) => {
+                let day = date_in_file_name(&path).ok_or(Error::MissingDate(path.clone()))?;
+
+                znc(&content, day)
+            }
+            None => return Err(Error::UnknownFormat(path)),
+        };
+
+        messages.extend(to_messages(&kind, events));
+    }
+
+    Ok(messages)
+}
+
+/// Imports the logs at `paths` into the history of `kind` on disk, returning
+/// how many messages were added. Messages already in the history, such as
+/// when importing the same logs again, are skipped.
+pub async fn import(
+    server: &server::Server,
+    kind: &Kind,
+    paths: Vec<PathBuf>,
+) -> Result<usize, Error> {
+    let messages = read(paths, kind.clone()).await?;
+
+    Ok(history::append_new(server, kind, messages).await?)
+}
+
+fn to_messages(kind: &Kind, events: Vec<(NaiveDateTime, Event)>) -> Vec<Message> {
+    let mut previous = None;
+    let mut repeated = 0;
+
+    events
+        .into_iter()
+        .filter_map(|(time, event)| {
+            // Logs are written w/ (at most) second precision, so lines of the
+            // same second are kept apart & in order by the millisecond
+            if previous == Some(time) {
+                repeated += 1;
+            } else {
+                previous = Some(time);
+                repeated = 0;
+            }
+
+            let server_time = to_utc(&Local, time)? + TimeDelta::try_milliseconds(repeated)?;
+
+            Some(to_message(kind, server_time, event))
+        })
+        .collect()
+}
+
+/// `time` in `timezone` as UTC. Times skipped when the clocks went forward
+/// are shifted forward past the gap, as the log was written around then.
+fn to_utc<Tz: TimeZone>(timezone: &Tz, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    [0, 30, 60, 90, 120].into_iter().find_map(|minutes| {
+        timezone
+            .from_local_datetime(&(time + TimeDelta::try_minutes(minutes)?))
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+    })
+}
+
+fn to_message(kind: &Kind, server_time: DateTime<Utc>, event: Event) -> Message {
+    let server_source =
+        |kind, nick: &str| Source::Server(Some(source::Server::new(kind, Some(Nick::from(nick)))));
+    let reason = |reason: Option<String>| {
+        reason
+            .map(|reason| format!(" ({reason})"))
+            .unwrap_or_default()
+    };
+
+    let (source, text) = match event {
+        Event::Message { nick, text } => {
+            (Source::User(User::from(Nick::from(nick.as_str()))), text)
+        }
+        Event::Action { nick, text } => (
+            Source::Action,
+            message::action_text(Nick::from(nick.as_str()).as_nickref(), &text),
+        ),
+        Event::Join { nick } => (
+            server_source(source::server::Kind::Join, &nick),
+            format!("⟶ {nick} has joined the channel"),
+        ),
+        Event::Part { nick, reason: text } => (
+            server_source(source::server::Kind::Part, &nick),
+            format!("⟵ {nick} has left the channel{}", reason(text)),
+        ),
+        Event::Quit { nick, reason: text } => (
+            server_source(source::server::Kind::Quit, &nick),
+            format!("⟵ {nick} has quit{}", reason(text)),
+        ),
+        Event::Other(text) => (Source::Server(None), format!(" ∙ {text}")),
+    };
+
+    let target = match kind {
+        Kind::Server => Target::Server { source },
+        Kind::Channel(channel) => Target::Channel {
+            channel: channel.to_string(),
+            source,
+        },
+        Kind::Query(nick) => Target::Query {
+            nick: nick.clone(),
+            source,
+        },
+    };
+
+    Message {
+        received_at: Posix::from_seconds(server_time.timestamp().max(0) as u64),
+        server_time,
+        id: None,
+        direction: Direction::Received,
+        target,
+        text,
+        content: Default::default(),
+    }
+}
+
+/// `2024-03-02 12:00:00\tnick\ttext`
+fn weechat(content: &str) -> Vec<(NaiveDateTime, Event)> {
+    content.lines().filter_map(weechat_line).collect()
+}
+
+fn weechat_line(line: &str) -> Option<(NaiveDateTime, Event)> {
+    let mut columns = line.splitn(3, '\t');
+
+    let time = NaiveDateTime::parse_from_str(columns.next()?, "%Y-%m-%d %H:%M:%S").ok()?;
+    let prefix = columns.next()?;
+    let text = columns.next().unwrap_or_default();
+
+    let event = match prefix.trim() {
+        "-->" | "<--" => membership(text).unwrap_or_else(|| Event::Other(text.to_string())),
+        "*" => action(text)?,
+        "" | "--" | "=!=" => Event::Other(text.to_string()),
+        nick => Event::Message {
+            nick: without_prefix(nick),
+            text: text.to_string(),
+        },
+    };
+
+    Some((time, event))
+}
+
+/// `--- Log opened Sat Mar 02 12:00:00 2024`, then `12:00 < nick> text`
+fn irssi(content: &str) -> Vec<(NaiveDateTime, Event)> {
+    let mut day = None;
+
+    content
+        .lines()
+        .filter_map(|line| {
+            if let Some(opened) = line.strip_prefix("--- Log opened ") {
+                day = NaiveDateTime::parse_from_str(opened, "%a %b %d %H:%M:%S %Y")
+                    .ok()
+                    .map(|time| time.date());
+                return None;
+            } else if let Some(changed) = line.strip_prefix("--- Day changed ") {
+                day = NaiveDate::parse_from_str(changed, "%a %b %d %Y").ok();
+                return None;
+            }
+
+            let (time, rest) = line.split_once(' ')?;
+            let time = NaiveTime::parse_from_str(time, "%H:%M:%S")
+                .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+                .ok()?;
+
+            let event = if let Some(text) = rest.strip_prefix("-!- ") {
+                membership(text).unwrap_or_else(|| Event::Other(text.to_string()))
+            } else if let Some(text) = rest.trim_start().strip_prefix("* ") {
+                action(text)?
+            } else {
+                let (nick, text) = rest.strip_prefix('<')?.split_once("> ")?;
+
+                Event::Message {
+                    nick: without_prefix(nick.trim()),
+                    text: text.to_string(),
+                }
+            };
+
+            Some((day?.and_time(time), event))
+        })
+        .collect()
+}
+
+/// `**** BEGIN LOGGING AT Sat Mar  2 12:00:00 2024`, then
+/// `Mar 02 12:00:00 <nick>\ttext`
+fn hexchat(content: &str) -> Vec<(NaiveDateTime, Event)> {
+    let mut year = None;
+    let mut month = 0;
+
+    content
+        .lines()
+        .filter_map(|line| {
+            if let Some(began) = line.strip_prefix("**** BEGIN LOGGING AT ") {
+                let began = began.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                if let Ok(time) = NaiveDateTime::parse_from_str(&began, "%a %b %d %H:%M:%S %Y") {
+                    year = Some(time.year());
+                    month = time.month();
+                }
+
+                return None;
+            }
+
+            let (stamp, rest) = split_words(line, 3)?;
+            let (prefix, text) = rest.split_once('\t')?;
+
+            let time =
+                NaiveDateTime::parse_from_str(&format!("{} {stamp}", year?), "%Y %b %d %H:%M:%S")
+                    .ok()?;
+
+            // Timestamps don't have a year, so count the years passed while
+            // logging
+            let time = if time.month() < month {
+                let next = time.with_year(time.year() + 1)?;
+                year = Some(next.year());
+                next
+            } else {
+                time
+            };
+            month = time.month();
+
+            let event = match prefix.trim() {
+                "-->" | "<--" => membership(text).unwrap_or_else(|| Event::Other(text.to_string())),
+                "*" => action(text)?,
+                prefix => match prefix
+                    .strip_prefix('<')
+                    .and_then(|nick| nick.strip_suffix('>'))
+                {
+                    Some(nick) => Event::Message {
+                        nick: without_prefix(nick),
+                        text: text.to_string(),
+                    },
+                    None => Event::Other(text.to_string()),
+                },
+            };
+
+            Some((time, event))
+        })
+        .collect()
+}
+
+/// `[12:00:00] <nick> text`, w/ the day in the file name
+fn znc(content: &str, day: NaiveDate) -> Vec<(NaiveDateTime, Event)> {
+    content
+        .lines()
+        .filter_map(znc_line)
+        .map(|(time, event)| (day.and_time(time), event))
+        .collect()
+}
+
+fn znc_line(line: &str) -> Option<(NaiveTime, Event)> {
+    let (time, rest) = line.strip_prefix('[')?.split_once("] ")?;
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S").ok()?;
+
+    let event = if let Some(text) = rest.strip_prefix("*** ") {
+        let joins = text.strip_prefix("Joins: ");
+        let parts = text.strip_prefix("Parts: ");
+        let quits = text.strip_prefix("Quits: ");
+
+        // `nick (ident@host) (reason)`
+        let reason = |text: &str| text.split_once(") ").and_then(|(_, text)| reason(text));
+
+        match (joins, parts, quits) {
+            (Some(text), _, _) => Event::Join {
+                nick: text.split_whitespace().next()?.to_string(),
+            },
+            (_, Some(text), _) => Event::Part {
+                nick: text.split_whitespace().next()?.to_string(),
+                reason: reason(text),
+            },
+            (_, _, Some(text)) => Event::Quit {
+                nick: text.split_whitespace().next()?.to_string(),
+                reason: reason(text),
+            },
+            _ => Event::Other(text.to_string()),
+        }
+    } else if let Some(text) = rest.strip_prefix("* ") {
+        action(text)?
+    } else {
+        let (nick, text) = rest.strip_prefix('<')?.split_once("> ")?;
+
+        Event::Message {
+            nick: nick.to_string(),
+            text: text.to_string(),
+        }
+    };
+
+    Some((time, event))
+}
+
+/// Joins, parts & quits, written as `nick (ident@host) has joined #channel`,
+/// `nick (ident@host) has left #channel (reason)` & `nick has quit (reason)`
+/// w/ parentheses or brackets
+fn membership(text: &str) -> Option<Event> {
+    let nick = text.split_whitespace().next()?.to_string();
+
+    if text.contains(" has joined ") {
+        Some(Event::Join { nick })
+    } else if let Some((_, left)) = text.split_once(" has left ") {
+        Some(Event::Part {
+            nick,
+            reason: left.split_once(' ').and_then(|(_, text)| reason(text)),
+        })
+    } else {
+        let (_, quit) = text.split_once(" has quit")?;
+
+        Some(Event::Quit {
+            nick,
+            reason: reason(quit),
+        })
+    }
+}
+
+/// `nick action`
+fn action(text: &str) -> Option<Event> {
+    let (nick, text) = text.trim().split_once(' ').unwrap_or((text.trim(), ""));
+
+    (!nick.is_empty()).then(|| Event::Action {
+        nick: nick.to_string(),
+        text: text.to_string(),
+    })
+}
+
+/// A reason w/o its surrounding parentheses or brackets
+fn reason(text: &str) -> Option<String> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix(['(', '['])
+        .and_then(|text| text.strip_suffix([')', ']']))
+        .unwrap_or(text);
+
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// A nickname w/o its channel membership prefix, such as `@` for operators
+fn without_prefix(nick: &str) -> String {
+    nick.trim_start_matches(['~', '&', '@', '%', '+', ' '])
+        .to_string()
+}
+
+/// The first `count` words of `text` & the rest after them
+fn split_words(text: &str, count: usize) -> Option<(&str, &str)> {
+    let mut end = 0;
+
+    for _ in 0..count {
+        let start = end + text[end..].find(|c: char| !c.is_whitespace())?;
+        end = start + text[start..].find(char::is_whitespace)?;
+    }
+
+    Some((&text[..end], text[end..].trim_start_matches(' ')))
+}
+
+/// ZNC names logs by day, as `2024-03-02.log` or `#channel_20240302.log`
+fn date_in_file_name(path: &Path) -> Option<NaiveDate> {
+    let stem = path.file_stem()?.to_str()?;
+
+    [("%Y-%m-%d", 10), ("%Y%m%d", 8)]
+        .into_iter()
+        .find_map(|(format, len)| {
+            let date = stem.get(stem.len().checked_sub(len)?..)?;
+
+            NaiveDate::parse_from_str(date, format).ok()
+        })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unrecognized log format: {}", .0.display())]
+    UnknownFormat(PathBuf),
+    #[error("no date in the name of ZNC log {}", .0.display())]
+    MissingDate(PathBuf),
+    #[error(transparent)]
+    History(#[from] history::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn at(day: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{day} {time}"), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn message(nick: &str, text: &str) -> Event {
+        Event::Message {
+            nick: nick.into(),
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn parse_logs() {
+        let expected = vec![
+            (
+                at("2024-03-02", "23:59:00"),
+                Event::Join { nick: "bob".into() },
+            ),
+            (at("2024-03-02", "23:59:00"), message("bob", "hello world")),
+            (
+                at("2024-03-03", "00:01:00"),
+                Event::Action {
+                    nick: "bob".into(),
+                    text: "waves".into(),
+                },
+            ),
+            (
+                at("2024-03-03", "00:02:00"),
+                Event::Quit {
+                    nick: "bob".into(),
+                    reason: Some("Leaving".into()),
+                },
+            ),
+        ];
+
+        let log = "2024-03-02 23:59:00\t-->\tbob (~bob@host) has joined #halloy\n\
+            2024-03-02 23:59:00\t@bob\thello world\n\
+            2024-03-03 00:01:00\t *\tbob waves\n\
+            2024-03-03 00:02:00\t<--\tbob (~bob@host) has quit (Leaving)\n";
+        assert_eq!(Format::detect(log), Some(Format::Weechat));
+        assert_eq!(weechat(log), expected);
+
+        let log = "--- Log opened Sat Mar 02 23:58:00 2024\n\
+            23:59 -!- bob [~bob@host] has joined #halloy\n\
+            23:59 <@bob> hello world\n\
+            --- Day changed Sun Mar 03 2024\n\
+            00:01  * bob waves\n\
+            00:02 -!- bob [~bob@host] has quit [Leaving]\n";
+        assert_eq!(Format::detect(log), Some(Format::Irssi));
+        assert_eq!(irssi(log), expected);
+
+        let log = "**** BEGIN LOGGING AT Sat Mar  2 23:58:00 2024\n\
+            \n\
+            Mar 02 23:59:00 -->\tbob (~bob@host) has joined #halloy\n\
+            Mar 02 23:59:00 <bob>\thello world\n\
+            Mar 03 00:01:00 *\tbob waves\n\
+            Mar 03 00:02:00 <--\tbob has quit (Leaving)\n";
+        assert_eq!(Format::detect(log), Some(Format::Hexchat));
+        assert_eq!(hexchat(log), expected);
+
+        let day = date_in_file_name(Path::new("znc/libera/#halloy/2024-03-02.log")).unwrap();
+        let log = "[23:59:00] *** Joins: bob (~bob@host)\n\
+            [23:59:00] <bob> hello world\n";
+        assert_eq!(Format::detect(log), Some(Format::Znc));
+        assert_eq!(znc(log, day), expected[..2]);
+        assert_eq!(
+            znc_line("[00:02:00] *** Quits: bob (~bob@host) (Leaving)").map(|(_, event)| event),
+            Some(expected[3].1.clone())
+        );
+        assert_eq!(
+            date_in_file_name(Path::new("libera_#halloy_20240302.log")),
+            Some(day)
+        );
+    }
+
+    #[test]
+    fn hexchat_year_changes() {
+        let log = "**** BEGIN LOGGING AT Tue Dec 31 23:00:00 2024\n\
+            Dec 31 23:59:59 <bob>\thappy\n\
+            Jan 01 00:00:00 <bob>\tnew year\n";
+
+        assert_eq!(
+            hexchat(log),
+            vec![
+                (at("2024-12-31", "23:59:59"), message("bob", "happy")),
+                (at("2025-01-01", "00:00:00"), message("bob", "new year")),
+            ]
+        );
+    }
+
+    #[test]
+    fn same_second_messages() {
+        let time = at("2024-03-02", "12:00:00");
+        let kind = Kind::Channel("#halloy".into());
+
+        let messages = to_messages(
+            &kind,
+            vec![(time, message("bob", "one")), (time, message("bob", "one"))],
+        );
+
+        assert_eq!(messages.len(), 2);
+        assert!(!messages[0].is_duplicate(&messages[1]));
+        assert!(messages[0].server_time < messages[1].server_time);
+    }
+}
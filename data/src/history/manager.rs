@@ -1,12 +1,13 @@
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
-use chrono::{DateTime, Utc};
-use futures::future::BoxFuture;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future::{BoxFuture, OptionFuture};
 use futures::{future, Future, FutureExt};
 use itertools::Itertools;
 use tokio::time::Instant;
 
-use crate::history::{self, History};
+use crate::history::{self, export, text, History};
 use crate::message::{self, Limit};
 use crate::time::Posix;
 use crate::user::Nick;
@@ -19,36 +20,99 @@ pub struct Resource {
     pub kind: history::Kind,
 }
 
+impl Resource {
+    pub fn buffer(&self) -> Buffer {
+        match &self.kind {
+            history::Kind::Server => Buffer::Server(self.server.clone()),
+            history::Kind::Channel(channel) => {
+                Buffer::Channel(self.server.clone(), channel.clone())
+            }
+            history::Kind::Query(nick) => Buffer::Query(self.server.clone(), nick.clone()),
+        }
+    }
+}
+
+impl From<Buffer> for Resource {
+    fn from(buffer: Buffer) -> Self {
+        match buffer {
+            Buffer::Server(server) => Resource {
+                server,
+                kind: history::Kind::Server,
+            },
+            Buffer::Channel(server, channel) => Resource {
+                server,
+                kind: history::Kind::Channel(channel),
+            },
+            Buffer::Query(server, nick) => Resource {
+                server,
+                kind: history::Kind::Query(nick),
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Message {
     Loaded(
         server::Server,
         history::Kind,
-        Result<Vec<crate::Message>, history::Error>,
+        Result<history::Page, history::Error>,
     ),
+    LoadedOlder(
+        server::Server,
+        history::Kind,
+        Result<history::Page, history::Error>,
+    ),
+    Pruned(Result<usize, history::Error>),
     Closed(server::Server, history::Kind, Result<(), history::Error>),
     Flushed(server::Server, history::Kind, Result<(), history::Error>),
+    Logged(Result<(), history::Error>),
+    Imported(
+        server::Server,
+        history::Kind,
+        Result<Vec<NaiveDate>, history::Error>,
+    ),
+    Exported(
+        server::Server,
+        history::Kind,
+        PathBuf,
+        Result<usize, export::Error>,
+    ),
 }
 
 #[derive(Debug, Default)]
 pub struct Manager {
     resources: HashSet<Resource>,
+    /// Days to load history back to, once a resource is tracked
+    since: HashMap<Resource, NaiveDate>,
     data: Data,
+    logger: text::Logger,
 }
 
 impl Manager {
+    pub fn new(config: &config::History) -> Self {
+        let mut manager = Self::default();
+        manager.configure(config);
+        manager
+    }
+
     pub fn track(&mut self, new_resources: HashSet<Resource>) -> Vec<BoxFuture<'static, Message>> {
         let added = new_resources.difference(&self.resources).cloned();
         let removed = self.resources.difference(&new_resources).cloned();
 
-        let added = added.into_iter().map(|resource| {
-            async move {
-                history::load(&resource.server.clone(), &resource.kind.clone())
-                    .map(move |result| Message::Loaded(resource.server, resource.kind, result))
-                    .await
-            }
-            .boxed()
-        });
+        let added = added
+            .map(|resource| {
+                let since = self.since.remove(&resource);
+
+                async move {
+                    history::load(&resource.server.clone(), &resource.kind.clone(), since)
+                        .map(move |result| Message::Loaded(resource.server, resource.kind, result))
+                        .await
+                }
+                .boxed()
+            })
+            .collect::<Vec<_>>();
+        self.since.clear();
 
         let removed = removed.into_iter().filter_map(|resource| {
             self.data
@@ -59,7 +123,7 @@ impl Manager {
                 })
         });
 
-        let tasks = added.chain(removed).collect();
+        let tasks = added.into_iter().chain(removed).collect();
 
         self.resources = new_resources;
 
@@ -68,16 +132,32 @@ impl Manager {
 
     pub fn update(&mut self, message: Message) {
         match message {
-            Message::Loaded(server, kind, Ok(messages)) => {
+            Message::Loaded(server, kind, Ok(page)) => {
                 log::debug!(
                     "loaded history for {kind} on {server}: {} messages",
-                    messages.len()
+                    page.messages.len()
                 );
-                self.data.loaded(server, kind, messages);
+                self.data.loaded(server, kind, page);
             }
             Message::Loaded(server, kind, Err(error)) => {
                 log::warn!("failed to load history for {kind} on {server}: {error}");
             }
+            Message::LoadedOlder(server, kind, Ok(page)) => {
+                log::debug!(
+                    "loaded older history for {kind} on {server}: {} messages",
+                    page.messages.len()
+                );
+                self.data.loaded_older(server, kind, page);
+            }
+            Message::LoadedOlder(server, kind, Err(error)) => {
+                log::warn!("failed to load older history for {kind} on {server}: {error}");
+            }
+            Message::Pruned(Ok(deleted)) => {
+                log::debug!("pruned history: {deleted} segments deleted");
+            }
+            Message::Pruned(Err(error)) => {
+                log::warn!("failed to prune history: {error}");
+            }
             Message::Closed(server, kind, Ok(_)) => {
                 log::debug!("closed history for {kind} on {server}",);
             }
@@ -90,11 +170,143 @@ impl Manager {
             Message::Flushed(server, kind, Err(error)) => {
                 log::warn!("failed to flush history for {kind} on {server}: {error}")
             }
+            Message::Logged(Ok(_)) => {
+                log::debug!("appended messages to text logs");
+            }
+            Message::Logged(Err(error)) => {
+                log::warn!("failed to append messages to text logs: {error}")
+            }
+            Message::Imported(server, kind, Ok(days)) => {
+                log::debug!("imported logs for {kind} on {server}");
+                self.data.imported(&server, &kind, days);
+            }
+            Message::Imported(server, kind, Err(error)) => {
+                log::warn!("failed to import logs for {kind} on {server}: {error}")
+            }
+            Message::Exported(server, kind, path, Ok(count)) => {
+                log::info!("exported {count} messages of {kind} on {server} to {path:?}");
+            }
+            Message::Exported(server, kind, path, Err(error)) => {
+                log::warn!("failed to export {kind} on {server} to {path:?}: {error}")
+            }
         }
     }
 
+    /// Loads the page of messages preceding those loaded for `resource`,
+    /// unless it's fully loaded or already loading
+    pub fn load_older(&mut self, resource: &Resource) -> Option<BoxFuture<'static, Message>> {
+        self.data.load_older(resource, None)
+    }
+
+    /// Loads the history of `resource` back to `day`, such as to show a
+    /// message found by search. If it isn't loaded yet, it's loaded back to
+    /// `day` once tracked.
+    pub fn load_since(
+        &mut self,
+        resource: &Resource,
+        day: NaiveDate,
+    ) -> Option<BoxFuture<'static, Message>> {
+        let is_loaded = matches!(
+            self.data
+                .map
+                .get(&resource.server)
+                .and_then(|map| map.get(&resource.kind)),
+            Some(History::Full { .. })
+        );
+
+        if is_loaded {
+            self.data.load_older(resource, Some(day))
+        } else {
+            self.since.insert(resource.clone(), day);
+            None
+        }
+    }
+
+    /// Applies the history config, such as whether to write text logs
+    pub fn configure(&mut self, config: &config::History) {
+        self.logger.configure(&config.text_log);
+    }
+
+    /// Merges messages imported from the logs of other clients into the
+    /// history of `resource`
+    pub fn import(
+        &mut self,
+        resource: Resource,
+        messages: Vec<crate::Message>,
+    ) -> BoxFuture<'static, Message> {
+        let to_append = match self
+            .data
+            .map
+            .get_mut(&resource.server)
+            .and_then(|map| map.get_mut(&resource.kind))
+        {
+            Some(history) => history.import(messages),
+            None => messages,
+        };
+
+        let days = to_append
+            .iter()
+            .map(|message| message.server_time.date_naive())
+            .unique()
+            .collect();
+
+        async move {
+            let result = history::append_new(&resource.server, &resource.kind, to_append)
+                .await
+                .map(|_| days);
+
+            Message::Imported(resource.server, resource.kind, result)
+        }
+        .boxed()
+    }
+
+    /// Writes the history of `resource` in `range` to `path`, including the
+    /// messages which haven't been flushed yet
+    pub fn export(
+        &self,
+        resource: Resource,
+        range: export::Range,
+        path: PathBuf,
+        style: export::Style,
+    ) -> BoxFuture<'static, Message> {
+        let unflushed = self
+            .data
+            .map
+            .get(&resource.server)
+            .and_then(|map| map.get(&resource.kind))
+            .map(History::unflushed)
+            .unwrap_or_default();
+
+        async move {
+            let result = export::export(
+                resource.server.clone(),
+                resource.kind.clone(),
+                range,
+                unflushed,
+                path.clone(),
+                style,
+            )
+            .await;
+
+            Message::Exported(resource.server, resource.kind, path, result)
+        }
+        .boxed()
+    }
+
+    /// Deletes the history older than configured
+    pub fn prune(&self, config: &config::History) -> Option<BoxFuture<'static, Message>> {
+        let max_age = config.max_age?;
+
+        Some(history::prune(max_age).map(Message::Pruned).boxed())
+    }
+
     pub fn tick(&mut self, now: Instant) -> Vec<BoxFuture<'static, Message>> {
-        self.data.flush_all(now)
+        let logged = self
+            .logger
+            .flush()
+            .map(|task| task.map(Message::Logged).boxed());
+
+        self.data.flush_all(now).into_iter().chain(logged).collect()
     }
 
     pub fn close(
@@ -103,9 +315,10 @@ impl Manager {
         kind: history::Kind,
     ) -> Option<impl Future<Output = ()>> {
         let history = self.data.map.get_mut(&server)?.remove(&kind)?;
+        let connected_at = self.data.connected_at.get(&server).copied();
 
         Some(async move {
-            match history.close().await {
+            match history.close(connected_at).await {
                 Ok(_) => {
                     log::debug!("closed history for {kind} on {server}",);
                 }
@@ -118,11 +331,14 @@ impl Manager {
 
     pub fn close_server(&mut self, server: Server) -> Option<impl Future<Output = ()>> {
         let map = self.data.map.remove(&server)?;
+        let connected_at = self.data.connected_at.remove(&server);
 
         Some(async move {
             let tasks = map.into_iter().map(move |(kind, state)| {
                 let server = server.clone();
-                state.close().map(move |result| (server, kind, result))
+                state
+                    .close(connected_at)
+                    .map(move |result| (server, kind, result))
             });
 
             let results = future::join_all(tasks).await;
@@ -141,13 +357,24 @@ impl Manager {
     }
 
     pub fn close_all(&mut self) -> impl Future<Output = ()> {
-        let map = std::mem::take(&mut self.data).map;
+        let Data {
+            map, connected_at, ..
+        } = std::mem::take(&mut self.data);
+        let logged = self.logger.flush();
 
         async move {
+            if let Some(Err(error)) = OptionFuture::from(logged).await {
+                log::warn!("failed to append messages to text logs: {error}");
+            }
+
             let tasks = map.into_iter().flat_map(|(server, map)| {
+                let connected_at = connected_at.get(&server).copied();
+
                 map.into_iter().map(move |(kind, state)| {
                     let server = server.clone();
-                    state.close().map(move |result| (server, kind, result))
+                    state
+                        .close(connected_at)
+                        .map(move |result| (server, kind, result))
                 })
             });
 
@@ -166,8 +393,8 @@ impl Manager {
         }
     }
 
-    pub fn record_input(&mut self, input: Input, user: User) {
-        if let Some(message) = input.message(user) {
+    pub fn record_input(&mut self, input: Input, user: User, chantypes: &[char]) {
+        for message in input.messages(user, chantypes) {
             self.record_message(input.server(), message);
         }
 
@@ -181,11 +408,61 @@ impl Manager {
     }
 
     pub fn record_message(&mut self, server: &Server, message: crate::Message) {
-        self.data.add_message(
-            server.clone(),
-            history::Kind::from(message.target.clone()),
-            message,
-        );
+        let kind = history::Kind::from(message.target.clone());
+
+        if let Some(message) = self.data.add_message(server.clone(), kind.clone(), message) {
+            self.logger.record(server, &kind, message);
+        }
+    }
+
+    /// Merges messages played back by the server into their histories
+    pub fn record_chathistory(&mut self, server: &Server, messages: Vec<crate::Message>) {
+        let by_kind = messages
+            .into_iter()
+            .into_group_map_by(|message| history::Kind::from(message.target.clone()));
+
+        for (kind, messages) in by_kind {
+            let added = self
+                .data
+                .add_messages(server.clone(), kind.clone(), messages);
+
+            for message in &added {
+                self.logger.record(server, &kind, message);
+            }
+        }
+    }
+
+    /// Server time of the latest message stored for `kind`, which
+    /// playback is requested after
+    pub fn latest_server_time(
+        &self,
+        server: &Server,
+        kind: &history::Kind,
+    ) -> BoxFuture<'static, Option<DateTime<Utc>>> {
+        let in_memory = self
+            .data
+            .map
+            .get(server)
+            .and_then(|map| map.get(kind))
+            .and_then(History::latest_server_time);
+
+        if let Some(latest) = in_memory {
+            return future::ready(latest).boxed();
+        }
+
+        let server = server.clone();
+        let kind = kind.clone();
+
+        async move {
+            match history::latest_server_time(&server, &kind).await {
+                Ok(latest) => latest,
+                Err(error) => {
+                    log::warn!("failed to load history for {kind} on {server}: {error}");
+                    None
+                }
+            }
+        }
+        .boxed()
     }
 
     pub fn get_channel_messages(
@@ -197,7 +474,7 @@ impl Manager {
     ) -> Option<history::View<'_>> {
         self.data.history_view(
             server,
-            &history::Kind::Channel(channel.to_string()),
+            &history::Kind::Channel(channel.into()),
             limit,
             buffer_config,
         )
@@ -271,16 +548,13 @@ impl Manager {
     ) {
         let map = self.data.map.entry(server.clone()).or_default();
 
-        let channels = map
-            .keys()
-            .filter_map(|kind| {
-                if let history::Kind::Channel(channel) = kind {
-                    Some(channel)
-                } else {
-                    None
-                }
-            })
-            .cloned();
+        let channels = map.keys().filter_map(|kind| {
+            if let history::Kind::Channel(channel) = kind {
+                Some(channel.to_string())
+            } else {
+                None
+            }
+        });
         let mut queries = map
             .keys()
             .filter_map(|kind| {
@@ -294,14 +568,25 @@ impl Manager {
 
         let messages = match broadcast {
             Broadcast::Connecting => message::broadcast::connecting(sent_time),
-            Broadcast::Connected => message::broadcast::connected(sent_time),
-            Broadcast::ConnectionFailed { error } => {
-                message::broadcast::connection_failed(error, sent_time)
+            Broadcast::Connected { address } => {
+                self.data.connected_at.insert(server.clone(), sent_time);
+
+                message::broadcast::connected(&address, sent_time)
+            }
+            Broadcast::ConnectionFailed { address, error } => {
+                message::broadcast::connection_failed(&address, error, sent_time)
+            }
+            Broadcast::SaslFailed { error, disconnect } => {
+                message::broadcast::sasl_failed(error, disconnect, sent_time)
             }
             Broadcast::Disconnected { error } => {
                 message::broadcast::disconnected(channels, queries, error, sent_time)
             }
-            Broadcast::Reconnected => message::broadcast::reconnected(channels, queries, sent_time),
+            Broadcast::Reconnected { address } => {
+                self.data.connected_at.insert(server.clone(), sent_time);
+
+                message::broadcast::reconnected(channels, queries, &address, sent_time)
+            }
             Broadcast::Quit {
                 user,
                 comment,
@@ -369,7 +654,7 @@ fn with_limit<'a>(
             collected[length.saturating_sub(n)..length].to_vec()
         }
         Some(Limit::Since(timestamp)) => messages
-            .skip_while(|message| message.received_at < timestamp)
+            .skip_while(|message| message.server_time < timestamp)
             .collect(),
         None => messages.collect(),
     }
@@ -378,18 +663,21 @@ fn with_limit<'a>(
 #[derive(Debug, Default)]
 struct Data {
     map: HashMap<server::Server, HashMap<history::Kind, History>>,
+    /// When each server was last connected to, before which messages are
+    /// played back
+    connected_at: HashMap<server::Server, DateTime<Utc>>,
     input: input::Storage,
 }
 
 impl Data {
-    fn loaded(
-        &mut self,
-        server: server::Server,
-        kind: history::Kind,
-        mut messages: Vec<crate::Message>,
-    ) {
+    fn loaded(&mut self, server: server::Server, kind: history::Kind, page: history::Page) {
         use std::collections::hash_map;
 
+        let history::Page {
+            mut messages,
+            older,
+        } = page;
+
         match self
             .map
             .entry(server.clone())
@@ -405,11 +693,19 @@ impl Data {
                 } => {
                     let last_received_at = *last_received_at;
                     let opened_at = *opened_at;
-                    messages.extend(std::mem::take(new_messages));
+                    // Messages received while partial haven't been written yet
+                    let unflushed = history::dedupe_received(
+                        &messages,
+                        std::mem::take(new_messages),
+                        self.connected_at.get(&server).copied(),
+                    );
+                    history::merge_sorted(&mut messages, unflushed.clone());
                     entry.insert(History::Full {
                         server,
                         kind,
                         messages,
+                        unflushed,
+                        older,
                         last_received_at,
                         opened_at,
                     });
@@ -419,6 +715,8 @@ impl Data {
                         server,
                         kind,
                         messages,
+                        unflushed: vec![],
+                        older,
                         last_received_at: None,
                         opened_at: Posix::now(),
                     });
@@ -429,6 +727,8 @@ impl Data {
                     server,
                     kind,
                     messages,
+                    unflushed: vec![],
+                    older,
                     last_received_at: None,
                     opened_at: Posix::now(),
                 });
@@ -436,6 +736,35 @@ impl Data {
         }
     }
 
+    fn load_older(
+        &mut self,
+        resource: &Resource,
+        since: Option<NaiveDate>,
+    ) -> Option<BoxFuture<'static, Message>> {
+        let older = self
+            .map
+            .get_mut(&resource.server)?
+            .get_mut(&resource.kind)?
+            .take_older(since)?;
+
+        let Resource { server, kind } = resource.clone();
+
+        Some(
+            async move {
+                let result = history::load_older(&server, &kind, older, since).await;
+
+                Message::LoadedOlder(server, kind, result)
+            }
+            .boxed(),
+        )
+    }
+
+    fn loaded_older(&mut self, server: server::Server, kind: history::Kind, page: history::Page) {
+        if let Some(history) = self.map.get_mut(&server).and_then(|map| map.get_mut(&kind)) {
+            history.add_older(page);
+        }
+    }
+
     fn history_view(
         &self,
         server: &server::Server,
@@ -445,6 +774,7 @@ impl Data {
     ) -> Option<history::View> {
         let History::Full {
             messages,
+            older,
             opened_at,
             ..
         } = self.map.get(server)?.get(kind)?
@@ -507,6 +837,7 @@ impl Data {
 
         Some(history::View {
             total,
+            has_older: !older.is_empty(),
             old_messages: old.to_vec(),
             new_messages: new.to_vec(),
         })
@@ -517,13 +848,35 @@ impl Data {
         server: server::Server,
         kind: history::Kind,
         message: crate::Message,
-    ) {
+    ) -> Option<&crate::Message> {
+        let connected_at = self.connected_at.get(&server).copied();
+
         self.map
             .entry(server.clone())
             .or_default()
             .entry(kind.clone())
             .or_insert_with(|| History::partial(server, kind, message.received_at))
-            .add_message(message)
+            .add_message(message, connected_at)
+    }
+
+    fn add_messages(
+        &mut self,
+        server: server::Server,
+        kind: history::Kind,
+        messages: Vec<crate::Message>,
+    ) -> Vec<crate::Message> {
+        self.map
+            .entry(server.clone())
+            .or_default()
+            .entry(kind.clone())
+            .or_insert_with(|| History::partial(server, kind, Posix::now()))
+            .add_messages(messages)
+    }
+
+    fn imported(&mut self, server: &server::Server, kind: &history::Kind, days: Vec<NaiveDate>) {
+        if let Some(history) = self.map.get_mut(server).and_then(|map| map.get_mut(kind)) {
+            history.add_older_days(days);
+        }
     }
 
     fn untrack(
@@ -540,11 +893,13 @@ impl Data {
         self.map
             .iter_mut()
             .flat_map(|(server, map)| {
-                map.iter_mut().filter_map(|(kind, state)| {
+                let connected_at = self.connected_at.get(server).copied();
+
+                map.iter_mut().filter_map(move |(kind, state)| {
                     let server = server.clone();
                     let kind = kind.clone();
 
-                    state.flush(now).map(move |task| {
+                    state.flush(now, connected_at).map(move |task| {
                         task.map(move |result| Message::Flushed(server, kind, result))
                             .boxed()
                     })
@@ -574,14 +929,23 @@ fn smart_filter_message(
 #[derive(Debug, Clone)]
 pub enum Broadcast {
     Connecting,
-    Connected,
+    Connected {
+        address: String,
+    },
     ConnectionFailed {
+        address: String,
         error: String,
     },
+    SaslFailed {
+        error: String,
+        disconnect: bool,
+    },
     Disconnected {
         error: Option<String>,
     },
-    Reconnected,
+    Reconnected {
+        address: String,
+    },
     Quit {
         user: User,
         comment: Option<String>,
@@ -599,3 +963,68 @@ pub enum Broadcast {
         user_channels: Vec<String>,
     },
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::message::Source;
+
+    fn line(hour: u32, minute: u32, text: &str) -> crate::Message {
+        crate::Message {
+            server_time: Utc.with_ymd_and_hms(2024, 3, 2, hour, minute, 0).unwrap(),
+            ..crate::Message::test(Source::User(User::from(Nick::from("casper"))), text)
+        }
+    }
+
+    #[test]
+    fn bouncer_playback_is_deduped() {
+        let server = Server::from("bouncer");
+        let config = Config::default();
+        let mut manager = Manager::default();
+
+        let connected_at = |hour| Utc.with_ymd_and_hms(2024, 3, 2, hour, 0, 0).unwrap();
+        let address = || "irc.example.org".to_string();
+
+        manager.broadcast(
+            &server,
+            Broadcast::Connected { address: address() },
+            &config,
+            connected_at(12),
+        );
+        // Played back w/o a `msgid` on connecting, then received live
+        manager.record_message(&server, line(11, 0, "hi"));
+        manager.record_message(&server, line(11, 1, "hello"));
+        manager.record_message(&server, line(12, 30, "live"));
+
+        manager.broadcast(
+            &server,
+            Broadcast::Reconnected { address: address() },
+            &config,
+            connected_at(13),
+        );
+        manager.record_message(&server, line(11, 0, "hi"));
+        manager.record_message(&server, line(11, 1, "hello"));
+        manager.record_message(&server, line(12, 30, "live"));
+        // Live repeats are kept
+        manager.record_message(&server, line(13, 5, "hi"));
+        manager.record_message(&server, line(13, 5, "hi"));
+
+        let Some(History::Partial { messages, .. }) = manager
+            .data
+            .map
+            .get(&server)
+            .and_then(|map| map.get(&history::Kind::Channel("#halloy".into())))
+        else {
+            panic!("history wasn't created");
+        };
+
+        // W/o the status messages of reconnecting
+        assert!(messages
+            .iter()
+            .filter(|message| matches!(message.target.source(), Source::User(_)))
+            .map(|message| message.text.as_str())
+            .eq(["hi", "hello", "live", "hi", "hi"]));
+    }
+}
@@ -0,0 +1,323 @@
+//! Plain-text logs, one file per network, buffer & day, for reading history
+//! with standard tools.
+//!
+//! Messages are written as they're added to history, and existing history
+//! can be exported with [`export`].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDate};
+use futures::Future;
+use itertools::Itertools;
+use tokio::fs;
+
+use crate::config::history::TextLog;
+use crate::history::{self, Error, Kind};
+use crate::isupport::CaseMap;
+use crate::message::Source;
+use crate::{formatting, search, server, Buffer, Message};
+
+const LOG_EXTENSION: &str = ".log";
+
+/// Collects the messages added to history, to append them to the logs
+#[derive(Debug, Default)]
+pub struct Logger {
+    /// Set when logging is enabled
+    config: Option<TextLog>,
+    pending: Vec<(Buffer, Message)>,
+}
+
+impl Logger {
+    pub fn configure(&mut self, config: &TextLog) {
+        self.config = config.enabled.then(|| config.clone());
+    }
+
+    pub fn record(&mut self, server: &server::Server, kind: &Kind, message: &Message) {
+        if self.config.is_none() {
+            return;
+        }
+
+        let buffer = history::Resource {
+            server: server.clone(),
+            kind: kind.clone(),
+        }
+        .buffer();
+
+        self.pending.push((buffer, message.clone()));
+    }
+
+    /// Appends the messages recorded since the last flush to their logs
+    pub fn flush(&mut self) -> Option<impl Future<Output = Result<(), Error>>> {
+        let config = self.config.as_ref()?;
+
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let directory = config.directory();
+        let files = std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|(buffer, message)| {
+                let day = message.server_time.with_timezone(&Local).date_naive();
+                let path = path(&directory, &buffer, day);
+
+                (path, line(config, &message))
+            })
+            .into_group_map();
+
+        Some(async move {
+            for (path, lines) in files {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+
+                history::append_to(&path, lines.concat().as_bytes()).await?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+/// What [`export`] did
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Export {
+    /// Log files written
+    pub written: usize,
+    /// Histories which weren't exported, as their buffer is unknown
+    pub skipped: usize,
+}
+
+/// Writes the logs of all stored history, replacing the logs of the same
+/// days. History written to a single file by older versions is migrated
+/// first, if it belongs to one of `servers`.
+pub async fn export(config: TextLog, servers: &[server::Server]) -> Result<Export, Error> {
+    let directory = config.directory();
+    let root = history::root_dir().await?;
+    let mut export = Export::default();
+
+    for path in history::migrate_files(servers).await? {
+        log::warn!("skipped exporting history at {}", path.display());
+        export.skipped += 1;
+    }
+
+    let mut entries = fs::read_dir(&root).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+
+        let dir = entry.path();
+
+        // History is indexed w/ its buffer when written
+        let Some((buffer, days)) = search::read_index(&dir).await? else {
+            if !history::segments(&dir).await?.is_empty() {
+                log::warn!("skipped exporting history at {}", dir.display());
+                export.skipped += 1;
+            }
+
+            continue;
+        };
+
+        let mut files = BTreeMap::<PathBuf, String>::new();
+
+        for day in days.into_keys() {
+            let messages =
+                history::read_segment_if_exists(&history::segment_path(&dir, day)).await?;
+
+            // Segments are split by UTC day & logs by local day
+            for message in messages {
+                let day = message.server_time.with_timezone(&Local).date_naive();
+
+                files
+                    .entry(path(&directory, &buffer, day))
+                    .or_default()
+                    .push_str(&line(&config, &message));
+            }
+        }
+
+        for (path, content) in files {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+
+            fs::write(&path, content).await?;
+            export.written += 1;
+        }
+    }
+
+    Ok(export)
+}
+
+/// Log file of `buffer` for `day`. Server messages are logged in the
+/// network's directory & other buffers in a directory of their own.
+fn path(directory: &Path, buffer: &Buffer, day: NaiveDate) -> PathBuf {
+    let casemapping = CaseMap::default();
+
+    let dir = match buffer {
+        Buffer::Server(server) => directory.join(file_name(server.as_ref())),
+        Buffer::Channel(server, channel) => directory
+            .join(file_name(server.as_ref()))
+            .join(file_name(channel.normalized())),
+        Buffer::Query(server, nick) => directory
+            .join(file_name(server.as_ref()))
+            .join(file_name(&casemapping.normalize(nick.as_ref()))),
+    };
+
+    dir.join(format!("{day}{LOG_EXTENSION}"))
+}
+
+/// `name` w/ the characters which aren't allowed in file names replaced
+fn file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    if name.chars().all(|c| c == '.') {
+        name.replace('.', "_")
+    } else {
+        name
+    }
+}
+
+/// `message` formatted by the configured template, w/o its IRC formatting
+pub(crate) fn line(config: &TextLog, message: &Message) -> String {
+    let time = message
+        .server_time
+        .with_timezone(&Local)
+        .format(&config.timestamp)
+        .to_string();
+
+    let (source, nick, text) = match message.target.source() {
+        Source::User(user) => (
+            format!("<{}>", user.nickname()),
+            user.nickname().to_string(),
+            message.text.as_str(),
+        ),
+        Source::Action => (
+            "*".to_string(),
+            message.action_sender().unwrap_or_default().to_string(),
+            message.action_text().unwrap_or_default(),
+        ),
+        Source::Server(_) | Source::Internal(_) => {
+            ("-!-".to_string(), String::new(), message.text.as_str())
+        }
+    };
+    let text = formatting::strip(text).replace(['\r', '\n'], " ");
+
+    let mut line = String::with_capacity(config.template.len() + text.len());
+    let mut rest = config.template.as_str();
+
+    // Replaced in a single pass, so placeholders in nicknames & text are
+    // left as is
+    while let Some(start) = rest.find('{') {
+        line.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let placeholder = [
+            ("{time}", &time),
+            ("{source}", &source),
+            ("{nick}", &nick),
+            ("{text}", &text),
+        ]
+        .into_iter()
+        .find(|(placeholder, _)| rest.starts_with(placeholder));
+
+        match placeholder {
+            Some((placeholder, value)) => {
+                line.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                line.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    line.push_str(rest);
+    line.push('\n');
+
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::user::Nick;
+    use crate::User;
+
+    #[test]
+    fn format_lines() {
+        let config = TextLog {
+            template: "{source} {text}".into(),
+            ..TextLog::default()
+        };
+        let message = Message::test;
+
+        let user = Source::User(User::from(Nick::from("bob")));
+
+        assert_eq!(
+            line(&config, &message(user, "\x02hello\x02 world")),
+            "<bob> hello world\n"
+        );
+        assert_eq!(
+            line(
+                &TextLog {
+                    template: "{{source}} {text}".into(),
+                    ..config.clone()
+                },
+                &message(Source::Server(None), "{nick} joined")
+            ),
+            "{-!-} {nick} joined\n"
+        );
+        assert_eq!(
+            line(&config, &message(Source::Action, " ∙ bob waves")),
+            "* bob waves\n"
+        );
+        assert_eq!(
+            line(
+                &TextLog {
+                    template: "{nick}: {text}".into(),
+                    ..config
+                },
+                &message(Source::Action, " ∙ bob waves")
+            ),
+            "bob: bob waves\n"
+        );
+    }
+
+    #[test]
+    fn log_paths() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+        let server = server::Server::from("libera");
+
+        assert_eq!(
+            path(Path::new("logs"), &Buffer::Server(server.clone()), day),
+            Path::new("logs/libera/2024-03-02.log")
+        );
+        assert_eq!(
+            path(
+                Path::new("logs"),
+                &Buffer::Channel(server.clone(), "#Halloy/Dev".into()),
+                day
+            ),
+            Path::new("logs/libera/#halloy_dev/2024-03-02.log")
+        );
+        assert_eq!(
+            path(
+                Path::new("logs"),
+                &Buffer::Query(server, Nick::from("..")),
+                day
+            ),
+            Path::new("logs/libera/__/2024-03-02.log")
+        );
+    }
+}
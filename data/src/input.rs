@@ -3,38 +3,140 @@ use std::collections::HashMap;
 use chrono::Utc;
 use irc::proto;
 use irc::proto::format;
+use unicode_segmentation::UnicodeSegmentation;
 
+use crate::formatting::Modifier;
+use crate::isupport::ISupport;
 use crate::time::Posix;
-use crate::{command, message, Buffer, Command, Message, Server, User};
+use crate::{command, formatting, message, Buffer, Command, Message, Server, User};
 
 const INPUT_HISTORY_LENGTH: usize = 100;
 
-pub fn parse(buffer: Buffer, input: &str) -> Result<Input, Error> {
+const DEFAULT_NICK_LEN: usize = 30;
+const DEFAULT_USERNAME_LEN: usize = 10;
+const DEFAULT_HOSTNAME_LEN: usize = 63;
+
+pub fn parse(
+    buffer: Buffer,
+    input: &str,
+    our_user: Option<&User>,
+    isupport: &ISupport,
+    markup: bool,
+) -> Result<Input, Error> {
     let content = match command::parse(input, Some(&buffer)) {
         Ok(command) => Content::Command(command),
         Err(command::Error::MissingSlash) => Content::Text(input.to_string()),
         Err(error) => return Err(Error::Command(error)),
     };
 
-    if content
-        .proto(&buffer)
-        .map(exceeds_byte_limit)
-        .unwrap_or_default()
-    {
-        return Err(Error::ExceedsByteLimit);
+    let command = content.command(&buffer).map(|command| {
+        if markup {
+            encode_markup(command)
+        } else {
+            command
+        }
+    });
+
+    if let Some(Command::Nick(nick)) = &command {
+        if let Some(nicklen) = isupport.nicklen() {
+            if nick.chars().count() > nicklen as usize {
+                return Err(Error::ExceedsNickLength(nicklen));
+            }
+        }
+    }
+
+    if let Some((name, targets)) = command.as_ref().and_then(targets) {
+        if let Some(max) = isupport.targmax(name) {
+            if targets.split(',').count() > max as usize {
+                return Err(Error::ExceedsTargetLimit(name, max));
+            }
+        }
     }
 
+    let byte_limit = isupport
+        .linelen()
+        .map(usize::from)
+        .unwrap_or(format::BYTE_LIMIT);
+
+    let commands = match command {
+        Some(command) => split(command, byte_limit, prefix_len(our_user, isupport))?,
+        None => vec![],
+    };
+
     Ok(Input {
         buffer,
-        content,
+        commands,
         raw: Some(input.to_string()),
     })
 }
 
+/// The text sent for `input` w/ its markup converted into formatting,
+/// if there's any markup to preview
+pub fn preview(buffer: &Buffer, input: &str) -> Option<String> {
+    let text = match command::parse(input, Some(buffer)) {
+        Ok(Command::Msg(_, text) | Command::Me(_, text) | Command::Notice(_, text)) => text,
+        Err(command::Error::MissingSlash) => input.to_string(),
+        _ => return None,
+    };
+
+    let encoded = formatting::encode(&text);
+
+    (encoded != text).then_some(encoded)
+}
+
+/// Where formatting is inserted into a draft, in graphemes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cursor {
+    Index(usize),
+    Selection { start: usize, end: usize },
+}
+
+/// Inserts the markup of `modifier` into `draft` at the cursor, or around
+/// the selected text if `modifier` toggles formatting. Returns the new
+/// draft & the cursor position after the inserted markup.
+pub fn insert_markup(draft: &str, modifier: Modifier, cursor: Cursor) -> (String, usize) {
+    let graphemes = draft.graphemes(true).collect::<Vec<_>>();
+    let clamp = |index: usize| index.min(graphemes.len());
+    let markup = modifier.markup();
+    let markup_len = markup.graphemes(true).count();
+
+    let (start, end) = match cursor {
+        Cursor::Index(index) => (clamp(index), clamp(index)),
+        Cursor::Selection { start, end } => (clamp(start.min(end)), clamp(start.max(end))),
+    };
+
+    let before = graphemes[..start].concat();
+    let selected = graphemes[start..end].concat();
+    let after = graphemes[end..].concat();
+
+    // Colors & reset aren't toggled, so the selection is only prefixed
+    if start != end && modifier.is_toggle() {
+        (
+            format!("{before}{markup}{selected}{markup}{after}"),
+            end + 2 * markup_len,
+        )
+    } else {
+        (
+            format!("{before}{markup}{selected}{after}"),
+            start + markup_len,
+        )
+    }
+}
+
+/// Converts the markup of message text into formatting codes
+fn encode_markup(command: Command) -> Command {
+    match command {
+        Command::Msg(target, text) => Command::Msg(target, formatting::encode(&text)),
+        Command::Me(target, text) => Command::Me(target, formatting::encode(&text)),
+        Command::Notice(target, text) => Command::Notice(target, formatting::encode(&text)),
+        command => command,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Input {
     buffer: Buffer,
-    content: Content,
+    commands: Vec<Command>,
     raw: Option<String>,
 }
 
@@ -42,7 +144,7 @@ impl Input {
     pub fn command(buffer: Buffer, command: Command) -> Self {
         Self {
             buffer,
-            content: Content::Command(command),
+            commands: vec![command],
             raw: None,
         }
     }
@@ -55,11 +157,11 @@ impl Input {
         self.buffer.server()
     }
 
-    pub fn message(&self, user: User) -> Option<Message> {
-        let to_target = |target: String, source| {
-            if proto::is_channel(&target) {
+    pub fn messages(&self, user: User, chantypes: &[char]) -> Vec<Message> {
+        let to_target = |target: &str, source| {
+            if proto::is_channel(target, chantypes) {
                 Some(message::Target::Channel {
-                    channel: target,
+                    channel: target.to_string(),
                     source,
                 })
             } else if let Ok(user) = User::try_from(target) {
@@ -72,29 +174,39 @@ impl Input {
             }
         };
 
-        let command = self.content.command(&self.buffer)?;
-
-        match command {
-            Command::Msg(target, text) => Some(Message {
-                received_at: Posix::now(),
-                server_time: Utc::now(),
-                direction: message::Direction::Sent,
-                target: to_target(target, message::Source::User(user))?,
-                text,
-            }),
-            Command::Me(target, action) => Some(Message {
-                received_at: Posix::now(),
-                server_time: Utc::now(),
-                direction: message::Direction::Sent,
-                target: to_target(target, message::Source::Action)?,
-                text: message::action_text(user.nickname(), &action),
-            }),
-            _ => None,
-        }
+        self.commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Msg(target, text) | Command::Notice(target, text) => Some(Message {
+                    received_at: Posix::now(),
+                    server_time: Utc::now(),
+                    id: None,
+                    direction: message::Direction::Sent,
+                    target: to_target(target, message::Source::User(user.clone()))?,
+                    text: text.clone(),
+                    content: Default::default(),
+                }),
+                Command::Me(target, action) => Some(Message {
+                    received_at: Posix::now(),
+                    server_time: Utc::now(),
+                    id: None,
+                    direction: message::Direction::Sent,
+                    target: to_target(target, message::Source::Action)?,
+                    text: message::action_text(user.nickname(), action),
+                    content: Default::default(),
+                }),
+                _ => None,
+            })
+            .collect()
     }
 
-    pub fn encoded(&self) -> Option<message::Encoded> {
-        self.content.proto(&self.buffer).map(message::Encoded::from)
+    pub fn encoded(&self) -> Vec<message::Encoded> {
+        self.commands
+            .iter()
+            .cloned()
+            .filter_map(proto)
+            .map(message::Encoded::from)
+            .collect()
     }
 
     pub fn raw(&self) -> Option<&str> {
@@ -118,12 +230,6 @@ impl Content {
             Self::Command(command) => Some(command.clone()),
         }
     }
-
-    fn proto(&self, buffer: &Buffer) -> Option<proto::Message> {
-        self.command(buffer)
-            .and_then(|command| proto::Command::try_from(command).ok())
-            .map(proto::Message::from)
-    }
 }
 
 #[derive(Debug, Clone)]
@@ -171,15 +277,276 @@ pub struct Cache<'a> {
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error(
-        "message exceeds maximum encoded length of {} bytes",
-        format::BYTE_LIMIT
-    )]
-    ExceedsByteLimit,
+    #[error("message exceeds maximum encoded length of {0} bytes")]
+    ExceedsByteLimit(usize),
+    #[error("nickname exceeds maximum length of {0} characters")]
+    ExceedsNickLength(u16),
+    #[error("{0} accepts at most {1} targets")]
+    ExceedsTargetLimit(&'static str, u16),
     #[error(transparent)]
     Command(#[from] command::Error),
 }
 
-fn exceeds_byte_limit(message: proto::Message) -> bool {
-    format::message(message).len() > format::BYTE_LIMIT
+fn proto(command: Command) -> Option<proto::Message> {
+    proto::Command::try_from(command)
+        .ok()
+        .map(proto::Message::from)
+}
+
+/// The IRC command & comma separated targets of commands a server may limit
+/// the # of targets for w/ `TARGMAX`
+fn targets(command: &Command) -> Option<(&'static str, &str)> {
+    match command {
+        Command::Msg(targets, _) | Command::Me(targets, _) => Some(("PRIVMSG", targets)),
+        Command::Notice(targets, _) => Some(("NOTICE", targets)),
+        Command::Join(chanlist, _) => Some(("JOIN", chanlist)),
+        Command::Part(chanlist, _) => Some(("PART", chanlist)),
+        Command::Kick(_, users, _) => Some(("KICK", users)),
+        Command::Whois(_, nicks) => Some(("WHOIS", nicks)),
+        _ => None,
+    }
+}
+
+/// Length of the `:nick!user@host ` source the server prepends when relaying
+/// our messages, assuming the longest allowed values for any part we don't know
+fn prefix_len(our_user: Option<&User>, isupport: &ISupport) -> usize {
+    let nick = our_user
+        .map(|user| user.nickname().as_ref().len())
+        .or(isupport.nicklen().map(usize::from))
+        .unwrap_or(DEFAULT_NICK_LEN);
+    let username = our_user
+        .and_then(User::username)
+        .map_or(DEFAULT_USERNAME_LEN, str::len);
+    let hostname = our_user
+        .and_then(User::hostname)
+        .map_or(DEFAULT_HOSTNAME_LEN, str::len);
+
+    ":!@ ".len() + nick + username + hostname
+}
+
+/// Splits the text of PRIVMSG, NOTICE & ACTION commands into as many commands
+/// as needed for each line to fit `byte_limit` once relayed w/ our prefix.
+/// Message tags are excluded as they have a separate limit.
+fn split(command: Command, byte_limit: usize, prefix_len: usize) -> Result<Vec<Command>, Error> {
+    let line_len = |command: &Command| {
+        prefix_len + proto(command.clone()).map_or(0, |message| format::message(message).len())
+    };
+
+    if line_len(&command) <= byte_limit {
+        return Ok(vec![command]);
+    }
+
+    let (target, text, into_command): (_, _, fn(String, String) -> Command) = match command {
+        Command::Msg(target, text) => (target, text, Command::Msg),
+        Command::Notice(target, text) => (target, text, Command::Notice),
+        Command::Me(target, text) => (target, text, Command::Me),
+        _ => return Err(Error::ExceedsByteLimit(byte_limit)),
+    };
+
+    // Empty text is always formatted as a trailing parameter, giving the
+    // overhead of any split line
+    let overhead = line_len(&into_command(target.clone(), String::new()));
+    let max_text_len = byte_limit.saturating_sub(overhead);
+
+    Ok(split_text(&text, max_text_len)
+        .ok_or(Error::ExceedsByteLimit(byte_limit))?
+        .into_iter()
+        .map(|text| into_command(target.clone(), text))
+        .collect())
+}
+
+/// Splits `text` into lines of at most `max_len` bytes, preferring to break
+/// at whitespace and otherwise between grapheme clusters
+fn split_text(text: &str, max_len: usize) -> Option<Vec<String>> {
+    let mut lines = vec![];
+    let mut remaining = text;
+
+    while remaining.len() > max_len {
+        let mut end = 0;
+        let mut whitespace = None;
+
+        for (index, grapheme) in remaining.grapheme_indices(true) {
+            if index + grapheme.len() > max_len {
+                break;
+            }
+
+            end = index + grapheme.len();
+
+            if index > 0 && grapheme.chars().all(char::is_whitespace) {
+                whitespace = Some((index, end));
+            }
+        }
+
+        // A single grapheme cluster which doesn't fit, so fallback to
+        // breaking between chars
+        if end == 0 {
+            end = (1..=max_len)
+                .rev()
+                .find(|index| remaining.is_char_boundary(*index))?;
+        }
+
+        let (line, rest) = match whitespace {
+            Some((start, end)) => (&remaining[..start], &remaining[end..]),
+            None => remaining.split_at(end),
+        };
+
+        lines.push(line.to_string());
+        remaining = rest;
+    }
+
+    if !remaining.is_empty() || lines.is_empty() {
+        lines.push(remaining.to_string());
+    }
+
+    Some(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use crate::isupport;
+
+    use super::*;
+
+    #[test]
+    fn split_text_boundaries() {
+        let tests = [
+            ("hello world", 20, vec!["hello world"]),
+            ("hello world", 8, vec!["hello", "world"]),
+            ("hello  world", 6, vec!["hello", " world"]),
+            ("helloworld", 4, vec!["hell", "owor", "ld"]),
+            // Never split the 2 byte `é`, nor the `e` from its combining accent
+            ("ééé", 3, vec!["é", "é", "é"]),
+            ("e\u{301}e\u{301}", 4, vec!["e\u{301}", "e\u{301}"]),
+        ];
+
+        for (text, max_len, expected) in tests {
+            assert_eq!(split_text(text, max_len).unwrap(), expected, "{text}");
+        }
+
+        assert_eq!(split_text("hello", 0), None);
+    }
+
+    #[test]
+    fn targmax() {
+        let buffer = Buffer::Server(Server::from("server"));
+        let mut isupport = ISupport::default();
+        let args = [
+            "nick",
+            "TARGMAX=PRIVMSG:2,JOIN:",
+            "are supported by this server",
+        ]
+        .map(String::from);
+        for operation in isupport::parse(&args) {
+            isupport.apply(operation);
+        }
+
+        assert!(parse(buffer.clone(), "/msg a,b hello", None, &isupport, false).is_ok());
+        assert!(matches!(
+            parse(buffer.clone(), "/msg a,b,c hello", None, &isupport, false),
+            Err(Error::ExceedsTargetLimit("PRIVMSG", 2))
+        ));
+        assert!(parse(buffer, "/join #a,#b,#c", None, &isupport, false).is_ok());
+    }
+
+    #[test]
+    fn markup_is_opt_in() {
+        let buffer = Buffer::Channel(Server::from("server"), "#channel".into());
+        let text = |markup| match parse(
+            buffer.clone(),
+            "50%Battery *foo*",
+            None,
+            &ISupport::default(),
+            markup,
+        )
+        .unwrap()
+        .commands
+        .as_slice()
+        {
+            [Command::Msg(_, text)] => text.clone(),
+            commands => panic!("{commands:?}"),
+        };
+
+        assert_eq!(text(false), "50%Battery *foo*");
+        assert_eq!(text(true), "50\u{2}attery \u{2}foo\u{2}");
+    }
+
+    #[test]
+    fn insert_markup_at_cursor() {
+        let tests = [
+            ("hello", Modifier::Bold, Cursor::Index(5), "hello%B", 7),
+            ("hello", Modifier::Bold, Cursor::Index(2), "he%Bllo", 4),
+            (
+                "say hello",
+                Modifier::Italic,
+                Cursor::Selection { start: 4, end: 9 },
+                "say %Ihello%I",
+                13,
+            ),
+            (
+                "é hello",
+                Modifier::Bold,
+                Cursor::Selection { start: 7, end: 2 },
+                "é %Bhello%B",
+                11,
+            ),
+            (
+                "say hello",
+                Modifier::Color,
+                Cursor::Selection { start: 4, end: 9 },
+                "say %Chello",
+                6,
+            ),
+            ("", Modifier::Reset, Cursor::Index(3), "%O", 2),
+        ];
+
+        for (draft, modifier, cursor, expected, position) in tests {
+            assert_eq!(
+                insert_markup(draft, modifier, cursor),
+                (expected.to_string(), position),
+                "{draft} {cursor:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn split_fits_byte_limit() {
+        let buffer = Buffer::Channel(Server::from("server"), "#channel".into());
+        let our_user = User::try_from("nick!user@host").unwrap();
+        let text = "lorem ipsum dolor sit amet "
+            .repeat(40)
+            .trim_end()
+            .to_string();
+
+        for input in [text.clone(), format!("/me {text}")] {
+            let input = parse(
+                buffer.clone(),
+                &input,
+                Some(&our_user),
+                &ISupport::default(),
+                false,
+            )
+            .unwrap();
+            let encoded = input.encoded();
+
+            assert!(encoded.len() > 1);
+            for message in encoded {
+                let line = format!(":nick!user@host {}", format::message(message.into()));
+                assert!(line.len() <= format::BYTE_LIMIT, "{line}");
+            }
+
+            let messages = input.messages(our_user.clone(), &['#']);
+            let joined = messages
+                .iter()
+                .map(|message| {
+                    message
+                        .text
+                        .strip_prefix(" ∙ nick ")
+                        .unwrap_or(&message.text)
+                })
+                .join(" ");
+            assert_eq!(joined, text);
+        }
+    }
 }
@@ -0,0 +1,708 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::mode;
+
+/// Channel prefixes assumed when the server doesn't advertise `CHANTYPES`
+pub const DEFAULT_CHANTYPES: &[char] = &['#', '&', '+', '!'];
+
+/// Default `PREFIX` per RFC 1459, extended with the common founder & admin modes
+pub const DEFAULT_PREFIX: &[PrefixMap] = &[
+    PrefixMap {
+        mode: 'q',
+        prefix: '~',
+    },
+    PrefixMap {
+        mode: 'a',
+        prefix: '&',
+    },
+    PrefixMap {
+        mode: 'o',
+        prefix: '@',
+    },
+    PrefixMap {
+        mode: 'h',
+        prefix: '%',
+    },
+    PrefixMap {
+        mode: 'v',
+        prefix: '+',
+    },
+];
+
+/// Parse the parameters of a `RPL_ISUPPORT` reply
+///
+/// The first parameter (our nickname) and the trailing human readable
+/// text are skipped. Tokens which fail to parse are logged and ignored.
+pub fn parse(args: &[String]) -> Vec<Operation> {
+    let tokens = args
+        .get(1..args.len().saturating_sub(1))
+        .unwrap_or_default();
+
+    tokens
+        .iter()
+        .filter_map(|token| match token.parse::<Operation>() {
+            Ok(operation) => Some(operation),
+            Err(error) => {
+                log::debug!("isupport token {token:?} ignored: {error}");
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Add(Parameter),
+    Remove(Kind),
+}
+
+impl Operation {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Operation::Add(parameter) => parameter.kind(),
+            Operation::Remove(kind) => *kind,
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = Error;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        if let Some(negated) = token.strip_prefix('-') {
+            return negated.parse::<Kind>().map(Operation::Remove);
+        }
+
+        let (key, value) = token.split_once('=').unwrap_or((token, ""));
+        let kind = key.parse::<Kind>()?;
+
+        Parameter::new(kind, &unescape(value)).map(Operation::Add)
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    AWAYLEN,
+    CASEMAPPING,
+    CHANLIMIT,
+    CHANMODES,
+    CHANNELLEN,
+    CHANTYPES,
+    CHATHISTORY,
+    EXCEPTS,
+    INVEX,
+    KICKLEN,
+    LINELEN,
+    MAXTARGETS,
+    MODES,
+    MONITOR,
+    NETWORK,
+    NICKLEN,
+    PREFIX,
+    STATUSMSG,
+    TARGMAX,
+    TOPICLEN,
+    UTF8ONLY,
+    WHOX,
+}
+
+impl FromStr for Kind {
+    type Err = Error;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        use Kind::*;
+
+        Ok(match key {
+            "AWAYLEN" => AWAYLEN,
+            "CASEMAPPING" => CASEMAPPING,
+            "CHANLIMIT" => CHANLIMIT,
+            "CHANMODES" => CHANMODES,
+            "CHANNELLEN" => CHANNELLEN,
+            "CHANTYPES" => CHANTYPES,
+            "CHATHISTORY" => CHATHISTORY,
+            "EXCEPTS" => EXCEPTS,
+            "INVEX" => INVEX,
+            "KICKLEN" => KICKLEN,
+            "LINELEN" => LINELEN,
+            "MAXTARGETS" => MAXTARGETS,
+            "MODES" => MODES,
+            "MONITOR" => MONITOR,
+            "NETWORK" => NETWORK,
+            "NICKLEN" => NICKLEN,
+            "PREFIX" => PREFIX,
+            "STATUSMSG" => STATUSMSG,
+            "TARGMAX" => TARGMAX,
+            "TOPICLEN" => TOPICLEN,
+            "UTF8ONLY" => UTF8ONLY,
+            "WHOX" => WHOX,
+            _ => return Err(Error::Unsupported(key.to_string())),
+        })
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Parameter {
+    AWAYLEN(u16),
+    CASEMAPPING(CaseMap),
+    CHANLIMIT(Vec<ChannelLimit>),
+    CHANMODES(ChannelModes),
+    CHANNELLEN(u16),
+    CHANTYPES(Vec<char>),
+    CHATHISTORY(u16),
+    EXCEPTS(char),
+    INVEX(char),
+    KICKLEN(u16),
+    LINELEN(u16),
+    MAXTARGETS(Option<u16>),
+    MODES(Option<u16>),
+    MONITOR(Option<u16>),
+    NETWORK(String),
+    NICKLEN(u16),
+    PREFIX(Vec<PrefixMap>),
+    STATUSMSG(Vec<char>),
+    TARGMAX(Vec<CommandTargetLimit>),
+    TOPICLEN(u16),
+    UTF8ONLY,
+    WHOX,
+}
+
+impl Parameter {
+    fn new(kind: Kind, value: &str) -> Result<Self, Error> {
+        let number = || {
+            value
+                .parse::<u16>()
+                .map_err(|_| Error::InvalidValue(kind, value.to_string()))
+        };
+        let optional_number = || {
+            if value.is_empty() {
+                Ok(None)
+            } else {
+                number().map(Some)
+            }
+        };
+        let character = |default| {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (None, _) => Ok(default),
+                (Some(c), None) => Ok(c),
+                _ => Err(Error::InvalidValue(kind, value.to_string())),
+            }
+        };
+
+        Ok(match kind {
+            Kind::AWAYLEN => Parameter::AWAYLEN(number()?),
+            Kind::CASEMAPPING => Parameter::CASEMAPPING(value.parse()?),
+            Kind::CHANLIMIT => Parameter::CHANLIMIT(
+                value
+                    .split(',')
+                    .filter(|limit| !limit.is_empty())
+                    .map(ChannelLimit::from_str)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Kind::CHANMODES => Parameter::CHANMODES(value.parse()?),
+            Kind::CHANNELLEN => Parameter::CHANNELLEN(number()?),
+            // An empty value means the server supports no channels at all
+            Kind::CHANTYPES => Parameter::CHANTYPES(value.chars().collect()),
+            Kind::CHATHISTORY => Parameter::CHATHISTORY(number()?),
+            Kind::EXCEPTS => Parameter::EXCEPTS(character('e')?),
+            Kind::INVEX => Parameter::INVEX(character('I')?),
+            Kind::KICKLEN => Parameter::KICKLEN(number()?),
+            Kind::LINELEN => Parameter::LINELEN(number()?),
+            Kind::MAXTARGETS => Parameter::MAXTARGETS(optional_number()?),
+            Kind::MODES => Parameter::MODES(optional_number()?),
+            Kind::MONITOR => Parameter::MONITOR(optional_number()?),
+            Kind::NETWORK => Parameter::NETWORK(value.to_string()),
+            Kind::NICKLEN => Parameter::NICKLEN(number()?),
+            Kind::PREFIX => Parameter::PREFIX(parse_prefix(value)?),
+            Kind::STATUSMSG => Parameter::STATUSMSG(value.chars().collect()),
+            Kind::TARGMAX => Parameter::TARGMAX(
+                value
+                    .split(',')
+                    .filter(|limit| !limit.is_empty())
+                    .map(CommandTargetLimit::from_str)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Kind::TOPICLEN => Parameter::TOPICLEN(number()?),
+            Kind::UTF8ONLY => Parameter::UTF8ONLY,
+            Kind::WHOX => Parameter::WHOX,
+        })
+    }
+
+    pub fn kind(&self) -> Kind {
+        match self {
+            Parameter::AWAYLEN(_) => Kind::AWAYLEN,
+            Parameter::CASEMAPPING(_) => Kind::CASEMAPPING,
+            Parameter::CHANLIMIT(_) => Kind::CHANLIMIT,
+            Parameter::CHANMODES(_) => Kind::CHANMODES,
+            Parameter::CHANNELLEN(_) => Kind::CHANNELLEN,
+            Parameter::CHANTYPES(_) => Kind::CHANTYPES,
+            Parameter::CHATHISTORY(_) => Kind::CHATHISTORY,
+            Parameter::EXCEPTS(_) => Kind::EXCEPTS,
+            Parameter::INVEX(_) => Kind::INVEX,
+            Parameter::KICKLEN(_) => Kind::KICKLEN,
+            Parameter::LINELEN(_) => Kind::LINELEN,
+            Parameter::MAXTARGETS(_) => Kind::MAXTARGETS,
+            Parameter::MODES(_) => Kind::MODES,
+            Parameter::MONITOR(_) => Kind::MONITOR,
+            Parameter::NETWORK(_) => Kind::NETWORK,
+            Parameter::NICKLEN(_) => Kind::NICKLEN,
+            Parameter::PREFIX(_) => Kind::PREFIX,
+            Parameter::STATUSMSG(_) => Kind::STATUSMSG,
+            Parameter::TARGMAX(_) => Kind::TARGMAX,
+            Parameter::TOPICLEN(_) => Kind::TOPICLEN,
+            Parameter::UTF8ONLY => Kind::UTF8ONLY,
+            Parameter::WHOX => Kind::WHOX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMap {
+    Ascii,
+    #[default]
+    Rfc1459,
+    StrictRfc1459,
+    Rfc7613,
+}
+
+impl FromStr for CaseMap {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ascii" => Ok(CaseMap::Ascii),
+            "rfc1459" => Ok(CaseMap::Rfc1459),
+            "strict-rfc1459" => Ok(CaseMap::StrictRfc1459),
+            "rfc7613" => Ok(CaseMap::Rfc7613),
+            _ => Err(Error::InvalidValue(Kind::CASEMAPPING, value.to_string())),
+        }
+    }
+}
+
+impl CaseMap {
+    /// Fold `value` to the form servers use when comparing nicknames &
+    /// channel names, so two names are the same if their normalized forms are
+    pub fn normalize(&self, value: &str) -> String {
+        match self {
+            CaseMap::Ascii => value.to_ascii_lowercase(),
+            CaseMap::Rfc1459 => value
+                .chars()
+                .map(|c| match c {
+                    '[' => '{',
+                    ']' => '}',
+                    '\\' => '|',
+                    '~' => '^',
+                    c => c.to_ascii_lowercase(),
+                })
+                .collect(),
+            CaseMap::StrictRfc1459 => value
+                .chars()
+                .map(|c| match c {
+                    '[' => '{',
+                    ']' => '}',
+                    '\\' => '|',
+                    c => c.to_ascii_lowercase(),
+                })
+                .collect(),
+            CaseMap::Rfc7613 => value.to_lowercase(),
+        }
+    }
+}
+
+/// Channel modes grouped by the four `CHANMODES` types
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelModes {
+    /// Modes that add or remove an address to or from a list
+    pub a: Vec<char>,
+    /// Modes that always take a parameter
+    pub b: Vec<char>,
+    /// Modes that only take a parameter when set
+    pub c: Vec<char>,
+    /// Modes that never take a parameter
+    pub d: Vec<char>,
+}
+
+impl ChannelModes {
+    /// Whether `mode` consumes an argument, `None` if the mode isn't listed
+    pub fn takes_arg(&self, mode: char, operation: &mode::Operation) -> Option<bool> {
+        if self.a.contains(&mode) || self.b.contains(&mode) {
+            Some(true)
+        } else if self.c.contains(&mode) {
+            Some(matches!(operation, mode::Operation::Add))
+        } else if self.d.contains(&mode) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for ChannelModes {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut groups = value.split(',').map(|group| group.chars().collect());
+
+        let (Some(a), Some(b), Some(c), Some(d)) =
+            (groups.next(), groups.next(), groups.next(), groups.next())
+        else {
+            return Err(Error::InvalidValue(Kind::CHANMODES, value.to_string()));
+        };
+
+        // Additional groups may be added by future specs and are ignored
+        Ok(ChannelModes { a, b, c, d })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixMap {
+    pub mode: char,
+    pub prefix: char,
+}
+
+fn parse_prefix(value: &str) -> Result<Vec<PrefixMap>, Error> {
+    if value.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let invalid = || Error::InvalidValue(Kind::PREFIX, value.to_string());
+
+    let (modes, prefixes) = value
+        .strip_prefix('(')
+        .and_then(|value| value.split_once(')'))
+        .ok_or_else(invalid)?;
+
+    if modes.chars().count() != prefixes.chars().count() {
+        return Err(invalid());
+    }
+
+    Ok(modes
+        .chars()
+        .zip(prefixes.chars())
+        .map(|(mode, prefix)| PrefixMap { mode, prefix })
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelLimit {
+    pub prefix: Vec<char>,
+    pub limit: Option<u16>,
+}
+
+impl FromStr for ChannelLimit {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (prefix, limit) = value
+            .split_once(':')
+            .ok_or_else(|| Error::InvalidValue(Kind::CHANLIMIT, value.to_string()))?;
+
+        Ok(ChannelLimit {
+            prefix: prefix.chars().collect(),
+            limit: if limit.is_empty() {
+                None
+            } else {
+                Some(
+                    limit
+                        .parse()
+                        .map_err(|_| Error::InvalidValue(Kind::CHANLIMIT, value.to_string()))?,
+                )
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTargetLimit {
+    pub command: String,
+    pub limit: Option<u16>,
+}
+
+impl FromStr for CommandTargetLimit {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (command, limit) = value.split_once(':').unwrap_or((value, ""));
+
+        Ok(CommandTargetLimit {
+            command: command.to_uppercase(),
+            limit: if limit.is_empty() {
+                None
+            } else {
+                Some(
+                    limit
+                        .parse()
+                        .map_err(|_| Error::InvalidValue(Kind::TARGMAX, value.to_string()))?,
+                )
+            },
+        })
+    }
+}
+
+/// Decode `\xHH` escapes allowed in `RPL_ISUPPORT` values
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(i) = rest.find("\\x") {
+        unescaped.push_str(&rest[..i]);
+
+        let escaped = rest
+            .get(i + 2..i + 4)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                unescaped.push(char::from(byte));
+                rest = &rest[i + 4..];
+            }
+            None => {
+                unescaped.push_str("\\x");
+                rest = &rest[i + 2..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// The parameters advertised by a server through `RPL_ISUPPORT`
+#[derive(Debug, Clone, Default)]
+pub struct ISupport(HashMap<Kind, Parameter>);
+
+impl ISupport {
+    pub fn apply(&mut self, operation: Operation) {
+        match operation {
+            Operation::Add(parameter) => {
+                self.0.insert(parameter.kind(), parameter);
+            }
+            Operation::Remove(kind) => {
+                self.0.remove(&kind);
+            }
+        }
+    }
+
+    pub fn get(&self, kind: Kind) -> Option<&Parameter> {
+        self.0.get(&kind)
+    }
+
+    pub fn chantypes(&self) -> &[char] {
+        match self.get(Kind::CHANTYPES) {
+            Some(Parameter::CHANTYPES(chantypes)) => chantypes,
+            _ => DEFAULT_CHANTYPES,
+        }
+    }
+
+    pub fn prefix(&self) -> &[PrefixMap] {
+        match self.get(Kind::PREFIX) {
+            Some(Parameter::PREFIX(prefix)) => prefix,
+            _ => DEFAULT_PREFIX,
+        }
+    }
+
+    pub fn chanmodes(&self) -> Option<&ChannelModes> {
+        match self.get(Kind::CHANMODES) {
+            Some(Parameter::CHANMODES(chanmodes)) => Some(chanmodes),
+            _ => None,
+        }
+    }
+
+    pub fn casemapping(&self) -> CaseMap {
+        match self.get(Kind::CASEMAPPING) {
+            Some(Parameter::CASEMAPPING(casemapping)) => *casemapping,
+            _ => CaseMap::default(),
+        }
+    }
+
+    pub fn nicklen(&self) -> Option<u16> {
+        match self.get(Kind::NICKLEN) {
+            Some(Parameter::NICKLEN(len)) => Some(*len),
+            _ => None,
+        }
+    }
+
+    /// Maximum length of a line in bytes, including the trailing CRLF
+    pub fn linelen(&self) -> Option<u16> {
+        match self.get(Kind::LINELEN) {
+            Some(Parameter::LINELEN(len)) => Some(*len),
+            _ => None,
+        }
+    }
+
+    /// Maximum number of targets for `command`, `None` if unlimited or unknown
+    pub fn targmax(&self, command: &str) -> Option<u16> {
+        match self.get(Kind::TARGMAX) {
+            Some(Parameter::TARGMAX(limits)) => limits
+                .iter()
+                .find(|limit| limit.command.eq_ignore_ascii_case(command))
+                .and_then(|limit| limit.limit),
+            _ => None,
+        }
+    }
+
+    /// Size of the `MONITOR` list if supported, `Some(None)` if unlimited
+    pub fn monitor(&self) -> Option<Option<u16>> {
+        match self.get(Kind::MONITOR) {
+            Some(Parameter::MONITOR(limit)) => Some(*limit),
+            _ => None,
+        }
+    }
+
+    /// Maximum # messages returned per `CHATHISTORY` request, `None` if
+    /// unlimited or unknown
+    pub fn chathistory_limit(&self) -> Option<u16> {
+        match self.get(Kind::CHATHISTORY) {
+            Some(Parameter::CHATHISTORY(limit)) => Some(*limit).filter(|limit| *limit > 0),
+            _ => None,
+        }
+    }
+
+    pub fn is_channel(&self, target: &str) -> bool {
+        irc::proto::is_channel(target, self.chantypes())
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("unsupported parameter {0}")]
+    Unsupported(String),
+    #[error("invalid value for {0:?}: {1:?}")]
+    InvalidValue(Kind, String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn operation() {
+        let tests = [
+            (
+                "CHANTYPES=#",
+                Operation::Add(Parameter::CHANTYPES(vec!['#'])),
+            ),
+            ("CHANTYPES=", Operation::Add(Parameter::CHANTYPES(vec![]))),
+            (
+                "PREFIX=(Yqaohv)!~&@%+",
+                Operation::Add(Parameter::PREFIX(vec![
+                    PrefixMap {
+                        mode: 'Y',
+                        prefix: '!',
+                    },
+                    PrefixMap {
+                        mode: 'q',
+                        prefix: '~',
+                    },
+                    PrefixMap {
+                        mode: 'a',
+                        prefix: '&',
+                    },
+                    PrefixMap {
+                        mode: 'o',
+                        prefix: '@',
+                    },
+                    PrefixMap {
+                        mode: 'h',
+                        prefix: '%',
+                    },
+                    PrefixMap {
+                        mode: 'v',
+                        prefix: '+',
+                    },
+                ])),
+            ),
+            (
+                "CHANMODES=eIbq,k,flj,CFLMPQScgimnprstuz",
+                Operation::Add(Parameter::CHANMODES(ChannelModes {
+                    a: vec!['e', 'I', 'b', 'q'],
+                    b: vec!['k'],
+                    c: vec!['f', 'l', 'j'],
+                    d: "CFLMPQScgimnprstuz".chars().collect(),
+                })),
+            ),
+            (
+                "CASEMAPPING=strict-rfc1459",
+                Operation::Add(Parameter::CASEMAPPING(CaseMap::StrictRfc1459)),
+            ),
+            (
+                "TARGMAX=NAMES:1,PRIVMSG:4,JOIN:",
+                Operation::Add(Parameter::TARGMAX(vec![
+                    CommandTargetLimit {
+                        command: "NAMES".into(),
+                        limit: Some(1),
+                    },
+                    CommandTargetLimit {
+                        command: "PRIVMSG".into(),
+                        limit: Some(4),
+                    },
+                    CommandTargetLimit {
+                        command: "JOIN".into(),
+                        limit: None,
+                    },
+                ])),
+            ),
+            ("MONITOR", Operation::Add(Parameter::MONITOR(None))),
+            (
+                "CHATHISTORY=100",
+                Operation::Add(Parameter::CHATHISTORY(100)),
+            ),
+            (
+                "NETWORK=Example\\x20Net",
+                Operation::Add(Parameter::NETWORK("Example Net".into())),
+            ),
+            ("EXCEPTS", Operation::Add(Parameter::EXCEPTS('e'))),
+            ("-NICKLEN", Operation::Remove(Kind::NICKLEN)),
+        ];
+
+        for (token, expected) in tests {
+            let operation = token.parse::<Operation>().unwrap();
+            assert_eq!(operation, expected);
+        }
+
+        assert!("NICKLEN=abc".parse::<Operation>().is_err());
+        assert!("PREFIX=(ov)@".parse::<Operation>().is_err());
+        assert!("UNKNOWN=1".parse::<Operation>().is_err());
+    }
+
+    #[test]
+    fn casemapping() {
+        let tests = [
+            (CaseMap::Ascii, "Nick[]\\~", "nick[]\\~"),
+            (CaseMap::Rfc1459, "Nick[]\\~", "nick{}|^"),
+            (CaseMap::StrictRfc1459, "Nick[]\\~", "nick{}|~"),
+            (CaseMap::Rfc7613, "NÏCK", "nïck"),
+        ];
+
+        for (casemapping, value, expected) in tests {
+            assert_eq!(casemapping.normalize(value), expected);
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let args = [
+            "nick",
+            "CHANTYPES=#!",
+            "NICKLEN=16",
+            "are supported by this server",
+        ]
+        .map(String::from);
+
+        let mut isupport = ISupport::default();
+        for operation in parse(&args) {
+            isupport.apply(operation);
+        }
+
+        assert_eq!(isupport.chantypes(), &['#', '!']);
+        assert_eq!(isupport.nicklen(), Some(16));
+        assert!(isupport.is_channel("!chan"));
+        assert!(!isupport.is_channel("&chan"));
+
+        isupport.apply("-CHANTYPES".parse().unwrap());
+        assert_eq!(isupport.chantypes(), DEFAULT_CHANTYPES);
+    }
+}
@@ -20,16 +20,20 @@ pub mod client;
 pub mod command;
 mod compression;
 pub mod config;
+pub mod ctcp;
 pub mod dashboard;
 pub mod dcc;
 pub mod environment;
 pub mod file_transfer;
+pub mod formatting;
 pub mod history;
 pub mod input;
+pub mod isupport;
 pub mod log;
 pub mod message;
 pub mod mode;
 pub mod pane;
+pub mod search;
 pub mod server;
 pub mod shortcut;
 pub mod stream;
@@ -3,25 +3,32 @@ use irc::proto;
 use irc::proto::Command;
 use serde::{Deserialize, Serialize};
 
+pub use self::content::{Content, Fragment};
 pub use self::source::Source;
-use crate::time::{self, Posix};
+use crate::ctcp;
+use crate::isupport::CaseMap;
+use crate::time::Posix;
 use crate::user::{Nick, NickRef};
 use crate::{Config, User};
 
 pub type Channel = String;
 
 pub(crate) mod broadcast;
+pub mod content;
+pub mod link;
 pub mod source;
 
 #[derive(Debug, Clone)]
 pub struct Encoded(proto::Message);
 
 impl Encoded {
-    pub fn user(&self) -> Option<User> {
+    pub fn user(&self, casemapping: CaseMap) -> Option<User> {
         let source = self.source.as_ref()?;
 
         match source {
-            proto::Source::User(user) => Some(User::from(user.clone())),
+            proto::Source::User(user) => {
+                Some(User::from(user.clone()).with_casemapping(casemapping))
+            }
             _ => None,
         }
     }
@@ -80,9 +87,16 @@ pub enum Direction {
 pub struct Message {
     pub received_at: Posix,
     pub server_time: DateTime<Utc>,
+    /// The server assigned `msgid`, missing from history written before
+    /// it was stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub direction: Direction,
     pub target: Target,
     pub text: String,
+    /// `text` split into formatted fragments & links when first shown
+    #[serde(skip)]
+    pub content: Content,
 }
 
 impl Message {
@@ -91,22 +105,77 @@ impl Message {
             && matches!(self.target.source(), Source::User(_) | Source::Action)
     }
 
+    /// Whether `other` is this message received again, going by their
+    /// `msgid`. Messages w/o one are never duplicates, as the same text
+    /// can legitimately be sent twice within a second.
+    pub fn is_duplicate(&self, other: &Message) -> bool {
+        matches!((&self.id, &other.id), (Some(id), Some(other_id)) if id == other_id)
+    }
+
+    /// Whether `other` is this message played back again, such as through
+    /// a bouncer or chathistory. Messages are compared by `msgid`, falling
+    /// back to their server time, source & text if either has none.
+    pub fn is_replayed(&self, other: &Message) -> bool {
+        match (&self.id, &other.id) {
+            (Some(id), Some(other_id)) => id == other_id,
+            _ => {
+                self.server_time == other.server_time
+                    && self.target.source() == other.target.source()
+                    && self.text == other.text
+            }
+        }
+    }
+
+    /// Text of an action w/o the bullet it's stored with, as "nick action"
+    pub fn action_text(&self) -> Option<&str> {
+        matches!(self.target.source(), Source::Action)
+            .then(|| self.text.trim_start().trim_start_matches('∙').trim_start())
+    }
+
+    /// Nickname of the user who sent an action
+    pub fn action_sender(&self) -> Option<&str> {
+        self.action_text()?.split_whitespace().next()
+    }
+
+    /// The text split into formatted fragments & links, parsed once
+    pub fn fragments(&self, chantypes: &[char]) -> &[Fragment] {
+        self.content.fragments(&self.text, chantypes)
+    }
+
     pub fn received(
         encoded: Encoded,
         our_nick: Nick,
         config: &Config,
+        chantypes: &[char],
+        casemapping: CaseMap,
         resolve_attributes: impl Fn(&User, &str) -> Option<User>,
     ) -> Option<Message> {
         let server_time = server_time(&encoded);
-        let text = text(&encoded, &our_nick, config, &resolve_attributes)?;
-        let target = target(encoded, &our_nick, &resolve_attributes)?;
+        let id = msgid(&encoded);
+        let text = text(
+            &encoded,
+            &our_nick,
+            config,
+            chantypes,
+            casemapping,
+            &resolve_attributes,
+        )?;
+        let target = target(
+            encoded,
+            &our_nick,
+            chantypes,
+            casemapping,
+            &resolve_attributes,
+        )?;
 
         Some(Message {
             received_at: Posix::now(),
             server_time,
+            id,
             direction: Direction::Received,
             target,
             text,
+            content: Default::default(),
         })
     }
 
@@ -114,12 +183,14 @@ impl Message {
         Message {
             received_at: Posix::now(),
             server_time: Utc::now(),
+            id: None,
             direction: Direction::Received,
             target: Target::Query {
                 nick: from.clone(),
                 source: Source::Action,
             },
             text: format!(" ∙ {from} wants to send you \"{filename}\""),
+            content: Default::default(),
         }
     }
 
@@ -127,12 +198,14 @@ impl Message {
         Message {
             received_at: Posix::now(),
             server_time: Utc::now(),
+            id: None,
             direction: Direction::Sent,
             target: Target::Query {
                 nick: to.clone(),
                 source: Source::Action,
             },
             text: format!(" ∙ offering to send {to} \"{filename}\""),
+            content: Default::default(),
         }
     }
 
@@ -141,21 +214,51 @@ impl Message {
     }
 }
 
+#[cfg(test)]
+impl Message {
+    /// A message received in #halloy at noon on 2024-03-02
+    pub(crate) fn test(source: Source, text: &str) -> Self {
+        use chrono::TimeZone;
+
+        Message {
+            received_at: Posix::now(),
+            server_time: Utc.with_ymd_and_hms(2024, 3, 2, 12, 0, 0).unwrap(),
+            id: None,
+            direction: Direction::Received,
+            target: Target::Channel {
+                channel: "#halloy".into(),
+                source,
+            },
+            text: text.into(),
+            content: Default::default(),
+        }
+    }
+}
+
 fn target(
     message: Encoded,
     our_nick: &Nick,
+    chantypes: &[char],
+    casemapping: CaseMap,
     resolve_attributes: &dyn Fn(&User, &str) -> Option<User>,
 ) -> Option<Target> {
     use proto::command::Numeric::*;
 
-    let user = message.user();
+    let user = message.user(casemapping);
+    let target_user = |target: &str| {
+        User::try_from(target)
+            .ok()
+            .map(|user| user.with_casemapping(casemapping))
+    };
 
     match message.0.command {
         // Channel
-        Command::MODE(target, ..) if proto::is_channel(&target) => Some(Target::Channel {
-            channel: target,
-            source: source::Source::Server(None),
-        }),
+        Command::MODE(target, ..) if proto::is_channel(&target, chantypes) => {
+            Some(Target::Channel {
+                channel: target,
+                source: source::Source::Server(None),
+            })
+        }
         Command::TOPIC(channel, _) | Command::KICK(channel, _, _) => Some(Target::Channel {
             channel,
             source: source::Source::Server(None),
@@ -193,13 +296,25 @@ fn target(
         }
         Command::Numeric(RPL_AWAY, params) => {
             let user = params.get(1)?;
-            let target = User::try_from(user.as_str()).ok()?;
+            let target = target_user(user)?;
 
             Some(Target::Query {
                 nick: target.nickname().to_owned(),
                 source: Source::Action,
             })
         }
+        Command::PRIVMSG(_, text) if is_ctcp(&text) => Some(Target::Server {
+            source: Source::Server(Some(source::Server::new(
+                source::server::Kind::CtcpQuery,
+                Some(user?.nickname().to_owned()),
+            ))),
+        }),
+        Command::NOTICE(_, text) if is_ctcp(&text) => Some(Target::Server {
+            source: Source::Server(Some(source::Server::new(
+                source::server::Kind::CtcpReply,
+                Some(user?.nickname().to_owned()),
+            ))),
+        }),
         Command::PRIVMSG(target, text) => {
             let is_action = is_action(&text);
             let source = |user| {
@@ -210,7 +325,7 @@ fn target(
                 }
             };
 
-            match (proto::is_channel(&target), user) {
+            match (proto::is_channel(&target, chantypes), user) {
                 (true, Some(user)) => {
                     let source = source(resolve_attributes(&user, &target).unwrap_or(user));
                     Some(Target::Channel {
@@ -221,7 +336,7 @@ fn target(
                 (false, Some(user)) => {
                     let (nick, source) = if user.nickname() == *our_nick {
                         // Message from ourself, from another client.
-                        let target = User::try_from(target.as_str()).ok()?;
+                        let target = target_user(&target)?;
                         (target.nickname().to_owned(), source(user))
                     } else {
                         // Message from conversation partner.
@@ -243,7 +358,7 @@ fn target(
                 }
             };
 
-            match (proto::is_channel(&target), user) {
+            match (proto::is_channel(&target, chantypes), user) {
                 (true, Some(user)) => {
                     let source = source(resolve_attributes(&user, &target).unwrap_or(user));
                     Some(Target::Channel {
@@ -252,7 +367,7 @@ fn target(
                     })
                 }
                 (false, Some(user)) => {
-                    let target = User::try_from(target.as_str()).ok()?;
+                    let target = target_user(&target)?;
 
                     (target.nickname() == *our_nick).then(|| Target::Query {
                         nick: user.nickname().to_owned(),
@@ -308,6 +423,14 @@ fn target(
     }
 }
 
+pub fn msgid(message: &Encoded) -> Option<String> {
+    message
+        .tags
+        .iter()
+        .find(|tag| &tag.key == "msgid")
+        .and_then(|tag| tag.value.clone())
+}
+
 pub fn server_time(message: &Encoded) -> DateTime<Utc> {
     message
         .tags
@@ -323,13 +446,15 @@ fn text(
     message: &Encoded,
     our_nick: &Nick,
     config: &Config,
+    chantypes: &[char],
+    casemapping: CaseMap,
     resolve_attributes: &dyn Fn(&User, &str) -> Option<User>,
 ) -> Option<String> {
     use irc::proto::command::Numeric::*;
 
     match &message.command {
         Command::TOPIC(target, topic) => {
-            let raw_user = message.user()?;
+            let raw_user = message.user(casemapping)?;
             let user = resolve_attributes(&raw_user, target).unwrap_or(raw_user);
 
             let topic = topic.as_ref()?;
@@ -337,7 +462,7 @@ fn text(
             Some(format!(" ∙ {user} changed topic to {topic}"))
         }
         Command::PART(target, text) => {
-            let raw_user = message.user()?;
+            let raw_user = message.user(casemapping)?;
             let user = resolve_attributes(&raw_user, target)
                 .unwrap_or(raw_user)
                 .formatted(config.buffer.server_messages.part.username_format);
@@ -350,7 +475,7 @@ fn text(
             Some(format!("⟵ {user} has left the channel{text}"))
         }
         Command::JOIN(target, _) => {
-            let raw_user = message.user()?;
+            let raw_user = message.user(casemapping)?;
             let user = resolve_attributes(&raw_user, target).unwrap_or(raw_user);
 
             (user.nickname() != *our_nick).then(|| {
@@ -361,7 +486,7 @@ fn text(
             })
         }
         Command::KICK(channel, victim, comment) => {
-            let raw_user = message.user()?;
+            let raw_user = message.user(casemapping)?;
             let user = resolve_attributes(&raw_user, channel).unwrap_or(raw_user);
 
             let comment = comment
@@ -376,8 +501,8 @@ fn text(
 
             Some(format!("⟵ {target} been kicked by {user}{comment}"))
         }
-        Command::MODE(target, modes, args) if proto::is_channel(target) => {
-            let raw_user = message.user()?;
+        Command::MODE(target, modes, args) if proto::is_channel(target, chantypes) => {
+            let raw_user = message.user(casemapping)?;
             let user = resolve_attributes(&raw_user, target).unwrap_or(raw_user);
 
             let modes = modes
@@ -394,9 +519,51 @@ fn text(
 
             Some(format!(" ∙ {user} sets mode {modes} {args}"))
         }
+        Command::PRIVMSG(target, text) if is_ctcp(text) => {
+            let query = ctcp::decode(text)?;
+            let user = message.user(casemapping)?;
+            let params = query
+                .params
+                .map(|params| format!(" {params}"))
+                .unwrap_or_default();
+
+            if user.nickname() == *our_nick {
+                Some(format!(
+                    " ∙ sent CTCP {} to {target}{params}",
+                    query.command
+                ))
+            } else {
+                Some(format!(
+                    " ∙ {} requested CTCP {}{params}",
+                    user.nickname(),
+                    query.command
+                ))
+            }
+        }
+        Command::NOTICE(_, text) if is_ctcp(text) => {
+            let reply = ctcp::decode(text)?;
+            let user = message.user(casemapping)?;
+
+            let params = match (&reply.command, reply.params) {
+                // Our own PING queries contain the time they were sent
+                (ctcp::Command::Ping, Some(params)) => params
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|sent| Posix::now().as_nanos().checked_sub(sent))
+                    .map(|nanos| format!("{}ms", nanos / 1_000_000))
+                    .unwrap_or_else(|| params.to_string()),
+                (_, params) => params.unwrap_or_default().to_string(),
+            };
+
+            Some(format!(
+                " ∙ CTCP {} reply from {}: {params}",
+                reply.command,
+                user.nickname()
+            ))
+        }
         Command::PRIVMSG(_, text) => {
             // Check if a synthetic action message
-            if let Some(nick) = message.user().as_ref().map(User::nickname) {
+            if let Some(nick) = message.user(casemapping).as_ref().map(User::nickname) {
                 if let Some(action) = parse_action(nick, text) {
                     return Some(action);
                 }
@@ -523,7 +690,7 @@ fn text(
 pub enum Limit {
     Top(usize),
     Bottom(usize),
-    Since(time::Posix),
+    Since(DateTime<Utc>),
 }
 
 impl Limit {
@@ -540,18 +707,78 @@ impl Limit {
 }
 
 fn is_action(text: &str) -> bool {
-    text.starts_with("\u{1}ACTION ") && text.ends_with('\u{1}')
+    ctcp::decode(text).is_some_and(|message| message.command == ctcp::Command::Action)
+}
+
+/// A CTCP message other than `ACTION`
+fn is_ctcp(text: &str) -> bool {
+    ctcp::decode(text).is_some_and(|message| message.command != ctcp::Command::Action)
 }
 
 pub fn parse_action(nick: NickRef, text: &str) -> Option<String> {
-    let action = text.strip_prefix("\u{1}ACTION ")?.strip_suffix('\u{1}')?;
-    Some(action_text(nick, action))
+    let message = ctcp::decode(text)?;
+
+    (message.command == ctcp::Command::Action)
+        .then(|| action_text(nick, message.params.unwrap_or_default()))
 }
 
 pub fn action_text(nick: NickRef, action: &str) -> String {
     format!(" ∙ {nick} {action}")
 }
 
-pub fn reference_user(sender: NickRef, own_nick: NickRef, text: &str) -> bool {
-    sender != own_nick && text.contains(own_nick.as_ref())
+pub fn reference_user(
+    sender: NickRef,
+    own_nick: NickRef,
+    text: &str,
+    casemapping: CaseMap,
+) -> bool {
+    sender != own_nick && casemapping.normalize(text).contains(own_nick.normalized())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn duplicates() {
+        let message = Message {
+            received_at: Posix::now(),
+            server_time: Utc::now(),
+            id: None,
+            direction: Direction::Received,
+            target: Target::Server {
+                source: Source::Server(None),
+            },
+            text: "hello".into(),
+            content: Default::default(),
+        };
+
+        // History written before ids were stored has no `id` field
+        let mut legacy = serde_json::to_value(&message).unwrap();
+        legacy.as_object_mut().unwrap().remove("id");
+        let legacy: Message = serde_json::from_value(legacy).unwrap();
+
+        assert_eq!(legacy.id, None);
+        assert!(legacy.is_replayed(&message));
+        assert!(!legacy.is_duplicate(&message));
+
+        let first = Message {
+            id: Some("a".into()),
+            ..message.clone()
+        };
+        let second = Message {
+            id: Some("b".into()),
+            ..message.clone()
+        };
+        let other = Message {
+            text: "bye".into(),
+            ..message.clone()
+        };
+
+        assert!(!first.is_duplicate(&second));
+        assert!(first.is_duplicate(&first.clone()));
+        assert!(!first.is_replayed(&second));
+        assert!(first.is_replayed(&message));
+        assert!(!message.is_replayed(&other));
+    }
 }
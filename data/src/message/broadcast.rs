@@ -23,9 +23,11 @@ fn expand(
         Message {
             received_at: Posix::now(),
             server_time: sent_time,
+            id: None,
             direction: Direction::Received,
             target,
             text,
+            content: Default::default(),
         }
     };
 
@@ -77,8 +79,8 @@ pub fn connecting(sent_time: DateTime<Utc>) -> Vec<Message> {
     )
 }
 
-pub fn connected(sent_time: DateTime<Utc>) -> Vec<Message> {
-    let text = " ∙ connected".into();
+pub fn connected(address: &str, sent_time: DateTime<Utc>) -> Vec<Message> {
+    let text = format!(" ∙ connected to {address}");
     expand(
         [],
         [],
@@ -89,8 +91,25 @@ pub fn connected(sent_time: DateTime<Utc>) -> Vec<Message> {
     )
 }
 
-pub fn connection_failed(error: String, sent_time: DateTime<Utc>) -> Vec<Message> {
-    let text = format!(" ∙ connection to server failed ({error})");
+pub fn connection_failed(address: &str, error: String, sent_time: DateTime<Utc>) -> Vec<Message> {
+    let text = format!(" ∙ connection to {address} failed ({error})");
+    expand(
+        [],
+        [],
+        true,
+        Cause::Status(source::Status::Error),
+        text,
+        sent_time,
+    )
+}
+
+pub fn sasl_failed(error: String, disconnect: bool, sent_time: DateTime<Utc>) -> Vec<Message> {
+    let outcome = if disconnect {
+        "disconnecting"
+    } else {
+        "continuing without authentication"
+    };
+    let text = format!(" ∙ SASL authentication failed ({error}), {outcome}");
     expand(
         [],
         [],
@@ -122,9 +141,10 @@ pub fn disconnected(
 pub fn reconnected(
     channels: impl IntoIterator<Item = String>,
     queries: impl IntoIterator<Item = Nick>,
+    address: &str,
     sent_time: DateTime<Utc>,
 ) -> Vec<Message> {
-    let text = " ∙ connection to server restored".into();
+    let text = format!(" ∙ connection to server restored ({address})");
     expand(
         channels,
         queries,
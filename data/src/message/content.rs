@@ -0,0 +1,88 @@
+use std::sync::OnceLock;
+
+use super::link::{self, Link};
+use crate::formatting::{self, Formatting};
+
+/// A run of message text sharing the same formatting, which is either
+/// plain text or a URL / channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    pub text: String,
+    pub formatting: Formatting,
+    pub link: Option<Link>,
+}
+
+/// The fragments of message text, parsed the first time they're needed
+#[derive(Debug, Clone, Default)]
+pub struct Content(OnceLock<Vec<Fragment>>);
+
+impl Content {
+    pub fn fragments(&self, text: &str, chantypes: &[char]) -> &[Fragment] {
+        self.0.get_or_init(|| parse(text, chantypes))
+    }
+}
+
+/// Splits `text` into its formatted spans, and those into plain text & the
+/// URLs and channels they mention
+pub fn parse(text: &str, chantypes: &[char]) -> Vec<Fragment> {
+    formatting::parse(text)
+        .into_iter()
+        .flat_map(|span| {
+            link::parse(&span.text, chantypes)
+                .into_iter()
+                .map(|fragment| match fragment {
+                    link::Fragment::Text(text) => Fragment {
+                        text: text.to_string(),
+                        formatting: span.formatting,
+                        link: None,
+                    },
+                    link::Fragment::Link(link) => Fragment {
+                        text: link.target().to_string(),
+                        formatting: span.formatting,
+                        link: Some(link),
+                    },
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fragments() {
+        let bold = Formatting {
+            bold: true,
+            ..Formatting::default()
+        };
+        let fragment = |text: &str, formatting, link| Fragment {
+            text: text.to_string(),
+            formatting,
+            link,
+        };
+
+        assert_eq!(
+            parse("see \x02https://halloy.chat\x02 in #halloy", &['#']),
+            vec![
+                fragment("see ", Formatting::default(), None),
+                fragment(
+                    "https://halloy.chat",
+                    bold,
+                    Some(Link::Url("https://halloy.chat".to_string()))
+                ),
+                fragment(" in ", Formatting::default(), None),
+                fragment(
+                    "#halloy",
+                    Formatting::default(),
+                    Some(Link::Channel("#halloy".to_string()))
+                ),
+            ]
+        );
+
+        let content = Content::default();
+        let first = content.fragments("hello", &['#']).as_ptr();
+        assert_eq!(content.fragments("hello", &['#']).as_ptr(), first);
+    }
+}
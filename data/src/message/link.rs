@@ -0,0 +1,183 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    Url(String),
+    Channel(String),
+}
+
+impl Link {
+    /// The URL or channel as it appears in the text
+    pub fn target(&self) -> &str {
+        match self {
+            Link::Url(url) => url,
+            Link::Channel(channel) => channel,
+        }
+    }
+
+    /// The URL to open, defaulting to HTTPS for `www.` links
+    pub fn url(&self) -> Option<String> {
+        match self {
+            Link::Url(url) if url.to_ascii_lowercase().starts_with("www.") => {
+                Some(format!("https://{url}"))
+            }
+            Link::Url(url) => Some(url.clone()),
+            Link::Channel(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fragment<'a> {
+    Text(&'a str),
+    Link(Link),
+}
+
+const URL_PREFIXES: &[&str] = &["https://", "http://", "www."];
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ':', ';', '!', '?', '\'', '"'];
+
+/// Splits `text` into plain text & the URLs and channels it mentions
+pub fn parse<'a>(text: &'a str, chantypes: &[char]) -> Vec<Fragment<'a>> {
+    let mut fragments = vec![];
+    let mut plain_start = 0;
+
+    for (start, word) in words(text) {
+        let Some((offset, link)) = link(word, chantypes) else {
+            continue;
+        };
+
+        let start = start + offset;
+        let end = start + link.target().len();
+
+        if plain_start < start {
+            fragments.push(Fragment::Text(&text[plain_start..start]));
+        }
+
+        fragments.push(Fragment::Link(link));
+        plain_start = end;
+    }
+
+    if plain_start < text.len() {
+        fragments.push(Fragment::Text(&text[plain_start..]));
+    }
+
+    fragments
+}
+
+/// Whitespace separated words & their byte offset in `text`
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = vec![];
+    let mut start = None;
+
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(word_start), true) => {
+                words.push((word_start, &text[word_start..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    words
+}
+
+/// The link in `word` & its offset, w/o surrounding punctuation
+fn link(word: &str, chantypes: &[char]) -> Option<(usize, Link)> {
+    let trimmed = word.trim_start_matches(['(', '[', '<', '"', '\'']);
+    let offset = word.len() - trimmed.len();
+
+    let is_link = is_url(trimmed);
+    let is_channel = chantypes.iter().any(|c| trimmed.starts_with(*c));
+
+    if !is_link && !is_channel {
+        return None;
+    }
+
+    let mut link = trimmed;
+
+    loop {
+        let next = link.trim_end_matches(TRAILING_PUNCTUATION);
+        // Keep closing brackets which are part of the link, such as in
+        // wikipedia URLs
+        let next = [('(', ')'), ('[', ']'), ('<', '>')]
+            .iter()
+            .fold(next, |next, (open, close)| {
+                if next.ends_with(*close)
+                    && next.matches(*close).count() > next.matches(*open).count()
+                {
+                    &next[..next.len() - 1]
+                } else {
+                    next
+                }
+            });
+
+        if next == link {
+            break;
+        }
+
+        link = next;
+    }
+
+    let link = if is_channel {
+        // A lone channel prefix isn't a channel
+        (link.chars().count() > 1 && !link.contains(',')).then(|| Link::Channel(link.to_string()))
+    } else {
+        is_url(link).then(|| Link::Url(link.to_string()))
+    }?;
+
+    Some((offset, link))
+}
+
+fn is_url(text: &str) -> bool {
+    URL_PREFIXES.iter().any(|prefix| {
+        text.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+            && text.len() > prefix.len()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_links() {
+        let chantypes = &['#', '&'];
+
+        let tests = [
+            ("no links here", vec![Fragment::Text("no links here")]),
+            (
+                "see https://example.com/a_(b), then #rust.",
+                vec![
+                    Fragment::Text("see "),
+                    Fragment::Link(Link::Url("https://example.com/a_(b)".into())),
+                    Fragment::Text(", then "),
+                    Fragment::Link(Link::Channel("#rust".into())),
+                    Fragment::Text("."),
+                ],
+            ),
+            (
+                "(www.example.com)",
+                vec![
+                    Fragment::Text("("),
+                    Fragment::Link(Link::Url("www.example.com".into())),
+                    Fragment::Text(")"),
+                ],
+            ),
+            (
+                "<http://example.com>  &local",
+                vec![
+                    Fragment::Text("<"),
+                    Fragment::Link(Link::Url("http://example.com".into())),
+                    Fragment::Text(">  "),
+                    Fragment::Link(Link::Channel("&local".into())),
+                ],
+            ),
+            ("# https:// c#", vec![Fragment::Text("# https:// c#")]),
+        ];
+
+        for (text, expected) in tests {
+            assert_eq!(parse(text, chantypes), expected, "{text:?}");
+        }
+    }
+}
@@ -70,6 +70,8 @@ pub mod server {
         Part,
         Quit,
         ReplyTopic,
+        CtcpQuery,
+        CtcpReply,
     }
 
     #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
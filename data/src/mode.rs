@@ -1,3 +1,5 @@
+use crate::isupport::{ChannelModes, PrefixMap};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Mode<T> {
     Add(T, Option<String>),
@@ -88,6 +90,32 @@ impl From<char> for Channel {
     }
 }
 
+impl From<Channel> for char {
+    fn from(mode: Channel) -> Self {
+        use Channel::*;
+
+        match mode {
+            Ban => 'b',
+            Exception => 'e',
+            Limit => 'l',
+            InviteOnly => 'i',
+            InviteException => 'I',
+            Key => 'k',
+            Moderated => 'm',
+            RegisteredOnly => 'r',
+            Secret => 's',
+            ProtectedTopic => 't',
+            NoExternalMessages => 'n',
+            Founder => 'q',
+            Admin => 'a',
+            Oper => 'o',
+            Halfop => 'h',
+            Voice => 'v',
+            Unknown(c) => c,
+        }
+    }
+}
+
 impl Parser for Channel {
     fn takes_arg(self) -> bool {
         use Channel::*;
@@ -111,7 +139,17 @@ impl Parser for Channel {
     }
 }
 
-pub fn parse<T>(encoded: &str, args: &[String]) -> Vec<Mode<T>>
+/// Parse a modestring & its arguments
+///
+/// Whether a mode consumes an argument is looked up in the server's
+/// `PREFIX` and `CHANMODES`, falling back to [`Parser::takes_arg`] for
+/// modes the server didn't advertise.
+pub fn parse<T>(
+    encoded: &str,
+    args: &[String],
+    chanmodes: Option<&ChannelModes>,
+    prefix: &[PrefixMap],
+) -> Vec<Mode<T>>
 where
     T: Parser,
 {
@@ -146,9 +184,18 @@ where
             Mod::None(s) => s,
         };
 
+        let operation = match _mod {
+            Mod::Minus(_) => Operation::Remove,
+            Mod::Plus(_) | Mod::None(_) => Operation::Add,
+        };
+
         for c in modes.chars() {
             let value = T::from_char(c);
-            let arg = if value.takes_arg() {
+            let takes_arg = prefix.iter().any(|prefix| prefix.mode == c)
+                || chanmodes
+                    .and_then(|chanmodes| chanmodes.takes_arg(c, &operation))
+                    .unwrap_or_else(|| value.takes_arg());
+            let arg = if takes_arg {
                 args.next().cloned()
             } else {
                 None
@@ -168,6 +215,7 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::isupport;
 
     #[test]
     fn channel() {
@@ -187,7 +235,44 @@ mod test {
         ];
 
         for (modes, args, expected) in tests {
-            let modes = parse::<Channel>(modes, &args);
+            let modes = parse::<Channel>(modes, &args, None, isupport::DEFAULT_PREFIX);
+            assert_eq!(modes, expected);
+        }
+    }
+
+    #[test]
+    fn channel_isupport() {
+        // Libera: `q` is the quiet list & `f` only takes an argument when set
+        let chanmodes = "eIbq,k,flj,CFLMPQScgimnprstuz"
+            .parse::<ChannelModes>()
+            .unwrap();
+        let prefix = [PrefixMap {
+            mode: 'o',
+            prefix: '@',
+        }];
+
+        let tests = [
+            (
+                "+qo-f",
+                vec!["*!*@spam".into(), "nick".into()],
+                vec![
+                    Mode::Add(Channel::Founder, Some("*!*@spam".into())),
+                    Mode::Add(Channel::Oper, Some("nick".into())),
+                    Mode::Remove(Channel::Unknown('f'), None),
+                ],
+            ),
+            (
+                "+Yv",
+                vec!["nick".into()],
+                vec![
+                    Mode::Add(Channel::Unknown('Y'), None),
+                    Mode::Add(Channel::Voice, Some("nick".into())),
+                ],
+            ),
+        ];
+
+        for (modes, args, expected) in tests {
+            let modes = parse::<Channel>(modes, &args, Some(&chanmodes), &prefix);
             assert_eq!(modes, expected);
         }
     }
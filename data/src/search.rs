@@ -0,0 +1,347 @@
+//! Full-text search of the history stored on disk.
+//!
+//! Each segment has an index next to it, listing the words said on its
+//! day. It's updated whenever history of the day is flushed, so a search
+//! only reads the segments of the days which can match.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::history::{self, Error};
+use crate::isupport::CaseMap;
+use crate::message::Source;
+use crate::user::Nick;
+use crate::{compression, formatting, server, Buffer, Message};
+
+/// Max # matches returned by a search, newest first
+const MAX_MATCHES: usize = 500;
+const INDEX_EXTENSION: &str = ".index.json.gz";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// Words which messages must contain, or start words of
+    pub words: Vec<String>,
+    pub nick: Option<Nick>,
+    /// Channel, nickname or server of the buffer
+    pub buffer: Option<String>,
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl Query {
+    /// Parses the words to search for & the `from:<nick>`, `in:<buffer>`,
+    /// `since:<YYYY-MM-DD>` and `until:<YYYY-MM-DD>` filters
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut query = Query::default();
+
+        for term in text.split_whitespace() {
+            match term.split_once(':') {
+                Some(("from", nick)) if !nick.is_empty() => query.nick = Some(Nick::from(nick)),
+                Some(("in", buffer)) if !buffer.is_empty() => {
+                    query.buffer = Some(CaseMap::default().normalize(buffer));
+                }
+                Some(("since", date)) => query.since = Some(parse_date(date)?),
+                Some(("until", date)) => query.until = Some(parse_date(date)?),
+                _ => query.words.extend(words(term)),
+            }
+        }
+
+        if query == Query::default() {
+            return Err(ParseError::Empty);
+        }
+
+        Ok(query)
+    }
+
+    fn matches_buffer(&self, buffer: &Buffer) -> bool {
+        let Some(name) = &self.buffer else {
+            return true;
+        };
+
+        let target = match buffer {
+            Buffer::Server(server) => server.to_string(),
+            Buffer::Channel(_, channel) => channel.to_string(),
+            Buffer::Query(_, nick) => nick.to_string(),
+        };
+
+        CaseMap::default().normalize(&target) == *name
+    }
+
+    fn matches_day(&self, day: NaiveDate) -> bool {
+        let is_since = match self.since {
+            Some(since) => day >= since,
+            None => true,
+        };
+        let is_until = match self.until {
+            Some(until) => day <= until,
+            None => true,
+        };
+
+        is_since && is_until
+    }
+
+    fn matches_words(&self, words: &BTreeSet<String>) -> bool {
+        self.words.iter().all(|word| {
+            words
+                .range(word.clone()..)
+                .next()
+                .is_some_and(|candidate| candidate.starts_with(word.as_str()))
+        })
+    }
+
+    fn matches(&self, message: &Message) -> bool {
+        if !self.matches_day(message.server_time.date_naive()) {
+            return false;
+        }
+
+        if let Some(nick) = &self.nick {
+            let is_from = match message.target.source() {
+                Source::User(user) => user.nickname() == *nick,
+                Source::Action => message
+                    .action_sender()
+                    .is_some_and(|sender| Nick::from(sender) == *nick),
+                Source::Server(_) | Source::Internal(_) => false,
+            };
+
+            if !is_from {
+                return false;
+            }
+        }
+
+        self.matches_words(&words(&message.text).collect())
+    }
+}
+
+/// A message found by a search
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub buffer: Buffer,
+    pub message: Message,
+}
+
+/// Searches the history of every buffer, returning the newest matches first.
+/// History written to a single file is migrated first, if it belongs to one
+/// of `servers`, so it's indexed.
+pub async fn search(query: Query, servers: Vec<server::Server>) -> Result<Vec<Match>, Error> {
+    for path in history::migrate_files(&servers).await? {
+        log::warn!("skipped searching history at {}", path.display());
+    }
+
+    let mut matches = vec![];
+    let mut entries = fs::read_dir(history::root_dir().await?).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+
+        let dir = entry.path();
+
+        let Some((buffer, days)) = read_index(&dir).await? else {
+            continue;
+        };
+
+        if !query.matches_buffer(&buffer) {
+            continue;
+        }
+
+        for (day, words) in days {
+            if !query.matches_day(day) || !query.matches_words(&words) {
+                continue;
+            }
+
+            let messages =
+                history::read_segment_if_exists(&history::segment_path(&dir, day)).await?;
+
+            matches.extend(
+                messages
+                    .into_iter()
+                    .filter(|message| query.matches(message))
+                    .map(|message| Match {
+                        buffer: buffer.clone(),
+                        message,
+                    }),
+            );
+        }
+    }
+
+    matches.sort_by_key(|found| Reverse(found.message.server_time));
+    matches.truncate(MAX_MATCHES);
+
+    Ok(matches)
+}
+
+/// The words said on a day, merged into its index when history is flushed
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    buffer: Buffer,
+    day: NaiveDate,
+    words: BTreeSet<String>,
+}
+
+impl Entry {
+    pub(crate) fn new(buffer: Buffer, day: NaiveDate, messages: &[Message]) -> Self {
+        Self {
+            buffer,
+            day,
+            words: messages
+                .iter()
+                .flat_map(|message| words(&message.text))
+                .collect(),
+        }
+    }
+}
+
+/// Adds `entries` to the indexes of their days in the history in `dir`,
+/// leaving the indexes of other days as they are
+pub(crate) async fn index(dir: &Path, entries: Vec<Entry>) -> Result<(), Error> {
+    for entry in entries {
+        let path = index_path(dir, entry.day);
+
+        let stored = match fs::read(&path).await {
+            Ok(bytes) => Some(compression::decompress::<Entry>(&bytes)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+
+        let compressed = compression::compress(&merge(stored, entry))?;
+
+        fs::write(&path, &compressed).await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes the index of `day` in the history in `dir`, as its segment was
+/// pruned
+pub(crate) async fn remove_index(dir: &Path, day: NaiveDate) -> Result<(), Error> {
+    match fs::remove_file(index_path(dir, day)).await {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+/// `entry` w/ the words of the `stored` entry of its day added
+fn merge(stored: Option<Entry>, mut entry: Entry) -> Entry {
+    if let Some(stored) = stored {
+        entry.words.extend(stored.words);
+    }
+
+    entry
+}
+
+fn index_path(dir: &Path, day: NaiveDate) -> PathBuf {
+    dir.join(format!("{day}{INDEX_EXTENSION}"))
+}
+
+/// The buffer of the history in `dir` & the words said on each day
+pub(crate) async fn read_index(
+    dir: &Path,
+) -> Result<Option<(Buffer, BTreeMap<NaiveDate, BTreeSet<String>>)>, Error> {
+    let mut buffer = None;
+    let mut days = BTreeMap::new();
+    let mut entries = fs::read_dir(dir).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let is_index = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.ends_with(INDEX_EXTENSION));
+
+        if !is_index {
+            continue;
+        }
+
+        let entry = compression::decompress::<Entry>(&fs::read(entry.path()).await?)?;
+
+        days.insert(entry.day, entry.words);
+        buffer = Some(entry.buffer);
+    }
+
+    Ok(buffer.map(|buffer| (buffer, days)))
+}
+
+/// Lowercased words of `text`, w/o its formatting
+fn words(text: &str) -> impl Iterator<Item = String> {
+    formatting::strip(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, ParseError> {
+    date.parse()
+        .map_err(|_| ParseError::InvalidDate(date.to_string()))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("nothing to search for")]
+    Empty,
+    #[error("invalid date {0:?}, expected YYYY-MM-DD")]
+    InvalidDate(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::User;
+
+    #[test]
+    fn parse_query() {
+        let query = Query::parse("Hello, World from:Bob in:#Halloy since:2024-03-01").unwrap();
+
+        assert_eq!(query.words, ["hello", "world"]);
+        assert_eq!(query.nick, Some(Nick::from("bob")));
+        assert_eq!(query.buffer.as_deref(), Some("#halloy"));
+        assert_eq!(query.since, NaiveDate::from_ymd_opt(2024, 3, 1));
+        assert_eq!(query.until, None);
+
+        assert!(matches!(Query::parse("  "), Err(ParseError::Empty)));
+        assert!(matches!(
+            Query::parse("until:yesterday"),
+            Err(ParseError::InvalidDate(_))
+        ));
+    }
+
+    #[test]
+    fn match_messages() {
+        let message = |nick: &str, text: &str| {
+            Message::test(Source::User(User::from(Nick::from(nick))), text)
+        };
+
+        let query = Query::parse("hal \x02release\x02 from:bob since:2024-03-02").unwrap();
+
+        assert!(query.matches(&message("Bob", "Halloy's next release is out")));
+        assert!(!query.matches(&message("alice", "Halloy's next release is out")));
+        assert!(!query.matches(&message("bob", "a new version of halloy")));
+        assert!(!Query::parse("until:2024-03-01")
+            .unwrap()
+            .matches(&message("bob", "anything")));
+    }
+
+    #[test]
+    fn merge_index() {
+        let buffer = Buffer::Channel("server".into(), "#halloy".into());
+        let day = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+        let entry = |words: &[&str]| Entry {
+            buffer: buffer.clone(),
+            day,
+            words: words.iter().map(|word| word.to_string()).collect(),
+        };
+
+        assert_eq!(merge(None, entry(&["hello"])), entry(&["hello"]));
+        assert_eq!(
+            merge(Some(entry(&["a", "b"])), entry(&["b", "c"])),
+            entry(&["a", "b", "c"])
+        );
+    }
+}
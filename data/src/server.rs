@@ -2,7 +2,7 @@ use std::collections::BTreeMap;
 use std::fmt;
 use std::fs;
 
-use futures::channel::mpsc::Sender;
+use futures::channel::mpsc::UnboundedSender;
 use irc::proto;
 use serde::{Deserialize, Serialize};
 
@@ -10,7 +10,7 @@ use crate::config;
 use crate::config::server::Sasl;
 use crate::config::Error;
 
-pub type Handle = Sender<proto::Message>;
+pub type Handle = UnboundedSender<proto::Message>;
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Server(String);
@@ -21,6 +21,12 @@ impl fmt::Display for Server {
     }
 }
 
+impl From<&str> for Server {
+    fn from(value: &str) -> Self {
+        Server(value.to_string())
+    }
+}
+
 impl AsRef<str> for Server {
     fn as_ref(&self) -> &str {
         &self.0
@@ -46,57 +52,164 @@ impl<'a> From<(&'a Server, &'a config::Server)> for Entry {
 pub struct Map(BTreeMap<Server, config::Server>);
 
 impl Map {
+    pub fn insert(&mut self, server: Server, config: config::Server) {
+        self.0.insert(server, config);
+    }
+
     pub fn remove(&mut self, server: &Server) {
         self.0.remove(server);
     }
 
+    pub fn get(&self, server: &Server) -> Option<&config::Server> {
+        self.0.get(server)
+    }
+
     pub fn entries(&self) -> impl Iterator<Item = Entry> + '_ {
         self.0.iter().map(Entry::from)
     }
 
-    pub fn read_password_files(&mut self) -> Result<(), Error> {
-        let trimmed = |s: String| s.trim().to_string();
+    /// Servers added, removed or edited in `new`, a reloaded config
+    pub fn changes(&self, new: &Map) -> Vec<Change> {
+        let removed = self
+            .0
+            .keys()
+            .filter(|server| !new.0.contains_key(server))
+            .cloned()
+            .map(Change::Removed);
 
+        let added_or_edited =
+            new.0
+                .iter()
+                .filter_map(|(server, config)| match self.0.get(server) {
+                    None => Some(Change::Added(server.clone(), config.clone())),
+                    Some(current) if current != config => {
+                        Some(Change::Edited(server.clone(), config.clone()))
+                    }
+                    Some(_) => None,
+                });
+
+        removed.chain(added_or_edited).collect()
+    }
+
+    pub fn read_password_files(&mut self) -> Result<(), Error> {
         for (_, config) in self.0.iter_mut() {
-            if let Some(pass_file) = &config.password_file {
-                if config.password.is_some() {
-                    return Err(Error::Parse(
-                        "Only one of password and password_file can be set.".to_string(),
-                    ));
-                }
-                let pass = fs::read_to_string(pass_file)?;
-                config.password = Some(trimmed(pass));
+            read_password_files(config)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(Server, config::Server),
+    Removed(Server),
+    Edited(Server, config::Server),
+}
+
+/// Reads the passwords of `config` which are set w/ a password file
+pub(crate) fn read_password_files(config: &mut config::Server) -> Result<(), Error> {
+    let trimmed = |s: String| s.trim().to_string();
+
+    if let Some(pass_file) = &config.password_file {
+        if config.password.is_some() {
+            return Err(Error::Parse(
+                "Only one of password and password_file can be set.".to_string(),
+            ));
+        }
+        let pass = fs::read_to_string(pass_file)?;
+        config.password = Some(trimmed(pass));
+    }
+    if let Some(nick_pass_file) = &config.nick_password_file {
+        if config.nick_password.is_some() {
+            return Err(Error::Parse(
+                "Only one of nick_password and nick_password_file can be set.".to_string(),
+            ));
+        }
+        let nick_pass = fs::read_to_string(nick_pass_file)?;
+        config.nick_password = Some(trimmed(nick_pass));
+    }
+    if let Some(sasl) = &mut config.sasl {
+        match sasl {
+            Sasl::Plain {
+                password: Some(_),
+                password_file: Some(_),
+                ..
+            } => {
+                return Err(Error::Parse(
+                    "Exactly one of sasl.plain.password or sasl.plain.password_file must be set."
+                        .to_string(),
+                ));
             }
-            if let Some(nick_pass_file) = &config.nick_password_file {
-                if config.nick_password.is_some() {
-                    return Err(Error::Parse(
-                        "Only one of nick_password and nick_password_file can be set.".to_string(),
-                    ));
-                }
-                let nick_pass = fs::read_to_string(nick_pass_file)?;
-                config.nick_password = Some(trimmed(nick_pass));
+            Sasl::Scram {
+                password: Some(_),
+                password_file: Some(_),
+                ..
+            } => {
+                return Err(Error::Parse(
+                    "Exactly one of sasl.scram.password or sasl.scram.password_file must be set."
+                        .to_string(),
+                ));
             }
-            if let Some(sasl) = &mut config.sasl {
-                match sasl {
-                    Sasl::Plain {
-                        password: Some(_),
-                        password_file: Some(_),
-                        ..
-                    } => {
-                        return Err(Error::Parse("Exactly one of sasl.plain.password or sasl.plain.password_file must be set.".to_string()));
-                    }
-                    Sasl::Plain {
-                        password: password @ None,
-                        password_file: Some(pass_file),
-                        ..
-                    } => {
-                        let pass = fs::read_to_string(pass_file)?;
-                        *password = Some(trimmed(pass));
-                    }
-                    _ => {}
-                }
+            Sasl::Plain {
+                password: password @ None,
+                password_file: Some(pass_file),
+                ..
             }
+            | Sasl::Scram {
+                password: password @ None,
+                password_file: Some(pass_file),
+                ..
+            } => {
+                let pass = fs::read_to_string(pass_file)?;
+                *password = Some(trimmed(pass));
+            }
+            _ => {}
         }
-        Ok(())
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn changes() {
+        let server = |name: &str| {
+            let mut config = config::Server::default();
+            config.nickname = "halloy".into();
+            config.server = format!("irc.{name}.net");
+            config
+        };
+
+        let old = Map(BTreeMap::from([
+            (Server::from("libera"), server("libera")),
+            (Server::from("oftc"), server("oftc")),
+            (Server::from("rizon"), server("rizon")),
+        ]));
+
+        let mut edited = server("oftc");
+        edited.channels = vec!["#halloy".into()];
+
+        let new = Map(BTreeMap::from([
+            (Server::from("libera"), server("libera")),
+            (Server::from("oftc"), edited.clone()),
+            (Server::from("snoonet"), server("snoonet")),
+        ]));
+
+        assert_eq!(
+            old.changes(&new),
+            vec![
+                Change::Removed(Server::from("rizon")),
+                Change::Edited(Server::from("oftc"), edited.clone()),
+                Change::Added(Server::from("snoonet"), server("snoonet")),
+            ]
+        );
+
+        // Joining other channels applies w/o reconnecting
+        assert!(!server("oftc").is_connection_changed(&edited));
+
+        edited.nickname = "halloy-user".into();
+        assert!(server("oftc").is_connection_changed(&edited));
     }
 }
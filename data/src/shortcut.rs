@@ -5,6 +5,8 @@ use std::str::FromStr;
 use iced_core::keyboard::{self, key};
 use serde::Deserialize;
 
+use crate::formatting;
+
 pub fn shortcut(key_bind: KeyBind, command: Command) -> Shortcut {
     Shortcut { key_bind, command }
 }
@@ -34,6 +36,7 @@ pub enum Command {
     CyclePreviousBuffer,
     ToggleNicklist,
     CommandBar,
+    Format(formatting::Modifier),
 }
 
 macro_rules! default {
@@ -110,6 +113,14 @@ impl KeyBind {
     default!(cycle_previous_buffer, Tab, CTRL | SHIFT);
     default!(toggle_nick_list, "m", COMMAND | ALT);
     default!(command_bar, "k", COMMAND);
+    default!(format_bold, "b", COMMAND);
+    default!(format_italic, "i", COMMAND);
+    default!(format_underline, "u", COMMAND);
+    default!(format_strikethrough, "s", COMMAND | SHIFT);
+    default!(format_monospace, "m", COMMAND | SHIFT);
+    default!(format_reverse, "r", COMMAND | SHIFT);
+    default!(format_color, "k", COMMAND | SHIFT);
+    default!(format_reset, "o", COMMAND | SHIFT);
 
     pub fn is_pressed(
         &self,
@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
 use std::time::Duration;
 
 use futures::channel::mpsc;
@@ -6,6 +7,7 @@ use futures::never::Never;
 use futures::{stream, FutureExt, SinkExt, StreamExt};
 use irc::proto::{self, command};
 use irc::{codec, connection, Connection};
+use rand::Rng;
 use tokio::time::{self, Instant, Interval};
 
 use crate::client::Client;
@@ -22,10 +24,12 @@ pub enum Error {
 
 #[derive(Debug)]
 pub enum Update {
+    Controller(Server, Controller),
     Connected {
         server: Server,
         client: Client,
         is_initial: bool,
+        address: String,
         sent_time: DateTime<Utc>,
     },
     Disconnected {
@@ -36,75 +40,165 @@ pub enum Update {
     },
     ConnectionFailed {
         server: Server,
+        address: String,
         error: String,
         sent_time: DateTime<Utc>,
     },
+    /// Not connecting until told to, by choice of the user
+    Offline(Server),
+    /// Ping sent to measure lag
+    Pinged(Server),
+    /// Round-trip time of a ping, from its echoed token
+    Ponged(Server, Duration),
+    /// # of messages waiting to be sent, whenever it changes
+    Queued(Server, usize),
     MessagesReceived(Server, Vec<message::Encoded>),
 }
 
+#[derive(Debug)]
+enum Control {
+    Connect,
+    Disconnect,
+    Configure(Box<config::Server>),
+    Update(Box<config::Server>),
+}
+
+/// Handle to drive the connection of a server
+#[derive(Debug, Clone)]
+pub struct Controller(mpsc::UnboundedSender<Control>);
+
+impl Controller {
+    /// Connects right away, dropping the current connection if any
+    pub fn connect(&self) {
+        let _ = self.0.unbounded_send(Control::Connect);
+    }
+
+    /// Disconnects & stops reconnecting until [`Controller::connect`]
+    pub fn disconnect(&self) {
+        let _ = self.0.unbounded_send(Control::Disconnect);
+    }
+
+    /// Reconnects w/ `config`, unless disconnected by the user
+    pub fn configure(&self, config: config::Server) {
+        let _ = self.0.unbounded_send(Control::Configure(Box::new(config)));
+    }
+
+    /// Uses `config` from now on, without reconnecting
+    pub fn update(&self, config: config::Server) {
+        let _ = self.0.unbounded_send(Control::Update(Box::new(config)));
+    }
+}
+
 enum State {
     Disconnected {
-        last_retry: Option<Instant>,
+        retry_at: Option<Instant>,
     },
     Connected {
         stream: Stream,
         batch: Batch,
+        queue: Queue,
         ping_time: Interval,
         ping_timeout: Option<Interval>,
+        is_banned: bool,
     },
+    /// Not reconnecting, after being banned or disconnected by the user
+    Stopped,
 }
 
 enum Input {
     IrcMessage(Result<codec::ParseResult, codec::Error>),
     Batch(Vec<message::Encoded>),
     Send(proto::Message),
+    Control(Control),
+    Flush,
     Ping,
     PingTimeout,
 }
 
 struct Stream {
     connection: Connection<irc::Codec>,
-    receiver: mpsc::Receiver<proto::Message>,
+    receiver: mpsc::UnboundedReceiver<proto::Message>,
 }
 
 pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Never {
-    let server::Entry { server, config } = server;
+    let server::Entry { server, mut config } = server;
 
-    let reconnect_delay = Duration::from_secs(config.reconnect_delay);
+    let mut backoff = Backoff::from_config(&config);
 
     let mut is_initial = true;
-    let mut state = State::Disconnected { last_retry: None };
+    let mut state = if config.autoconnect {
+        State::Disconnected { retry_at: None }
+    } else {
+        State::Stopped
+    };
+    // Index of the address to connect to, rotated when connecting fails
+    let mut address = 0;
+
+    // The controller is kept so controls never end
+    let (controller, mut controls) = mpsc::unbounded();
+    let controller = Controller(controller);
 
-    // Notify app of initial disconnected state
     let _ = sender
-        .send(Update::Disconnected {
-            server: server.clone(),
-            is_initial,
-            error: None,
-            sent_time: Utc::now(),
-        })
+        .send(Update::Controller(server.clone(), controller.clone()))
         .await;
 
+    if config.autoconnect {
+        // Notify app of initial disconnected state
+        let _ = sender
+            .send(Update::Disconnected {
+                server: server.clone(),
+                is_initial,
+                error: None,
+                sent_time: Utc::now(),
+            })
+            .await;
+    } else {
+        let _ = sender.send(Update::Offline(server.clone())).await;
+    }
+
     loop {
         match &mut state {
-            State::Disconnected { last_retry } => {
-                if let Some(last_retry) = last_retry.as_ref() {
-                    let remaining = reconnect_delay.saturating_sub(last_retry.elapsed());
-
-                    if !remaining.is_zero() {
-                        time::sleep(remaining).await;
+            State::Disconnected { retry_at } => {
+                if let Some(retry_at) = *retry_at {
+                    match time::timeout_at(retry_at, controls.next()).await {
+                        Ok(Some(Control::Disconnect)) => {
+                            log::info!("[{server}] stopped reconnecting");
+                            let _ = sender.send(Update::Offline(server.clone())).await;
+                            state = State::Stopped;
+                            continue;
+                        }
+                        Ok(Some(Control::Connect)) => {
+                            backoff.reset();
+                            address = 0;
+                        }
+                        Ok(Some(Control::Configure(new))) => {
+                            config = *new;
+                            backoff = Backoff::from_config(&config);
+                            address = 0;
+                        }
+                        // Keeps waiting to retry
+                        Ok(Some(Control::Update(new))) => {
+                            config = *new;
+                            backoff.configure(&config);
+                            continue;
+                        }
+                        Ok(None) | Err(_) => {}
                     }
                 }
 
-                match connect(server.clone(), config.clone()).await {
+                let connection = config.connection(address);
+                let address_name = format!("{}:{}", connection.server, connection.port);
+
+                match connect(server.clone(), config.clone(), connection).await {
                     Ok((stream, client)) => {
-                        log::info!("[{server}] connected");
+                        log::info!("[{server}] connected to {address_name}");
 
                         let _ = sender
                             .send(Update::Connected {
                                 server: server.clone(),
                                 client,
                                 is_initial,
+                                address: address_name,
                                 sent_time: Utc::now(),
                             })
                             .await;
@@ -114,8 +208,10 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
                         state = State::Connected {
                             stream,
                             batch: Batch::new(),
+                            queue: Queue::new(config.send_burst, config.send_rate),
                             ping_timeout: None,
                             ping_time: ping_time_interval(config.ping_time),
+                            is_banned: false,
                         };
                     }
                     Err(e) => {
@@ -125,34 +221,51 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
                             _ => e.to_string(),
                         };
 
-                        log::warn!("[{server}] connection failed: {error}");
+                        log::warn!("[{server}] connection to {address_name} failed: {error}");
 
                         let _ = sender
                             .send(Update::ConnectionFailed {
                                 server: server.clone(),
+                                address: address_name,
                                 error,
                                 sent_time: Utc::now(),
                             })
                             .await;
 
-                        *last_retry = Some(Instant::now());
+                        // Try the next address right away, backing off once
+                        // all addresses have failed
+                        address = (address + 1) % config.address_count();
+
+                        *retry_at = (address == 0).then(|| backoff.next_retry());
                     }
                 }
             }
             State::Connected {
                 stream,
                 batch,
+                queue,
                 ping_time,
                 ping_timeout,
+                is_banned,
             } => {
                 let input = {
                     let mut select = stream::select_all([
                         (&mut stream.connection).map(Input::IrcMessage).boxed(),
                         (&mut stream.receiver).map(Input::Send).boxed(),
+                        (&mut controls).map(Input::Control).boxed(),
                         ping_time.tick().into_stream().map(|_| Input::Ping).boxed(),
                         batch.map(Input::Batch).boxed(),
                     ]);
 
+                    if let Some(next) = queue.next_send() {
+                        select.push(
+                            time::sleep_until(next)
+                                .into_stream()
+                                .map(|_| Input::Flush)
+                                .boxed(),
+                        );
+                    }
+
                     if let Some(timeout) = ping_timeout.as_mut() {
                         select.push(
                             timeout
@@ -169,34 +282,65 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
                 match input {
                     Input::IrcMessage(Ok(Ok(message))) => match message.command {
                         proto::Command::PING(token) => {
-                            let _ = stream.connection.send(command!("PONG", token)).await;
+                            queue.push(command!("PONG", token));
+                            queue
+                                .flush(&server, &mut stream.connection, &mut sender)
+                                .await;
                         }
                         proto::Command::PONG(_, token) => {
                             let token = token.unwrap_or_default();
                             log::trace!("[{server}] pong received: {token}");
 
                             *ping_timeout = None;
+
+                            if let Some(lag) = round_trip(&token, Posix::now()) {
+                                let _ = sender.send(Update::Ponged(server.clone(), lag)).await;
+                            }
                         }
                         proto::Command::ERROR(error) => {
+                            let is_banned = *is_banned || is_ban(&error);
+
                             log::warn!("[{server}] disconnected: {error}");
                             let _ = sender
                                 .send(Update::Disconnected {
                                     server: server.clone(),
                                     is_initial,
-                                    error: Some(error),
+                                    error: Some(if is_banned {
+                                        format!("{error}; banned, not reconnecting")
+                                    } else {
+                                        error
+                                    }),
                                     sent_time: Utc::now(),
                                 })
                                 .await;
-                            state = State::Disconnected {
-                                last_retry: Some(Instant::now()),
+
+                            state = if is_banned {
+                                State::Stopped
+                            } else {
+                                State::Disconnected {
+                                    retry_at: Some(backoff.next_retry()),
+                                }
                             };
                         }
+                        proto::Command::Numeric(numeric, _) => {
+                            match numeric {
+                                command::Numeric::RPL_WELCOME => {
+                                    backoff.reset();
+                                    // Measure lag right away
+                                    ping_time.reset_immediately();
+                                }
+                                command::Numeric::ERR_YOUREBANNEDCREEP => *is_banned = true,
+                                _ => {}
+                            }
+
+                            batch.messages.push(message.into());
+                        }
                         _ => {
                             batch.messages.push(message.into());
                         }
                     },
                     Input::IrcMessage(Ok(Err(e))) => {
-                        log::warn!("message decoding failed: {e}");
+                        log::warn!("[{server}] message decoding failed: {e}");
                     }
                     Input::IrcMessage(Err(e)) => {
                         log::warn!("[{server}] disconnected: {e}");
@@ -209,7 +353,7 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
                             })
                             .await;
                         state = State::Disconnected {
-                            last_retry: Some(Instant::now()),
+                            retry_at: Some(backoff.next_retry()),
                         };
                     }
                     Input::Batch(messages) => {
@@ -218,13 +362,71 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
                             .await;
                     }
                     Input::Send(message) => {
-                        let _ = stream.connection.send(message).await;
+                        queue.push(message);
+                        queue
+                            .flush(&server, &mut stream.connection, &mut sender)
+                            .await;
+                    }
+                    // Applied w/o reconnecting
+                    Input::Control(Control::Update(new)) => {
+                        config = *new;
+                        backoff.configure(&config);
+                    }
+                    Input::Control(control) => {
+                        let (error, next) = match control {
+                            Control::Connect => {
+                                backoff.reset();
+                                address = 0;
+
+                                ("reconnecting", State::Disconnected { retry_at: None })
+                            }
+                            Control::Update(_) => unreachable!("applied w/o reconnecting above"),
+                            Control::Configure(new) => {
+                                config = *new;
+                                backoff = Backoff::from_config(&config);
+                                address = 0;
+
+                                ("reconnecting", State::Disconnected { retry_at: None })
+                            }
+                            Control::Disconnect => ("disconnected", State::Stopped),
+                        };
+
+                        queue.push(command!("QUIT"));
+                        queue
+                            .flush(&server, &mut stream.connection, &mut sender)
+                            .await;
+
+                        log::info!("[{server}] {error}");
+                        let _ = sender
+                            .send(Update::Disconnected {
+                                server: server.clone(),
+                                is_initial,
+                                error: Some(error.into()),
+                                sent_time: Utc::now(),
+                            })
+                            .await;
+
+                        if let State::Stopped = next {
+                            let _ = sender.send(Update::Offline(server.clone())).await;
+                        }
+
+                        state = next;
+                    }
+                    Input::Flush => {
+                        queue
+                            .flush(&server, &mut stream.connection, &mut sender)
+                            .await;
                     }
                     Input::Ping => {
                         let now = Posix::now().as_nanos().to_string();
                         log::trace!("[{server}] ping sent: {now}");
 
-                        let _ = stream.connection.send(command!("PING", now)).await;
+                        queue.push(command!("PING", now));
+                        queue
+                            .flush(&server, &mut stream.connection, &mut sender)
+                            .await;
+
+                        let _ = sender.send(Update::Pinged(server.clone())).await;
 
                         if ping_timeout.is_none() {
                             *ping_timeout = Some(ping_timeout_interval(config.ping_timeout));
@@ -241,11 +443,41 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
                             })
                             .await;
                         state = State::Disconnected {
-                            last_retry: Some(Instant::now()),
+                            retry_at: Some(backoff.next_retry()),
                         };
                     }
                 }
             }
+            State::Stopped => match controls.next().await {
+                Some(Control::Connect) => {
+                    backoff.reset();
+                    address = 0;
+
+                    if is_initial {
+                        let _ = sender
+                            .send(Update::Disconnected {
+                                server: server.clone(),
+                                is_initial,
+                                error: None,
+                                sent_time: Utc::now(),
+                            })
+                            .await;
+                    }
+
+                    state = State::Disconnected { retry_at: None };
+                }
+                // Stopped after a ban, now by choice
+                Some(Control::Disconnect) => {
+                    let _ = sender.send(Update::Offline(server.clone())).await;
+                }
+                // Used once connected
+                Some(Control::Configure(new) | Control::Update(new)) => {
+                    config = *new;
+                    backoff = Backoff::from_config(&config);
+                    address = 0;
+                }
+                None => {}
+            },
         }
     }
 }
@@ -253,10 +485,11 @@ pub async fn run(server: server::Entry, mut sender: mpsc::Sender<Update>) -> Nev
 async fn connect(
     server: Server,
     config: config::Server,
+    connection: connection::Config<'_>,
 ) -> Result<(Stream, Client), connection::Error> {
-    let connection = Connection::new(config.connection(), irc::Codec).await?;
+    let connection = Connection::new(connection, config.codec()).await?;
 
-    let (sender, receiver) = mpsc::channel(100);
+    let (sender, receiver) = mpsc::unbounded();
 
     Ok((
         Stream {
@@ -310,6 +543,198 @@ impl futures::Stream for Batch {
     }
 }
 
+/// Paces outgoing messages w/ a token bucket so we don't get
+/// disconnected for flooding the server
+struct Queue {
+    burst: u16,
+    rate: Duration,
+    tokens: u16,
+    last_refill: Instant,
+    /// Keepalive & registration messages, sent ahead of anything else
+    priority: VecDeque<proto::Message>,
+    messages: VecDeque<proto::Message>,
+    /// Last # of queued messages sent as an update
+    reported: usize,
+}
+
+impl Queue {
+    fn new(burst: u16, rate: Duration) -> Self {
+        let burst = burst.max(1);
+
+        Self {
+            burst,
+            rate,
+            tokens: burst,
+            last_refill: Instant::now(),
+            priority: VecDeque::new(),
+            messages: VecDeque::new(),
+            reported: 0,
+        }
+    }
+
+    fn push(&mut self, message: proto::Message) {
+        if is_priority(&message) {
+            self.priority.push_back(message);
+        } else {
+            self.messages.push_back(message);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.priority.len() + self.messages.len()
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if self.tokens >= self.burst || self.rate.is_zero() {
+            self.tokens = self.burst;
+            self.last_refill = now;
+            return;
+        }
+
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        let refilled = (elapsed.as_nanos() / self.rate.as_nanos()).min(u16::MAX as u128) as u16;
+
+        if refilled > 0 {
+            self.tokens = self.tokens.saturating_add(refilled).min(self.burst);
+            self.last_refill = if self.tokens == self.burst {
+                now
+            } else {
+                self.last_refill + self.rate * u32::from(refilled)
+            };
+        }
+    }
+
+    /// Takes the next message which can be sent at `now`. Priority messages
+    /// are never held back, but still use up a token.
+    fn pop(&mut self, now: Instant) -> Option<proto::Message> {
+        self.refill(now);
+
+        if let Some(message) = self.priority.pop_front() {
+            self.tokens = self.tokens.saturating_sub(1);
+            return Some(message);
+        }
+
+        if self.tokens > 0 {
+            let message = self.messages.pop_front()?;
+            self.tokens -= 1;
+            return Some(message);
+        }
+
+        None
+    }
+
+    /// When the next queued message can be sent, if any are queued
+    fn next_send(&self) -> Option<Instant> {
+        (!self.messages.is_empty()).then_some(self.last_refill + self.rate)
+    }
+
+    async fn flush(
+        &mut self,
+        server: &Server,
+        connection: &mut Connection<irc::Codec>,
+        sender: &mut mpsc::Sender<Update>,
+    ) {
+        while let Some(message) = self.pop(Instant::now()) {
+            let _ = connection.send(message).await;
+        }
+
+        let queued = self.len();
+
+        if queued != self.reported {
+            log::debug!("[{server}] {queued} messages queued");
+
+            self.reported = queued;
+            let _ = sender.send(Update::Queued(server.clone(), queued)).await;
+        }
+    }
+}
+
+fn is_priority(message: &proto::Message) -> bool {
+    matches!(
+        message.command,
+        proto::Command::PING(_)
+            | proto::Command::PONG(_, _)
+            | proto::Command::CAP(_, _, _, _)
+            | proto::Command::AUTHENTICATE(_)
+            | proto::Command::PASS(_)
+            | proto::Command::NICK(_)
+            | proto::Command::USER(_, _)
+            | proto::Command::QUIT(_)
+    )
+}
+
+/// Delay between reconnection attempts, doubled after each failed attempt
+/// up to `max`, w/ jitter so clients don't reconnect in lockstep
+struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempts: u32,
+}
+
+impl Backoff {
+    fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max: max.max(base),
+            attempts: 0,
+        }
+    }
+
+    /// Delay before the next attempt, between half & all of the backed off
+    /// delay
+    fn next_delay(&mut self) -> Duration {
+        let delay = self
+            .base
+            .checked_mul(2_u32.saturating_pow(self.attempts))
+            .map_or(self.max, |delay| delay.min(self.max));
+
+        self.attempts = self.attempts.saturating_add(1);
+
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    fn from_config(config: &config::Server) -> Self {
+        Self::new(
+            Duration::from_secs(config.reconnect_delay),
+            Duration::from_secs(config.reconnect_delay_max),
+        )
+    }
+
+    /// Uses the delays of `config`, keeping the attempts made so far
+    fn configure(&mut self, config: &config::Server) {
+        *self = Self {
+            attempts: self.attempts,
+            ..Self::from_config(config)
+        };
+    }
+
+    fn next_retry(&mut self) -> Instant {
+        Instant::now() + self.next_delay()
+    }
+
+    /// Called once registered, so the next disconnect retries after `base`
+    fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// Whether the `ERROR` sent by the server when closing the connection is
+/// due to a ban, so reconnecting is pointless
+fn is_ban(error: &str) -> bool {
+    let error = error.to_lowercase();
+
+    ["k-lined", "g-lined", "z-lined", "d-lined", "banned"]
+        .iter()
+        .any(|ban| error.contains(ban))
+}
+
+/// Time since the ping w/ `token` was sent, if sent by [`run`]
+fn round_trip(token: &str, now: Posix) -> Option<Duration> {
+    let sent = token.parse::<u64>().ok()?;
+
+    now.as_nanos().checked_sub(sent).map(Duration::from_nanos)
+}
+
 fn ping_time_interval(secs: u64) -> Interval {
     time::interval_at(
         Instant::now() + Duration::from_secs(secs),
@@ -323,3 +748,93 @@ fn ping_timeout_interval(secs: u64) -> Interval {
         Duration::from_secs(secs),
     )
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use irc::proto::command;
+
+    use super::{is_ban, round_trip, Backoff, Queue};
+    use crate::time::Posix;
+
+    #[test]
+    fn queue_paces_messages() {
+        let rate = Duration::from_secs(1);
+        let mut queue = Queue::new(2, rate);
+        let start = queue.last_refill;
+
+        for i in 0..4 {
+            queue.push(command!("PRIVMSG", "#channel", i.to_string()));
+        }
+
+        // Burst is sent immediately
+        assert!(queue.pop(start).is_some());
+        assert!(queue.pop(start).is_some());
+        assert!(queue.pop(start).is_none());
+        assert_eq!(queue.len(), 2);
+
+        // Priority messages skip the queue
+        queue.push(command!("PONG", "token"));
+        assert_eq!(
+            queue.pop(start).unwrap().command,
+            command!("PONG", "token").command
+        );
+
+        // Then one message per `rate`
+        assert_eq!(queue.next_send(), Some(start + rate));
+        assert!(queue.pop(start + rate).is_some());
+        assert!(queue.pop(start + rate).is_none());
+        assert!(queue.pop(start + rate * 2).is_some());
+        assert_eq!(queue.len(), 0);
+        assert_eq!(queue.next_send(), None);
+
+        // Tokens refill up to the burst
+        let later = start + rate * 10;
+        for i in 0..3 {
+            queue.push(command!("PRIVMSG", "#channel", i.to_string()));
+        }
+        assert!(queue.pop(later).is_some());
+        assert!(queue.pop(later).is_some());
+        assert!(queue.pop(later).is_none());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let base = Duration::from_secs(10);
+        let max = Duration::from_secs(60);
+        let mut backoff = Backoff::new(base, max);
+
+        for expected in [10, 20, 40, 60, 60] {
+            let delay = backoff.next_delay();
+            let expected = Duration::from_secs(expected);
+
+            assert!(delay >= expected / 2 && delay <= expected);
+        }
+
+        backoff.reset();
+        assert!(backoff.next_delay() <= base);
+    }
+
+    #[test]
+    fn bans() {
+        assert!(is_ban("Closing Link: 127.0.0.1 (K-Lined)"));
+        assert!(is_ban("You are banned from this server- flooding"));
+        assert!(!is_ban(
+            "Closing Link: 127.0.0.1 (Ping timeout: 240 seconds)"
+        ));
+    }
+
+    #[test]
+    fn lag_from_pong_token() {
+        let sent = Posix::from_seconds(1).as_nanos().to_string();
+
+        assert_eq!(
+            round_trip(&sent, Posix::from_seconds(3)),
+            Some(Duration::from_secs(2))
+        );
+        // Pongs to pings sent by the user
+        assert_eq!(round_trip("LAG", Posix::from_seconds(3)), None);
+        assert_eq!(round_trip(&sent, Posix::from_seconds(0)), None);
+    }
+}
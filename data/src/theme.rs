@@ -4,6 +4,8 @@ use palette::{DarkenAssign, FromColor, LightenAssign, Mix, Okhsl, Srgb};
 use rand::prelude::*;
 use rand_chacha::ChaChaRng;
 
+use crate::formatting;
+
 const DEFAULT_THEME_NAME: &str = "Ferra";
 
 #[derive(Debug, Clone)]
@@ -181,6 +183,44 @@ pub fn randomize_color(original_color: Color, seed: &str) -> Color {
     from_hsl(randomized_hsl)
 }
 
+/// Resolves the text & background colors of formatted IRC text. A
+/// text color w/o a background is clamped in lightness so it stays
+/// readable against the theme background.
+pub fn formatting_colors(
+    formatting: &formatting::Formatting,
+    colors: &Colors,
+) -> (Option<Color>, Option<Color>) {
+    let to_color = |color: formatting::Color| {
+        let (r, g, b) = color.rgb();
+        Color::from_rgb8(r, g, b)
+    };
+
+    let background = formatting.background.map(to_color);
+    let foreground = formatting.foreground.map(to_color).map(|color| {
+        if background.is_some() {
+            return color;
+        }
+
+        let mut hsl = to_hsl(color);
+        hsl.lightness = if colors.is_dark_theme() {
+            hsl.lightness.max(0.45)
+        } else {
+            hsl.lightness.min(0.55)
+        };
+
+        from_hsl(hsl)
+    });
+
+    if formatting.reverse {
+        (
+            Some(background.unwrap_or(colors.background.base)),
+            Some(foreground.unwrap_or(colors.text.base)),
+        )
+    } else {
+        (foreground, background)
+    }
+}
+
 pub fn is_dark(color: Color) -> bool {
     to_hsl(color).lightness < 0.5
 }
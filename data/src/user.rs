@@ -5,6 +5,7 @@ use std::hash::Hash;
 use irc::proto;
 use serde::{Deserialize, Serialize};
 
+use crate::isupport::{self, CaseMap, PrefixMap};
 use crate::{buffer, config::buffer::UsernameFormat, mode};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,38 +60,7 @@ impl<'a> TryFrom<&'a str> for User {
     type Error = &'static str;
 
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
-        if value.is_empty() {
-            return Err("nickname can't be empty");
-        }
-
-        let access_levels = value
-            .chars()
-            .map(|c| AccessLevel::try_from(c).ok())
-            .take_while(Option::is_some)
-            .flatten()
-            .collect::<HashSet<_>>();
-
-        // Safe as access levels are just ASCII
-        let rest = &value[access_levels.len()..];
-
-        let (nickname, username, hostname) = match (rest.find('!'), rest.find('@')) {
-            (None, None) => (rest, None, None),
-            (Some(i), None) => (&rest[..i], Some(rest[i + 1..].to_string()), None),
-            (None, Some(i)) => (&rest[..i], None, Some(rest[i + 1..].to_string())),
-            (Some(i), Some(j)) => (
-                &rest[..i],
-                Some(rest[i + 1..j].to_string()),
-                Some(rest[j + 1..].to_string()),
-            ),
-        };
-
-        Ok(User {
-            nickname: Nick::from(nickname),
-            username,
-            hostname,
-            access_levels,
-            away: false,
-        })
+        Self::parse(value, isupport::DEFAULT_PREFIX, CaseMap::default())
     }
 }
 
@@ -120,6 +90,44 @@ impl From<Nick> for User {
 }
 
 impl User {
+    /// Parse a user from `[prefixes]nick[!user][@host]`, where the leading
+    /// access level prefixes are those advertised by the server in `PREFIX`
+    pub fn parse(
+        value: &str,
+        prefix: &[PrefixMap],
+        casemapping: CaseMap,
+    ) -> Result<Self, &'static str> {
+        if value.is_empty() {
+            return Err("nickname can't be empty");
+        }
+
+        let rest = value.trim_start_matches(|c| prefix.iter().any(|p| p.prefix == c));
+        let access_levels = access_levels(&value[..value.len() - rest.len()], prefix);
+
+        if rest.is_empty() {
+            return Err("nickname can't be empty");
+        }
+
+        let (nickname, username, hostname) = match (rest.find('!'), rest.find('@')) {
+            (None, None) => (rest, None, None),
+            (Some(i), None) => (&rest[..i], Some(rest[i + 1..].to_string()), None),
+            (None, Some(i)) => (&rest[..i], None, Some(rest[i + 1..].to_string())),
+            (Some(i), Some(j)) => (
+                &rest[..i],
+                Some(rest[i + 1..j].to_string()),
+                Some(rest[j + 1..].to_string()),
+            ),
+        };
+
+        Ok(User {
+            nickname: Nick::new(nickname, casemapping),
+            username,
+            hostname,
+            access_levels,
+            away: false,
+        })
+    }
+
     pub fn color_seed(&self, color: &buffer::Color) -> Option<String> {
         match color {
             buffer::Color::Solid => None,
@@ -136,7 +144,7 @@ impl User {
     }
 
     pub fn nickname(&self) -> NickRef {
-        NickRef(&self.nickname.0)
+        self.nickname.as_nickref()
     }
 
     pub fn hostname(&self) -> Option<&str> {
@@ -147,6 +155,21 @@ impl User {
         Self { nickname, ..self }
     }
 
+    pub fn with_casemapping(self, casemapping: CaseMap) -> Self {
+        Self {
+            nickname: self.nickname.with_casemapping(casemapping),
+            ..self
+        }
+    }
+
+    pub fn with_hostmask(self, username: Option<String>, hostname: Option<String>) -> Self {
+        Self {
+            username,
+            hostname,
+            ..self
+        }
+    }
+
     pub fn highest_access_level(&self) -> AccessLevel {
         self.access_levels
             .iter()
@@ -172,6 +195,12 @@ impl User {
         }
     }
 
+    /// Replace access levels with those in `prefixes`, such as the flags of a
+    /// `RPL_WHOREPLY`. Characters that aren't in `PREFIX` are ignored.
+    pub fn set_access_levels(&mut self, prefixes: &str, prefix: &[PrefixMap]) {
+        self.access_levels = access_levels(prefixes, prefix);
+    }
+
     pub fn update_away(&mut self, away: bool) {
         self.away = away;
     }
@@ -211,45 +240,105 @@ impl fmt::Display for User {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
-pub struct Nick(String);
+/// A nickname as sent by the server, compared using the server's casemapping
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "String")]
+#[serde(from = "String")]
+pub struct Nick {
+    raw: String,
+    normalized: String,
+}
+
+impl Nick {
+    pub fn new(nick: impl Into<String>, casemapping: CaseMap) -> Self {
+        let raw = nick.into();
+        let normalized = casemapping.normalize(&raw);
+
+        Nick { raw, normalized }
+    }
+
+    /// Re-normalize using `casemapping`, for when the server advertises a
+    /// casemapping after the nickname was created
+    pub fn with_casemapping(self, casemapping: CaseMap) -> Self {
+        Nick::new(self.raw, casemapping)
+    }
+
+    pub fn as_nickref(&self) -> NickRef {
+        NickRef(self)
+    }
+
+    /// The casemapped form of the nickname, used for identity
+    pub fn normalized(&self) -> &str {
+        &self.normalized
+    }
+}
+
+impl PartialEq for Nick {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized.eq(&other.normalized)
+    }
+}
+
+impl Eq for Nick {}
+
+impl Hash for Nick {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.normalized.hash(state);
+    }
+}
+
+impl Ord for Nick {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.normalized.cmp(&other.normalized)
+    }
+}
+
+impl PartialOrd for Nick {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
 impl fmt::Display for Nick {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
+        self.raw.fmt(f)
     }
 }
 
 impl AsRef<str> for Nick {
     fn as_ref(&self) -> &str {
-        self.0.as_ref()
+        self.raw.as_ref()
     }
 }
 
 impl From<String> for Nick {
     fn from(nick: String) -> Self {
-        Nick(nick)
+        Nick::new(nick, CaseMap::default())
     }
 }
 
 impl<'a> From<&'a str> for Nick {
     fn from(nick: &'a str) -> Self {
-        Nick(nick.to_string())
+        Nick::new(nick, CaseMap::default())
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct NickRef<'a>(&'a str);
-
-impl<'a> From<&'a str> for NickRef<'a> {
-    fn from(nick: &'a str) -> Self {
-        NickRef(nick)
+impl From<Nick> for String {
+    fn from(nick: Nick) -> Self {
+        nick.raw
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct NickRef<'a>(&'a Nick);
+
 impl<'a> NickRef<'a> {
     pub fn to_owned(self) -> Nick {
-        Nick(self.0.to_string())
+        self.0.clone()
+    }
+
+    pub fn normalized(&self) -> &'a str {
+        self.0.normalized()
     }
 }
 
@@ -261,28 +350,46 @@ impl<'a> fmt::Display for NickRef<'a> {
 
 impl<'a> AsRef<str> for NickRef<'a> {
     fn as_ref(&self) -> &str {
-        self.0
+        self.0.as_ref()
     }
 }
 
+impl<'a> PartialEq for NickRef<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq(other.0)
+    }
+}
+
+impl<'a> Eq for NickRef<'a> {}
+
 impl<'a> PartialOrd for NickRef<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.0.to_lowercase().cmp(&other.0.to_lowercase()))
+        Some(self.cmp(other))
     }
 }
 
 impl<'a> Ord for NickRef<'a> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.to_lowercase().cmp(&other.0.to_lowercase())
+        self.0.cmp(other.0)
     }
 }
 
 impl<'a> PartialEq<Nick> for NickRef<'a> {
     fn eq(&self, other: &Nick) -> bool {
-        self.0.eq(other.0.as_str())
+        self.0.eq(other)
     }
 }
 
+/// Map prefix symbols to access levels through their channel mode, so
+/// servers using non-standard symbols are still understood
+fn access_levels(prefixes: &str, prefix: &[PrefixMap]) -> HashSet<AccessLevel> {
+    prefixes
+        .chars()
+        .filter_map(|c| prefix.iter().find(|p| p.prefix == c))
+        .filter_map(|p| AccessLevel::try_from(mode::Channel::from(p.mode)).ok())
+        .collect()
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum AccessLevel {
     Member,
@@ -0,0 +1,312 @@
+//! mIRC text formatting, see <https://modern.ircdocs.horse/formatting>
+
+pub const BOLD: char = '\x02';
+pub const ITALIC: char = '\x1D';
+pub const UNDERLINE: char = '\x1F';
+pub const STRIKETHROUGH: char = '\x1E';
+pub const MONOSPACE: char = '\x11';
+pub const COLOR: char = '\x03';
+pub const HEX_COLOR: char = '\x04';
+pub const REVERSE: char = '\x16';
+pub const RESET: char = '\x0F';
+
+/// Colors 0-15 are the classic mIRC palette, 16-98 the extended one.
+/// Code 99 is reserved for the default color.
+const PALETTE: [u32; 99] = [
+    0xffffff, 0x000000, 0x00007f, 0x009300, 0xff0000, 0x7f0000, 0x9c009c, 0xfc7f00, 0xffff00,
+    0x00fc00, 0x009393, 0x00ffff, 0x0000fc, 0xff00ff, 0x7f7f7f, 0xd2d2d2, 0x470000, 0x472100,
+    0x474700, 0x324700, 0x004700, 0x00472c, 0x004747, 0x002747, 0x000047, 0x2e0047, 0x470047,
+    0x47002a, 0x740000, 0x743a00, 0x747400, 0x517400, 0x007400, 0x007449, 0x007474, 0x004074,
+    0x000074, 0x4b0074, 0x740074, 0x740045, 0xb50000, 0xb56300, 0xb5b500, 0x7db500, 0x00b500,
+    0x00b571, 0x00b5b5, 0x0063b5, 0x0000b5, 0x7500b5, 0xb500b5, 0xb5006b, 0xff0000, 0xff8c00,
+    0xffff00, 0xb2ff00, 0x00ff00, 0x00ffa0, 0x00ffff, 0x008cff, 0x0000ff, 0xa500ff, 0xff00ff,
+    0xff0098, 0xff5959, 0xffb459, 0xffff71, 0xcfff60, 0x6fff6f, 0x65ffc9, 0x6dffff, 0x59b4ff,
+    0x5959ff, 0xc459ff, 0xff66ff, 0xff59bc, 0xff9c9c, 0xffd39c, 0xffff9c, 0xe2ff9c, 0x9cff9c,
+    0x9cffdb, 0x9cffff, 0x9cd3ff, 0x9c9cff, 0xdc9cff, 0xff9cff, 0xff94d3, 0x000000, 0x131313,
+    0x282828, 0x363636, 0x4d4d4d, 0x656565, 0x818181, 0x9f9f9f, 0xbcbcbc, 0xe2e2e2, 0xffffff,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// An index into the 99 color palette
+    Code(u8),
+    /// A color set w/ the hex color code
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn code(code: u8) -> Option<Self> {
+        (usize::from(code) < PALETTE.len()).then_some(Color::Code(code))
+    }
+
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Color::Code(code) => {
+                let [_, r, g, b] = PALETTE[usize::from(code)].to_be_bytes();
+                (r, g, b)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Formatting {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub monospace: bool,
+    pub reverse: bool,
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+/// A run of text sharing the same formatting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub formatting: Formatting,
+}
+
+/// Whether `text` contains any formatting control characters
+pub fn is_formatted(text: &str) -> bool {
+    text.contains([
+        BOLD,
+        ITALIC,
+        UNDERLINE,
+        STRIKETHROUGH,
+        MONOSPACE,
+        COLOR,
+        HEX_COLOR,
+        REVERSE,
+        RESET,
+    ])
+}
+
+/// Splits `text` into spans, consuming all formatting control characters
+pub fn parse(text: &str) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut current = String::new();
+    let mut formatting = Formatting::default();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let previous = formatting;
+
+        match c {
+            BOLD => formatting.bold = !formatting.bold,
+            ITALIC => formatting.italic = !formatting.italic,
+            UNDERLINE => formatting.underline = !formatting.underline,
+            STRIKETHROUGH => formatting.strikethrough = !formatting.strikethrough,
+            MONOSPACE => formatting.monospace = !formatting.monospace,
+            REVERSE => formatting.reverse = !formatting.reverse,
+            RESET => formatting = Formatting::default(),
+            COLOR => {
+                match digits(&mut chars) {
+                    Some(foreground) => {
+                        formatting.foreground = Color::code(foreground);
+
+                        if let Some(background) = after_comma(&mut chars, digits) {
+                            formatting.background = Color::code(background);
+                        }
+                    }
+                    // A lone color code resets both colors
+                    None => {
+                        formatting.foreground = None;
+                        formatting.background = None;
+                    }
+                }
+            }
+            HEX_COLOR => match hex(&mut chars) {
+                Some(foreground) => {
+                    formatting.foreground = Some(foreground);
+
+                    if let Some(background) = after_comma(&mut chars, hex) {
+                        formatting.background = Some(background);
+                    }
+                }
+                None => {
+                    formatting.foreground = None;
+                    formatting.background = None;
+                }
+            },
+            c => {
+                current.push(c);
+                continue;
+            }
+        }
+
+        if formatting != previous && !current.is_empty() {
+            spans.push(Span {
+                text: std::mem::take(&mut current),
+                formatting: previous,
+            });
+        }
+    }
+
+    if !current.is_empty() {
+        spans.push(Span {
+            text: current,
+            formatting,
+        });
+    }
+
+    spans
+}
+
+/// Removes all formatting control characters from `text`
+pub fn strip(text: &str) -> String {
+    if !is_formatted(text) {
+        return text.to_string();
+    }
+
+    parse(text).into_iter().map(|span| span.text).collect()
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+/// Parses up to 2 digits
+fn digits(chars: &mut Chars) -> Option<u8> {
+    let mut value = None;
+
+    for _ in 0..2 {
+        match chars.peek().and_then(|c| c.to_digit(10)) {
+            Some(digit) => {
+                chars.next();
+                value = Some(value.unwrap_or(0) * 10 + digit as u8);
+            }
+            None => break,
+        }
+    }
+
+    value
+}
+
+/// Parses exactly 6 hex digits
+fn hex(chars: &mut Chars) -> Option<Color> {
+    let hex = chars.clone().take(6).collect::<String>();
+
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let value = u32::from_str_radix(&hex, 16).ok()?;
+    chars.nth(5);
+
+    let [_, r, g, b] = value.to_be_bytes();
+    Some(Color::Rgb(r, g, b))
+}
+
+/// Parses `,` followed by a value, leaving the comma alone if no
+/// value follows it
+fn after_comma<T>(chars: &mut Chars, parse: impl Fn(&mut Chars) -> Option<T>) -> Option<T> {
+    if chars.peek() != Some(&',') {
+        return None;
+    }
+
+    let mut lookahead = chars.clone();
+    lookahead.next();
+
+    let value = parse(&mut lookahead)?;
+    *chars = lookahead;
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn span(text: &str, formatting: Formatting) -> Span {
+        Span {
+            text: text.to_string(),
+            formatting,
+        }
+    }
+
+    #[test]
+    fn parse_spans() {
+        let bold = Formatting {
+            bold: true,
+            ..Default::default()
+        };
+        let red_on_blue = Formatting {
+            foreground: Some(Color::Code(4)),
+            background: Some(Color::Code(2)),
+            ..Default::default()
+        };
+
+        let tests = [
+            (
+                "plain text",
+                vec![span("plain text", Formatting::default())],
+            ),
+            (
+                "a \x02bold\x02 word",
+                vec![
+                    span("a ", Formatting::default()),
+                    span("bold", bold),
+                    span(" word", Formatting::default()),
+                ],
+            ),
+            (
+                "\x034,02red\x03 done",
+                vec![
+                    span("red", red_on_blue),
+                    span(" done", Formatting::default()),
+                ],
+            ),
+            (
+                "\x034,02red\x0f\x03,5",
+                vec![span("red", red_on_blue), span(",5", Formatting::default())],
+            ),
+            (
+                "\x0399,99x\x0310,",
+                vec![
+                    span("x", Formatting::default()),
+                    span(
+                        ",",
+                        Formatting {
+                            foreground: Some(Color::Code(10)),
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            ),
+            (
+                "\x04FF8000hex\x04 \x1d\x1fi\x1e\x11",
+                vec![
+                    span(
+                        "hex",
+                        Formatting {
+                            foreground: Some(Color::Rgb(0xff, 0x80, 0x00)),
+                            ..Default::default()
+                        },
+                    ),
+                    span(" ", Formatting::default()),
+                    span(
+                        "i",
+                        Formatting {
+                            italic: true,
+                            underline: true,
+                            ..Default::default()
+                        },
+                    ),
+                ],
+            ),
+            ("\x02\x02", vec![]),
+        ];
+
+        for (text, expected) in tests {
+            assert_eq!(parse(text), expected, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn strip_formatting() {
+        assert_eq!(strip("\x02\x0312,4hi\x0f there\x16!"), "hi there!");
+        assert_eq!(strip("no formatting"), "no formatting");
+        assert_eq!(Color::Code(52).rgb(), (0xff, 0x00, 0x00));
+    }
+}
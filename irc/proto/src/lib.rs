@@ -2,6 +2,7 @@ pub use self::command::Command;
 
 pub mod command;
 pub mod format;
+pub mod formatting;
 pub mod parse;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -48,11 +49,9 @@ pub fn command(command: &str, parameters: Vec<String>) -> Message {
     }
 }
 
-pub fn is_channel(target: &str) -> bool {
-    target.starts_with('#')
-        || target.starts_with('&')
-        || target.starts_with('+')
-        || target.starts_with('!')
+/// Whether `target` starts with one of the channel prefixes in `chantypes`
+pub fn is_channel(target: &str, chantypes: &[char]) -> bool {
+    target.starts_with(chantypes)
 }
 
 #[macro_export]
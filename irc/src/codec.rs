@@ -1,12 +1,26 @@
+use std::fmt;
 use std::io;
 
 use bytes::BytesMut;
+use encoding_rs::EncoderResult;
 use proto::{format, parse, Message};
 use tokio_util::codec::{Decoder, Encoder};
 
 pub type ParseResult<T = Message, E = parse::Error> = std::result::Result<T, E>;
 
-pub struct Codec;
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Codec {
+    /// Encoding used to decode incoming lines which aren't valid UTF-8
+    fallback: Encoding,
+    /// Encoding used for outgoing lines
+    outgoing: Encoding,
+}
+
+impl Codec {
+    pub fn new(fallback: Encoding, outgoing: Encoding) -> Self {
+        Self { fallback, outgoing }
+    }
+}
 
 impl Decoder for Codec {
     type Item = ParseResult;
@@ -19,7 +33,13 @@ impl Decoder for Codec {
 
         let bytes = Vec::from(src.split_to(pos + 2));
 
-        Ok(Some(parse::message_bytes(bytes)))
+        Ok(Some(match parse::message_bytes(bytes) {
+            // Line isn't valid UTF-8, decode it w/ the fallback encoding instead
+            Err(parse::Error::InvalidUtf8(error)) => {
+                parse::message(&self.fallback.decode(error.as_bytes()))
+            }
+            result => result,
+        }))
     }
 }
 
@@ -29,14 +49,125 @@ impl Encoder<Message> for Codec {
     fn encode(&mut self, message: Message, dst: &mut BytesMut) -> Result<(), Self::Error> {
         let encoded = format::message(message);
 
-        dst.extend(encoded.into_bytes());
+        self.outgoing.encode(&encoded, dst);
 
         Ok(())
     }
 }
 
+/// A text encoding compatible w/ the IRC protocol, which requires
+/// ASCII bytes (such as spaces and CRLF) to keep their meaning.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Encoding(&'static encoding_rs::Encoding);
+
+impl Encoding {
+    pub const UTF_8: Self = Self(encoding_rs::UTF_8);
+    pub const WINDOWS_1252: Self = Self(encoding_rs::WINDOWS_1252);
+
+    /// Looks up an encoding by its WHATWG label, such as `latin1`,
+    /// `iso-8859-15` or `windows-1251`.
+    pub fn for_label(label: &str) -> Option<Self> {
+        encoding_rs::Encoding::for_label(label.trim().as_bytes())
+            .filter(|encoding| encoding.is_ascii_compatible())
+            .map(Self)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// Decodes `bytes`, replacing malformed sequences w/ U+FFFD
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        self.0.decode_without_bom_handling(bytes).0.into_owned()
+    }
+
+    /// Encodes `text` into `dst`, replacing unmappable characters w/ `?`
+    pub fn encode(&self, text: &str, dst: &mut BytesMut) {
+        if *self == Self::UTF_8 {
+            dst.extend_from_slice(text.as_bytes());
+            return;
+        }
+
+        let mut encoder = self.0.new_encoder();
+        let mut buffer = [0; 1024];
+        let mut remaining = text;
+
+        loop {
+            let (result, read, written) =
+                encoder.encode_from_utf8_without_replacement(remaining, &mut buffer, true);
+
+            dst.extend_from_slice(&buffer[..written]);
+            remaining = &remaining[read..];
+
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(_) => dst.extend_from_slice(b"?"),
+            }
+        }
+    }
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self::UTF_8
+    }
+}
+
+impl fmt::Debug for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Encoding").field(&self.name()).finish()
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
 }
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use super::{Codec, Encoding};
+
+    #[test]
+    fn decode_fallback() {
+        let mut codec = Codec::new(Encoding::WINDOWS_1252, Encoding::UTF_8);
+
+        let mut src = BytesMut::from(&b":dan!d@localhost PRIVMSG #test :caf\xe9 \x80\r\n"[..]);
+        let message = codec.decode(&mut src).unwrap().unwrap().unwrap();
+        assert_eq!(
+            message.command,
+            proto::Command::PRIVMSG("#test".into(), "café €".into())
+        );
+
+        let mut src = BytesMut::from(":dan!d@localhost PRIVMSG #test :café\r\n");
+        let message = codec.decode(&mut src).unwrap().unwrap().unwrap();
+        assert_eq!(
+            message.command,
+            proto::Command::PRIVMSG("#test".into(), "café".into())
+        );
+    }
+
+    #[test]
+    fn encode_outgoing() {
+        let mut codec = Codec::new(Encoding::UTF_8, Encoding::for_label("latin1").unwrap());
+
+        let mut dst = BytesMut::new();
+        codec
+            .encode(proto::command!("PRIVMSG", "#test", "café ☕"), &mut dst)
+            .unwrap();
+        assert_eq!(&dst[..], b"PRIVMSG #test :caf\xe9 ?\r\n");
+    }
+
+    #[test]
+    fn for_label() {
+        assert_eq!(Encoding::for_label("UTF-8"), Some(Encoding::UTF_8));
+        assert_eq!(Encoding::for_label("cp1252"), Some(Encoding::WINDOWS_1252));
+        assert_eq!(Encoding::for_label("utf-16le"), None);
+        assert_eq!(Encoding::for_label("nope"), None);
+    }
+}
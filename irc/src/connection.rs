@@ -8,6 +8,9 @@ use tokio_rustls::client::TlsStream;
 use tokio_util::codec;
 use tokio_util::codec::Framed;
 
+pub use self::proxy::Proxy;
+
+mod proxy;
 mod tls;
 
 pub enum Connection<Codec> {
@@ -31,11 +34,15 @@ pub struct Config<'a> {
     pub server: &'a str,
     pub port: u16,
     pub security: Security<'a>,
+    pub proxy: Option<Proxy<'a>>,
 }
 
 impl<Codec> Connection<Codec> {
     pub async fn new(config: Config<'_>, codec: Codec) -> Result<Self, Error> {
-        let tcp = TcpStream::connect((config.server, config.port)).await?;
+        let tcp = match &config.proxy {
+            Some(proxy) => proxy::connect(proxy, config.server, config.port).await?,
+            None => TcpStream::connect((config.server, config.port)).await?,
+        };
 
         if let Security::Secured {
             accept_invalid_certs,
@@ -97,6 +104,8 @@ impl<Codec> Connection<Codec> {
 pub enum Error {
     #[error("tls error: {0}")]
     Tls(#[from] tls::Error),
+    #[error(transparent)]
+    Proxy(#[from] proxy::Error),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -0,0 +1,305 @@
+use std::net::IpAddr;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::Engine;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Largest response accepted from an HTTP proxy before the tunnel is open
+const MAX_HTTP_RESPONSE: usize = 8 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum Proxy<'a> {
+    /// SOCKS5 proxy, which resolves the server's hostname itself
+    Socks5 {
+        host: &'a str,
+        port: u16,
+        username: Option<&'a str>,
+        password: Option<&'a str>,
+    },
+    /// HTTP proxy supporting the CONNECT method
+    Http {
+        host: &'a str,
+        port: u16,
+        username: Option<&'a str>,
+        password: Option<&'a str>,
+    },
+}
+
+/// Connects to `server` through `proxy`, returning the stream once the
+/// tunnel is open
+pub async fn connect(proxy: &Proxy<'_>, server: &str, port: u16) -> Result<TcpStream, Error> {
+    match *proxy {
+        Proxy::Socks5 {
+            host,
+            port: proxy_port,
+            username,
+            password,
+        } => {
+            let mut stream = TcpStream::connect((host, proxy_port)).await?;
+            socks5(&mut stream, server, port, username, password).await?;
+            Ok(stream)
+        }
+        Proxy::Http {
+            host,
+            port: proxy_port,
+            username,
+            password,
+        } => {
+            let mut stream = TcpStream::connect((host, proxy_port)).await?;
+            http(&mut stream, server, port, username, password).await?;
+            Ok(stream)
+        }
+    }
+}
+
+mod socks5 {
+    pub const VERSION: u8 = 0x05;
+    pub const NO_AUTHENTICATION: u8 = 0x00;
+    pub const USERNAME_PASSWORD: u8 = 0x02;
+    pub const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+    pub const USERNAME_PASSWORD_VERSION: u8 = 0x01;
+    pub const CONNECT: u8 = 0x01;
+    pub const IPV4: u8 = 0x01;
+    pub const DOMAIN_NAME: u8 = 0x03;
+    pub const IPV6: u8 = 0x04;
+}
+
+/// Opens a tunnel as described in RFC 1928, w/ the username & password
+/// authentication of RFC 1929
+async fn socks5(
+    stream: &mut TcpStream,
+    server: &str,
+    port: u16,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> Result<(), Error> {
+    let credentials = username.map(|username| (username, password.unwrap_or_default()));
+
+    let methods: &[u8] = if credentials.is_some() {
+        &[socks5::NO_AUTHENTICATION, socks5::USERNAME_PASSWORD]
+    } else {
+        &[socks5::NO_AUTHENTICATION]
+    };
+
+    let mut greeting = vec![socks5::VERSION, methods.len() as u8];
+    greeting.extend_from_slice(methods);
+    stream.write_all(&greeting).await?;
+
+    let mut choice = [0; 2];
+    stream.read_exact(&mut choice).await?;
+
+    if choice[0] != socks5::VERSION {
+        return Err(Error::Socks5("invalid response from proxy".into()));
+    }
+
+    match (choice[1], credentials) {
+        (socks5::NO_AUTHENTICATION, _) => {}
+        (socks5::USERNAME_PASSWORD, Some((username, password))) => {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(Error::Socks5(
+                    "username and password must be at most 255 bytes".into(),
+                ));
+            }
+
+            let mut request = vec![socks5::USERNAME_PASSWORD_VERSION, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await?;
+
+            let mut status = [0; 2];
+            stream.read_exact(&mut status).await?;
+
+            if status[1] != 0x00 {
+                return Err(Error::Socks5("authentication failed".into()));
+            }
+        }
+        (socks5::NO_ACCEPTABLE_METHODS, _) => {
+            return Err(Error::Socks5(
+                "proxy requires an unsupported authentication method".into(),
+            ));
+        }
+        (method, _) => {
+            return Err(Error::Socks5(format!(
+                "proxy selected an unexpected authentication method ({method})"
+            )));
+        }
+    }
+
+    stream
+        .write_all(&socks5_connect_request(server, port)?)
+        .await?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await?;
+
+    if reply[1] != 0x00 {
+        return Err(Error::Socks5(socks5_reply_error(reply[1]).into()));
+    }
+
+    // Skip the address the proxy bound to
+    let address_len = match reply[3] {
+        socks5::IPV4 => 4,
+        socks5::IPV6 => 16,
+        socks5::DOMAIN_NAME => stream.read_u8().await? as usize,
+        _ => return Err(Error::Socks5("invalid response from proxy".into())),
+    };
+    let mut bound = vec![0; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(())
+}
+
+fn socks5_connect_request(server: &str, port: u16) -> Result<Vec<u8>, Error> {
+    let mut request = vec![socks5::VERSION, socks5::CONNECT, 0x00];
+
+    match server.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(socks5::IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(socks5::IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        // Resolved by the proxy
+        Err(_) => {
+            if server.len() > 255 {
+                return Err(Error::Socks5("server name is too long".into()));
+            }
+
+            request.push(socks5::DOMAIN_NAME);
+            request.push(server.len() as u8);
+            request.extend_from_slice(server.as_bytes());
+        }
+    }
+
+    request.extend_from_slice(&port.to_be_bytes());
+
+    Ok(request)
+}
+
+fn socks5_reply_error(reply: u8) -> &'static str {
+    match reply {
+        0x01 => "general proxy server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+/// Opens a tunnel w/ the CONNECT method of RFC 9110
+async fn http(
+    stream: &mut TcpStream,
+    server: &str,
+    port: u16,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> Result<(), Error> {
+    stream
+        .write_all(http_connect_request(server, port, username, password).as_bytes())
+        .await?;
+
+    // Read a byte at a time so nothing sent after the response is consumed
+    let mut response = Vec::new();
+
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_HTTP_RESPONSE {
+            return Err(Error::Http("response from proxy is too long".into()));
+        }
+
+        response.push(stream.read_u8().await?);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or_default();
+    let status = parts.next().unwrap_or_default();
+
+    if !version.starts_with("HTTP/") {
+        return Err(Error::Http("invalid response from proxy".into()));
+    }
+
+    if !status.starts_with('2') {
+        return Err(Error::Http(format!(
+            "proxy refused to connect ({})",
+            status_line.trim_start_matches(version).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+fn http_connect_request(
+    server: &str,
+    port: u16,
+    username: Option<&str>,
+    password: Option<&str>,
+) -> String {
+    let authority = if server.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{server}]:{port}")
+    } else {
+        format!("{server}:{port}")
+    };
+
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+
+    if let Some(username) = username {
+        let credentials = BASE64.encode(format!("{username}:{}", password.unwrap_or_default()));
+        request.push_str(&format!("Proxy-Authorization: Basic {credentials}\r\n"));
+    }
+
+    request.push_str("\r\n");
+
+    request
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("SOCKS5 proxy error: {0}")]
+    Socks5(String),
+    #[error("HTTP proxy error: {0}")]
+    Http(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn connect_requests() {
+        assert_eq!(
+            socks5_connect_request("irc.libera.chat", 6697).unwrap(),
+            [
+                &[0x05, 0x01, 0x00, 0x03, 15][..],
+                b"irc.libera.chat",
+                &[0x1a, 0x29]
+            ]
+            .concat()
+        );
+        assert_eq!(
+            socks5_connect_request("127.0.0.1", 6667).unwrap(),
+            [0x05, 0x01, 0x00, 0x01, 127, 0, 0, 1, 0x1a, 0x0b]
+        );
+
+        assert_eq!(
+            http_connect_request("::1", 6697, None, None),
+            "CONNECT [::1]:6697 HTTP/1.1\r\nHost: [::1]:6697\r\n\r\n"
+        );
+        assert_eq!(
+            http_connect_request("irc.libera.chat", 6697, Some("user"), Some("pass")),
+            "CONNECT irc.libera.chat:6697 HTTP/1.1\r\nHost: irc.libera.chat:6697\r\n\
+             Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+        );
+    }
+}
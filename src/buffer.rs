@@ -1,4 +1,6 @@
+use chrono::{DateTime, Utc};
 pub use data::buffer::Settings;
+use data::formatting::Modifier;
 use data::user::Nick;
 use data::{buffer, file_transfer, history, Config};
 use iced::Command;
@@ -6,7 +8,9 @@ use iced::Command;
 use self::channel::Channel;
 use self::file_transfers::FileTransfers;
 use self::query::Query;
+use self::search::Search;
 use self::server::Server;
+use self::server_editor::ServerEditor;
 use crate::widget::Element;
 
 pub mod channel;
@@ -15,7 +19,9 @@ pub mod file_transfers;
 mod input_view;
 pub mod query;
 mod scroll_view;
+pub mod search;
 pub mod server;
+pub mod server_editor;
 pub mod user_context;
 
 #[derive(Clone)]
@@ -25,6 +31,8 @@ pub enum Buffer {
     Server(Server),
     Query(Query),
     FileTransfers(FileTransfers),
+    Search(Search),
+    ServerEditor(ServerEditor),
 }
 
 #[derive(Debug, Clone)]
@@ -33,11 +41,21 @@ pub enum Message {
     Server(server::Message),
     Query(query::Message),
     FileTransfers(file_transfers::Message),
+    Search(search::Message),
+    ServerEditor(server_editor::Message),
 }
 
 #[derive(Debug, Clone)]
 pub enum Event {
     UserContext(user_context::Event),
+    OpenChannel(String),
+    LoadOlder,
+    OpenMessage(data::Buffer, DateTime<Utc>),
+    ServerSaved {
+        server: data::Server,
+        config: Box<data::config::Server>,
+        is_new: bool,
+    },
 }
 
 impl Buffer {
@@ -51,7 +69,7 @@ impl Buffer {
             Buffer::Channel(state) => Some(state.buffer()),
             Buffer::Server(state) => Some(state.buffer()),
             Buffer::Query(state) => Some(state.buffer()),
-            Buffer::FileTransfers(_) => None,
+            Buffer::FileTransfers(_) | Buffer::Search(_) | Buffer::ServerEditor(_) => None,
         }
     }
 
@@ -69,20 +87,29 @@ impl Buffer {
 
                 let event = event.map(|event| match event {
                     channel::Event::UserContext(event) => Event::UserContext(event),
+                    channel::Event::OpenChannel(channel) => Event::OpenChannel(channel),
+                    channel::Event::LoadOlder => Event::LoadOlder,
                 });
 
                 (command.map(Message::Channel), event)
             }
             (Buffer::Server(state), Message::Server(message)) => {
-                let command = state.update(message, clients, history);
+                let (command, event) = state.update(message, clients, history);
+
+                let event = event.map(|event| match event {
+                    server::Event::OpenChannel(channel) => Event::OpenChannel(channel),
+                    server::Event::LoadOlder => Event::LoadOlder,
+                });
 
-                (command.map(Message::Server), None)
+                (command.map(Message::Server), event)
             }
             (Buffer::Query(state), Message::Query(message)) => {
                 let (command, event) = state.update(message, clients, history);
 
                 let event = event.map(|event| match event {
                     query::Event::UserContext(event) => Event::UserContext(event),
+                    query::Event::OpenChannel(channel) => Event::OpenChannel(channel),
+                    query::Event::LoadOlder => Event::LoadOlder,
                 });
 
                 (command.map(Message::Query), event)
@@ -92,6 +119,34 @@ impl Buffer {
 
                 (command.map(Message::FileTransfers), None)
             }
+            (Buffer::Search(state), Message::Search(message)) => {
+                let (command, event) = state.update(message, config);
+
+                let event = event.map(|event| match event {
+                    search::Event::OpenMessage(buffer, server_time) => {
+                        Event::OpenMessage(buffer, server_time)
+                    }
+                });
+
+                (command.map(Message::Search), event)
+            }
+            (Buffer::ServerEditor(state), Message::ServerEditor(message)) => {
+                let (command, event) = state.update(message);
+
+                let event = event.map(|event| match event {
+                    server_editor::Event::Saved {
+                        server,
+                        config,
+                        is_new,
+                    } => Event::ServerSaved {
+                        server,
+                        config,
+                        is_new,
+                    },
+                });
+
+                (command.map(Message::ServerEditor), event)
+            }
             _ => (Command::none(), None),
         }
     }
@@ -125,6 +180,8 @@ impl Buffer {
             Buffer::FileTransfers(state) => {
                 file_transfers::view(state, file_transfers).map(Message::FileTransfers)
             }
+            Buffer::Search(state) => search::view(state, config).map(Message::Search),
+            Buffer::ServerEditor(state) => server_editor::view(state).map(Message::ServerEditor),
         }
     }
 
@@ -151,6 +208,8 @@ impl Buffer {
     pub fn focus(&self) -> Command<Message> {
         match self {
             Buffer::Empty | Buffer::FileTransfers(_) => Command::none(),
+            Buffer::Search(search) => search.focus().map(Message::Search),
+            Buffer::ServerEditor(editor) => editor.focus().map(Message::ServerEditor),
             Buffer::Channel(channel) => channel.focus().map(Message::Channel),
             Buffer::Server(server) => server.focus().map(Message::Server),
             Buffer::Query(query) => query.focus().map(Message::Query),
@@ -159,7 +218,10 @@ impl Buffer {
 
     pub fn reset(&self) -> Command<Message> {
         match self {
-            Buffer::Empty | Buffer::FileTransfers(_) => Command::none(),
+            Buffer::Empty
+            | Buffer::FileTransfers(_)
+            | Buffer::Search(_)
+            | Buffer::ServerEditor(_) => Command::none(),
             Buffer::Channel(channel) => channel.reset().map(Message::Channel),
             Buffer::Server(server) => server.reset().map(Message::Server),
             Buffer::Query(query) => query.reset().map(Message::Query),
@@ -173,7 +235,11 @@ impl Buffer {
     ) -> Command<Message> {
         if let Some(buffer) = self.data() {
             match self {
-                Buffer::Empty | Buffer::Server(_) | Buffer::FileTransfers(_) => Command::none(),
+                Buffer::Empty
+                | Buffer::Server(_)
+                | Buffer::FileTransfers(_)
+                | Buffer::Search(_)
+                | Buffer::ServerEditor(_) => Command::none(),
                 Buffer::Channel(channel) => channel
                     .input_view
                     .insert_user(nick, buffer, history)
@@ -188,9 +254,37 @@ impl Buffer {
         }
     }
 
+    pub fn insert_markup_to_input(&mut self, modifier: Modifier) -> Command<Message> {
+        if let Some(buffer) = self.data() {
+            match self {
+                Buffer::Empty
+                | Buffer::FileTransfers(_)
+                | Buffer::Search(_)
+                | Buffer::ServerEditor(_) => Command::none(),
+                Buffer::Channel(channel) => channel
+                    .input_view
+                    .insert_markup(modifier, buffer)
+                    .map(|message| Message::Channel(channel::Message::InputView(message))),
+                Buffer::Server(server) => server
+                    .input_view
+                    .insert_markup(modifier, buffer)
+                    .map(|message| Message::Server(server::Message::InputView(message))),
+                Buffer::Query(query) => query
+                    .input_view
+                    .insert_markup(modifier, buffer)
+                    .map(|message| Message::Query(query::Message::InputView(message))),
+            }
+        } else {
+            Command::none()
+        }
+    }
+
     pub fn scroll_to_start(&mut self) -> Command<Message> {
         match self {
-            Buffer::Empty | Buffer::FileTransfers(_) => Command::none(),
+            Buffer::Empty
+            | Buffer::FileTransfers(_)
+            | Buffer::Search(_)
+            | Buffer::ServerEditor(_) => Command::none(),
             Buffer::Channel(channel) => channel
                 .scroll_view
                 .scroll_to_start()
@@ -208,7 +302,10 @@ impl Buffer {
 
     pub fn scroll_to_end(&mut self) -> Command<Message> {
         match self {
-            Buffer::Empty | Buffer::FileTransfers(_) => Command::none(),
+            Buffer::Empty
+            | Buffer::FileTransfers(_)
+            | Buffer::Search(_)
+            | Buffer::ServerEditor(_) => Command::none(),
             Buffer::Channel(channel) => channel
                 .scroll_view
                 .scroll_to_end()
@@ -223,13 +320,37 @@ impl Buffer {
                 .map(|message| Message::Query(query::Message::ScrollView(message))),
         }
     }
+
+    /// Scrolls to the message sent at `server_time`, showing it at the top
+    pub fn scroll_to_message(&mut self, server_time: DateTime<Utc>) -> Command<Message> {
+        match self {
+            Buffer::Empty
+            | Buffer::FileTransfers(_)
+            | Buffer::Search(_)
+            | Buffer::ServerEditor(_) => Command::none(),
+            Buffer::Channel(channel) => channel
+                .scroll_view
+                .scroll_to_message(server_time)
+                .map(|message| Message::Channel(channel::Message::ScrollView(message))),
+            Buffer::Server(server) => server
+                .scroll_view
+                .scroll_to_message(server_time)
+                .map(|message| Message::Server(server::Message::ScrollView(message))),
+            Buffer::Query(query) => query
+                .scroll_view
+                .scroll_to_message(server_time)
+                .map(|message| Message::Query(query::Message::ScrollView(message))),
+        }
+    }
 }
 
 impl From<data::Buffer> for Buffer {
     fn from(buffer: data::Buffer) -> Self {
         match buffer {
             data::Buffer::Server(server) => Self::Server(Server::new(server)),
-            data::Buffer::Channel(server, channel) => Self::Channel(Channel::new(server, channel)),
+            data::Buffer::Channel(server, channel) => {
+                Self::Channel(Channel::new(server, channel.into()))
+            }
             data::Buffer::Query(server, user) => Self::Query(Query::new(server, user)),
         }
     }
@@ -1,5 +1,4 @@
 use data::server::Server;
-use data::user::Nick;
 use data::User;
 use data::{channel, history, message, Config};
 use iced::widget::{column, container, row};
@@ -7,7 +6,7 @@ use iced::{Command, Length};
 
 use super::{input_view, scroll_view, user_context};
 use crate::theme;
-use crate::widget::{selectable_text, Element};
+use crate::widget::{message_content, selectable_text, Element};
 
 mod topic;
 
@@ -21,6 +20,8 @@ pub enum Message {
 #[derive(Debug, Clone)]
 pub enum Event {
     UserContext(user_context::Event),
+    OpenChannel(String),
+    LoadOlder,
 }
 
 pub fn view<'a>(
@@ -34,9 +35,11 @@ pub fn view<'a>(
     let buffer = state.buffer();
     let input = history.input(&buffer);
     let our_nick = clients.nickname(&state.server);
+    let casemapping = clients.get_casemapping(&state.server);
+    let chantypes = clients.get_chantypes(&state.server);
 
     let our_user = our_nick
-        .map(|our_nick| User::from(Nick::from(our_nick.as_ref())))
+        .map(|our_nick| User::from(our_nick.to_owned()))
         .and_then(|user| clients.resolve_user_attributes(&state.server, &state.channel, &user));
 
     let messages = container(
@@ -73,7 +76,13 @@ pub fn view<'a>(
                         .map(scroll_view::Message::UserContext);
 
                         let space = selectable_text(" ");
-                        let text = selectable_text(&message.text);
+                        let text = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::none,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(
                             container(
@@ -89,6 +98,7 @@ pub fn view<'a>(
                                         user.nickname(),
                                         nick,
                                         &message.text,
+                                        casemapping,
                                     ) =>
                                 {
                                     theme::container::highlight(theme)
@@ -99,14 +109,24 @@ pub fn view<'a>(
                         )
                     }
                     message::Source::Server(_) => {
-                        let message =
-                            selectable_text(&message.text).style(theme::selectable_text::info);
+                        let message = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::info,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(container(row![].push_maybe(timestamp).push(message)).into())
                     }
                     message::Source::Action => {
-                        let message =
-                            selectable_text(&message.text).style(theme::selectable_text::accent);
+                        let message = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::accent,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(container(row![].push_maybe(timestamp).push(message)).into())
                     }
@@ -140,6 +160,8 @@ pub fn view<'a>(
 
     let channels = clients.get_channels(&state.server);
     let is_connected_to_channel = channels.iter().any(|c| c == &state.channel);
+    let isupport = clients.get_isupport(&state.server);
+    let our_user = clients.our_user(&state.server);
 
     let text_input = show_text_input.then(move || {
         input_view::view(
@@ -148,6 +170,9 @@ pub fn view<'a>(
             input,
             users,
             channels,
+            isupport,
+            our_user,
+            config.buffer.text_input.markup,
             is_focused,
             !is_connected_to_channel,
         )
@@ -202,7 +227,7 @@ impl Channel {
     }
 
     pub fn buffer(&self) -> data::Buffer {
-        data::Buffer::Channel(self.server.clone(), self.channel.clone())
+        data::Buffer::Channel(self.server.clone(), self.channel.as_str().into())
     }
 
     pub fn update(
@@ -217,6 +242,8 @@ impl Channel {
 
                 let event = event.map(|event| match event {
                     scroll_view::Event::UserContext(event) => Event::UserContext(event),
+                    scroll_view::Event::OpenChannel(channel) => Event::OpenChannel(channel),
+                    scroll_view::Event::LoadOlder => Event::LoadOlder,
                 });
 
                 (command.map(Message::ScrollView), event)
@@ -1,9 +1,11 @@
-use data::input::{Cache, Draft};
+use data::formatting::Modifier;
+use data::input::{self, Cache, Draft};
+use data::isupport::ISupport;
 use data::user::{Nick, User};
 use data::{client, history, Buffer, Input};
 use iced::Command;
 
-use crate::widget::{input, Element};
+use crate::widget::{self, Element};
 
 pub enum Event {
     InputSent,
@@ -14,6 +16,7 @@ pub enum Message {
     Input(Draft),
     Send(Input),
     Completion(Draft),
+    Format(Buffer, Modifier, Option<input::Cursor>),
 }
 
 pub fn view<'a>(
@@ -22,16 +25,22 @@ pub fn view<'a>(
     cache: Cache<'a>,
     users: &'a [User],
     channels: &'a [String],
+    isupport: Option<&'a ISupport>,
+    our_user: Option<User>,
+    markup: bool,
     buffer_focused: bool,
     disabled: bool,
 ) -> Element<'a, Message> {
-    input(
+    widget::input(
         state.input_id.clone(),
         buffer,
         cache.draft,
         cache.history,
         users,
         channels,
+        isupport,
+        our_user,
+        markup,
         buffer_focused,
         disabled,
         Message::Input,
@@ -42,7 +51,7 @@ pub fn view<'a>(
 
 #[derive(Debug, Clone)]
 pub struct State {
-    input_id: input::Id,
+    input_id: widget::input::Id,
 }
 
 impl Default for State {
@@ -54,7 +63,7 @@ impl Default for State {
 impl State {
     pub fn new() -> Self {
         Self {
-            input_id: input::Id::unique(),
+            input_id: widget::input::Id::unique(),
         }
     }
 
@@ -71,7 +80,7 @@ impl State {
                 (Command::none(), None)
             }
             Message::Send(input) => {
-                if let Some(encoded) = input.encoded() {
+                for encoded in input.encoded() {
                     clients.send(input.buffer(), encoded);
                 }
 
@@ -81,13 +90,15 @@ impl State {
                     // Resolve our attributes if sending this message in a channel
                     if let Buffer::Channel(server, channel) = input.buffer() {
                         if let Some(user_with_attributes) =
-                            clients.resolve_user_attributes(server, channel, &user)
+                            clients.resolve_user_attributes(server, channel.as_str(), &user)
                         {
                             user = user_with_attributes.clone();
                         }
                     }
 
-                    history.record_input(input, user);
+                    let chantypes = clients.get_chantypes(input.server());
+
+                    history.record_input(input, user, chantypes);
                 }
 
                 (Command::none(), Some(Event::InputSent))
@@ -95,17 +106,33 @@ impl State {
             Message::Completion(draft) => {
                 history.record_draft(draft);
 
-                (input::move_cursor_to_end(self.input_id.clone()), None)
+                (
+                    widget::input::move_cursor_to_end(self.input_id.clone()),
+                    None,
+                )
+            }
+            Message::Format(buffer, modifier, cursor) => {
+                let draft = history.input(&buffer).draft;
+                // Insert at the end if the input isn't shown
+                let cursor = cursor.unwrap_or(input::Cursor::Index(usize::MAX));
+                let (text, position) = input::insert_markup(draft, modifier, cursor);
+
+                history.record_draft(Draft { buffer, text });
+
+                (
+                    widget::input::move_cursor_to(self.input_id.clone(), position),
+                    None,
+                )
             }
         }
     }
 
     pub fn focus(&self) -> Command<Message> {
-        input::focus(self.input_id.clone())
+        widget::input::focus(self.input_id.clone())
     }
 
     pub fn reset(&self) -> Command<Message> {
-        input::reset(self.input_id.clone())
+        widget::input::reset(self.input_id.clone())
     }
 
     pub fn insert_user(
@@ -126,6 +153,14 @@ impl State {
 
         history.record_draft(Draft { buffer, text });
 
-        input::move_cursor_to_end(self.input_id.clone())
+        widget::input::move_cursor_to_end(self.input_id.clone())
+    }
+
+    /// Inserts the markup of `modifier` at the cursor, or around the
+    /// selected text
+    pub fn insert_markup(&mut self, modifier: Modifier, buffer: Buffer) -> Command<Message> {
+        widget::input::get_cursor(self.input_id.clone(), move |cursor| {
+            Message::Format(buffer.clone(), modifier, cursor)
+        })
     }
 }
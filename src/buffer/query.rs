@@ -5,7 +5,7 @@ use iced::{Command, Length};
 
 use super::{input_view, scroll_view, user_context};
 use crate::theme;
-use crate::widget::{selectable_text, Element};
+use crate::widget::{message_content, selectable_text, Element};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -16,6 +16,8 @@ pub enum Message {
 #[derive(Debug, Clone)]
 pub enum Event {
     UserContext(user_context::Event),
+    OpenChannel(String),
+    LoadOlder,
 }
 
 pub fn view<'a>(
@@ -26,6 +28,7 @@ pub fn view<'a>(
     is_focused: bool,
 ) -> Element<'a, Message> {
     let status = clients.status(&state.server);
+    let chantypes = clients.get_chantypes(&state.server);
     let buffer = state.buffer();
     let input = history.input(&buffer);
 
@@ -35,7 +38,7 @@ pub fn view<'a>(
             scroll_view::Kind::Query(&state.server, &state.nick),
             history,
             config,
-            |message| {
+            move |message| {
                 let timestamp =
                     config
                         .buffer
@@ -63,7 +66,13 @@ pub fn view<'a>(
                         .map(scroll_view::Message::UserContext);
 
                         let space = selectable_text(" ");
-                        let message = selectable_text(&message.text);
+                        let message = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::none,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(
                             container(
@@ -77,14 +86,24 @@ pub fn view<'a>(
                         )
                     }
                     message::Source::Server(_) => {
-                        let message =
-                            selectable_text(&message.text).style(theme::selectable_text::info);
+                        let message = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::info,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(container(row![].push_maybe(timestamp).push(message)).into())
                     }
                     message::Source::Action => {
-                        let message =
-                            selectable_text(&message.text).style(theme::selectable_text::accent);
+                        let message = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::accent,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(container(row![].push_maybe(timestamp).push(message)).into())
                     }
@@ -107,6 +126,8 @@ pub fn view<'a>(
     };
 
     let channels = clients.get_channels(&state.server);
+    let isupport = clients.get_isupport(&state.server);
+    let our_user = clients.our_user(&state.server);
 
     let text_input = show_text_input.then(|| {
         column![
@@ -117,6 +138,9 @@ pub fn view<'a>(
                 input,
                 &[],
                 channels,
+                isupport,
+                our_user,
+                config.buffer.text_input.markup,
                 is_focused,
                 !status.connected()
             )
@@ -170,6 +194,8 @@ impl Query {
 
                 let event = event.map(|event| match event {
                     scroll_view::Event::UserContext(event) => Event::UserContext(event),
+                    scroll_view::Event::OpenChannel(channel) => Event::OpenChannel(channel),
+                    scroll_view::Event::LoadOlder => Event::LoadOlder,
                 });
 
                 (command.map(Message::ScrollView), event)
@@ -1,29 +1,35 @@
+use chrono::{DateTime, Utc};
+use data::message::link::Link;
 use data::message::Limit;
 use data::server::Server;
 use data::user::Nick;
-use data::{history, time, Config};
+use data::{history, Config};
 use iced::widget::{column, container, horizontal_rule, row, text, scrollable, Scrollable};
-use iced::{Command, Length};
+use iced::{clipboard, Command, Length};
 
 use super::user_context;
 use crate::theme;
-use crate::widget::Element;
+use crate::widget::{message_content, Element};
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Scrolled {
         count: usize,
         remaining: bool,
-        oldest: time::Posix,
+        has_older: bool,
+        oldest: DateTime<Utc>,
         status: Status,
         viewport: scrollable::Viewport,
     },
     UserContext(user_context::Message),
+    Link(message_content::Message),
 }
 
 #[derive(Debug, Clone)]
 pub enum Event {
     UserContext(user_context::Event),
+    OpenChannel(String),
+    LoadOlder,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -42,6 +48,7 @@ pub fn view<'a>(
 ) -> Element<'a, Message> {
     let Some(history::View {
         total,
+        has_older,
         old_messages,
         new_messages,
     }) = (match kind {
@@ -65,8 +72,8 @@ pub fn view<'a>(
         .iter()
         .chain(&new_messages)
         .next()
-        .map(|message| message.received_at)
-        .unwrap_or_else(time::Posix::now);
+        .map(|message| message.server_time)
+        .unwrap_or_else(Utc::now);
     let status = state.status;
 
     let old = old_messages
@@ -114,6 +121,7 @@ pub fn view<'a>(
     .on_scroll(move |viewport| Message::Scrolled {
         count,
         remaining,
+        has_older,
         oldest,
         status,
         viewport,
@@ -149,12 +157,17 @@ impl State {
             Message::Scrolled {
                 count,
                 remaining,
+                has_older,
                 oldest,
                 status: old_status,
                 viewport,
             } => {
                 let relative_offset = viewport.relative_offset().y;
 
+                // Every message in memory is shown, load older ones from disk
+                let event = (has_older && !remaining && old_status.is_top(relative_offset))
+                    .then_some(Event::LoadOlder);
+
                 match old_status {
                     Status::Loading(anchor) => {
                         self.status = Status::Unlocked(anchor);
@@ -207,9 +220,11 @@ impl State {
                 if let Some(new_offset) = self.status.new_offset(old_status, viewport) {
                     return (
                         scrollable::scroll_to(self.scrollable.clone(), new_offset),
-                        None,
+                        event,
                     );
                 }
+
+                return (Command::none(), event);
             }
             Message::UserContext(message) => {
                 return (
@@ -217,11 +232,34 @@ impl State {
                     Some(Event::UserContext(user_context::update(message))),
                 );
             }
+            Message::Link(message_content::Message::Open(link)) => match link {
+                Link::Url(_) => {
+                    if let Some(url) = link.url() {
+                        let _ = open::that(url);
+                    }
+                }
+                Link::Channel(channel) => {
+                    return (Command::none(), Some(Event::OpenChannel(channel)));
+                }
+            },
+            Message::Link(message_content::Message::Copy(link)) => {
+                return (clipboard::write(link.target().to_string()), None);
+            }
         }
 
         (Command::none(), None)
     }
 
+    /// Shows the messages from `server_time` onwards, w/ the first at the top
+    pub fn scroll_to_message(&mut self, server_time: DateTime<Utc>) -> Command<Message> {
+        self.status = Status::Unlocked(Anchor::Bottom);
+        self.limit = Limit::Since(server_time);
+        scrollable::scroll_to(
+            self.scrollable.clone(),
+            scrollable::AbsoluteOffset { x: 0.0, y: 0.0 },
+        )
+    }
+
     pub fn scroll_to_start(&mut self) -> Command<Message> {
         self.status = Status::Idle(Anchor::Top);
         self.limit = Limit::top();
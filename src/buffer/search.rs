@@ -0,0 +1,220 @@
+use chrono::{DateTime, Local, Utc};
+use data::search::{self, Query};
+use data::{formatting, message, Config};
+use iced::widget::{button, column, container, row, scrollable, text, text_input, Scrollable};
+use iced::{Command, Length};
+
+use crate::widget::Element;
+use crate::{icon, theme};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Input(String),
+    Submit,
+    Searched(Result<Vec<search::Match>, String>),
+    Open(usize),
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    OpenMessage(data::Buffer, DateTime<Utc>),
+}
+
+pub fn view<'a>(state: &'a Search, config: &'a Config) -> Element<'a, Message> {
+    let input = text_input(
+        "Search history, e.g. release from:nick in:#channel since:2024-01-31",
+        &state.query,
+    )
+    .on_input(Message::Input)
+    .on_submit(Message::Submit)
+    .id(state.input_id.clone())
+    .padding(8)
+    .style(if matches!(state.status, Status::Failed(_)) {
+        theme::text_input::error
+    } else {
+        theme::text_input::primary
+    });
+
+    let content: Element<_> = match &state.status {
+        Status::Idle => placeholder("Search messages across all buffers"),
+        Status::Searching => placeholder("Searching..."),
+        Status::Failed(error) => placeholder(error),
+        Status::Done(matches) if matches.is_empty() => placeholder("No messages found"),
+        Status::Done(matches) => Scrollable::with_direction(
+            column(
+                matches
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, found)| result_row(found, idx, config)),
+            )
+            .spacing(1)
+            .padding([0, 2]),
+            scrollable::Direction::Vertical(
+                scrollable::Properties::new().width(1).scroller_width(1),
+            ),
+        )
+        .style(theme::scrollable::hidden)
+        .into(),
+    };
+
+    column![container(input).padding([4, 4, 0, 4]), content]
+        .spacing(4)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .into()
+}
+
+fn placeholder<'a>(content: &str) -> Element<'a, Message> {
+    container(
+        column![
+            icon::search()
+                .size(theme::TEXT_SIZE + 3.0)
+                .style(theme::text::transparent),
+            text(content.to_string()).style(theme::text::transparent)
+        ]
+        .spacing(8)
+        .align_items(iced::Alignment::Center),
+    )
+    .center_x()
+    .center_y()
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
+}
+
+fn result_row<'a>(
+    found: &'a search::Match,
+    idx: usize,
+    config: &'a Config,
+) -> Element<'a, Message> {
+    let buffer = match &found.buffer {
+        data::Buffer::Server(server) => server.to_string(),
+        data::Buffer::Channel(server, channel) => format!("{channel} @ {server}"),
+        data::Buffer::Query(server, nick) => format!("{nick} @ {server}"),
+    };
+    let sent_at = found
+        .message
+        .server_time
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M");
+
+    let nick = match found.message.target.source() {
+        message::Source::User(user) => Some(
+            text(format!("{} ", config.buffer.nickname.brackets.format(user))).style(
+                move |theme| {
+                    theme::text::nickname(
+                        theme,
+                        user.color_seed(&config.buffer.nickname.color),
+                        false,
+                    )
+                },
+            ),
+        ),
+        _ => None,
+    };
+
+    let content = column![
+        text(format!("{sent_at} · {buffer}")).style(theme::text::transparent),
+        row![]
+            .push_maybe(nick)
+            .push(text(formatting::strip(&found.message.text)))
+    ];
+
+    button(
+        container(content)
+            .padding([6, 4, 6, 8])
+            .width(Length::Fill)
+            .style(move |theme| theme::container::table_row(theme, idx)),
+    )
+    .padding(0)
+    .style(theme::button::bare)
+    .on_press(Message::Open(idx))
+    .into()
+}
+
+#[derive(Debug, Clone, Default)]
+enum Status {
+    #[default]
+    Idle,
+    Searching,
+    Done(Vec<search::Match>),
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    query: String,
+    status: Status,
+    input_id: text_input::Id,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            status: Status::default(),
+            input_id: text_input::Id::unique(),
+        }
+    }
+}
+
+impl Search {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(
+        &mut self,
+        message: Message,
+        config: &Config,
+    ) -> (Command<Message>, Option<Event>) {
+        match message {
+            Message::Input(query) => {
+                self.query = query;
+            }
+            Message::Submit => match Query::parse(&self.query) {
+                Ok(query) => {
+                    self.status = Status::Searching;
+
+                    let servers = config.servers.entries().map(|entry| entry.server).collect();
+
+                    return (
+                        Command::perform(search::search(query, servers), |result| {
+                            Message::Searched(result.map_err(|error| error.to_string()))
+                        }),
+                        None,
+                    );
+                }
+                Err(error) => {
+                    self.status = Status::Failed(error.to_string());
+                }
+            },
+            Message::Searched(Ok(matches)) => {
+                self.status = Status::Done(matches);
+            }
+            Message::Searched(Err(error)) => {
+                log::warn!("failed to search history: {error}");
+                self.status = Status::Failed(format!("Search failed: {error}"));
+            }
+            Message::Open(idx) => {
+                if let Status::Done(matches) = &self.status {
+                    if let Some(found) = matches.get(idx) {
+                        return (
+                            Command::none(),
+                            Some(Event::OpenMessage(
+                                found.buffer.clone(),
+                                found.message.server_time,
+                            )),
+                        );
+                    }
+                }
+            }
+        }
+
+        (Command::none(), None)
+    }
+
+    pub fn focus(&self) -> Command<Message> {
+        text_input::focus(self.input_id.clone())
+    }
+}
@@ -4,7 +4,7 @@ use iced::{Command, Length};
 
 use super::{input_view, scroll_view};
 use crate::theme;
-use crate::widget::{selectable_text, Element};
+use crate::widget::{message_content, selectable_text, Element};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -12,6 +12,12 @@ pub enum Message {
     InputView(input_view::Message),
 }
 
+#[derive(Debug, Clone)]
+pub enum Event {
+    OpenChannel(String),
+    LoadOlder,
+}
+
 pub fn view<'a>(
     state: &'a Server,
     clients: &'a data::client::Map,
@@ -20,6 +26,7 @@ pub fn view<'a>(
     is_focused: bool,
 ) -> Element<'a, Message> {
     let status = clients.status(&state.server);
+    let chantypes = clients.get_chantypes(&state.server);
     let buffer = state.buffer();
     let input = history.input(&buffer);
 
@@ -29,7 +36,7 @@ pub fn view<'a>(
             scroll_view::Kind::Server(&state.server),
             history,
             config,
-            |message| {
+            move |message| {
                 let timestamp =
                     config
                         .buffer
@@ -40,8 +47,13 @@ pub fn view<'a>(
 
                 match message.target.source() {
                     message::Source::Server(_) => {
-                        let message =
-                            selectable_text(&message.text).style(theme::selectable_text::info);
+                        let message = message_content::view(
+                            message,
+                            chantypes,
+                            config,
+                            theme::selectable_text::info,
+                        )
+                        .map(scroll_view::Message::Link);
 
                         Some(container(row![].push_maybe(timestamp).push(message)).into())
                     }
@@ -65,6 +77,8 @@ pub fn view<'a>(
     };
 
     let channels = clients.get_channels(&state.server);
+    let isupport = clients.get_isupport(&state.server);
+    let our_user = clients.our_user(&state.server);
 
     let text_input = show_text_input.then(|| {
         column![
@@ -75,6 +89,9 @@ pub fn view<'a>(
                 input,
                 &[],
                 channels,
+                isupport,
+                our_user,
+                config.buffer.text_input.markup,
                 is_focused,
                 !status.connected()
             )
@@ -119,22 +136,32 @@ impl Server {
         message: Message,
         clients: &mut data::client::Map,
         history: &mut history::Manager,
-    ) -> Command<Message> {
+    ) -> (Command<Message>, Option<Event>) {
         match message {
             Message::ScrollView(message) => {
-                let (command, _) = self.scroll_view.update(message);
-                command.map(Message::ScrollView)
+                let (command, event) = self.scroll_view.update(message);
+
+                let event = event.and_then(|event| match event {
+                    scroll_view::Event::UserContext(_) => None,
+                    scroll_view::Event::OpenChannel(channel) => Some(Event::OpenChannel(channel)),
+                    scroll_view::Event::LoadOlder => Some(Event::LoadOlder),
+                });
+
+                (command.map(Message::ScrollView), event)
             }
             Message::InputView(message) => {
                 let (command, event) = self.input_view.update(message, clients, history);
                 let command = command.map(Message::InputView);
 
                 match event {
-                    Some(input_view::Event::InputSent) => Command::batch(vec![
-                        command,
-                        self.scroll_view.scroll_to_end().map(Message::ScrollView),
-                    ]),
-                    None => command,
+                    Some(input_view::Event::InputSent) => (
+                        Command::batch(vec![
+                            command,
+                            self.scroll_view.scroll_to_end().map(Message::ScrollView),
+                        ]),
+                        None,
+                    ),
+                    None => (command, None),
                 }
             }
         }
@@ -0,0 +1,360 @@
+use std::fmt;
+
+use data::config::{self, edit};
+use iced::widget::{
+    button, column, container, row, scrollable, text, text_input, Scrollable, TextInput,
+};
+use iced::{Command, Length};
+
+use crate::widget::{Element, Renderer};
+use crate::{theme, Theme};
+
+const LABEL_WIDTH: f32 = 100.0;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Name(String),
+    Nickname(String),
+    Server(String),
+    Port(String),
+    UseTls(bool),
+    Sasl(Sasl),
+    SaslUsername(String),
+    SaslPassword(String),
+    Channels(String),
+    Save,
+    Saved(Result<Box<config::Server>, String>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Saved {
+        server: data::Server,
+        config: Box<config::Server>,
+        is_new: bool,
+    },
+}
+
+pub fn view(state: &ServerEditor) -> Element<'_, Message> {
+    let name = input(
+        "liberachat",
+        &state.name,
+        // Servers are renamed in the config file
+        state.is_new.then_some(Message::Name),
+    )
+    .id(state.name_id.clone());
+
+    let tls = row![
+        choice("TLS", state.use_tls, Message::UseTls(true)),
+        choice("Unencrypted", !state.use_tls, Message::UseTls(false)),
+    ]
+    .spacing(4);
+
+    let sasl = row(Sasl::ALL
+        .into_iter()
+        // External needs a certificate, which is set in the config file
+        .filter(|sasl| *sasl != Sasl::External || state.sasl == Sasl::External)
+        .map(|sasl| choice(sasl.to_string(), state.sasl == sasl, Message::Sasl(sasl))))
+    .spacing(4);
+
+    let mut fields = column![
+        field("Name", name),
+        field(
+            "Nickname",
+            input("halloy-user", &state.nickname, Some(Message::Nickname))
+        ),
+        field(
+            "Server",
+            input("irc.libera.chat", &state.server, Some(Message::Server))
+        ),
+        field("Port", input("6697", &state.port, Some(Message::Port))),
+        field("Security", tls),
+        field("SASL", sasl),
+    ]
+    .spacing(8);
+
+    if let Sasl::Plain | Sasl::Scram = state.sasl {
+        fields = fields
+            .push(field(
+                "Username",
+                input("account", &state.sasl_username, Some(Message::SaslUsername)),
+            ))
+            .push(field(
+                "Password",
+                input(
+                    if state.is_new {
+                        "password"
+                    } else {
+                        "leave empty to keep the current password"
+                    },
+                    &state.sasl_password,
+                    Some(Message::SaslPassword),
+                )
+                .secure(true),
+            ));
+    }
+
+    let status = match &state.status {
+        Status::Idle => None,
+        Status::Saving => Some(text("Saving...").style(theme::text::transparent)),
+        Status::Saved => Some(text("Saved to config file").style(theme::text::success)),
+        Status::Failed(error) => Some(text(error.clone()).style(theme::text::error)),
+    };
+
+    let save = button(text(if state.is_new { "Add server" } else { "Save" }))
+        .padding([6, 12])
+        .style(theme::button::primary)
+        .on_press_maybe((!matches!(state.status, Status::Saving)).then_some(Message::Save));
+
+    let content = fields
+        .push(field(
+            "Channels",
+            input("#halloy, #rust", &state.channels, Some(Message::Channels)),
+        ))
+        .push(
+            row![save]
+                .push_maybe(status)
+                .spacing(12)
+                .align_items(iced::Alignment::Center),
+        )
+        .padding(12)
+        .max_width(600);
+
+    container(
+        Scrollable::with_direction(
+            content,
+            scrollable::Direction::Vertical(
+                scrollable::Properties::new().width(1).scroller_width(1),
+            ),
+        )
+        .style(theme::scrollable::hidden),
+    )
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
+}
+
+fn input<'a>(
+    placeholder: &str,
+    value: &str,
+    on_input: Option<fn(String) -> Message>,
+) -> TextInput<'a, Message, Theme, Renderer> {
+    let input = text_input(placeholder, value)
+        .on_submit(Message::Save)
+        .padding(8)
+        .style(theme::text_input::primary);
+
+    match on_input {
+        Some(on_input) => input.on_input(on_input),
+        None => input,
+    }
+}
+
+fn field<'a>(label: &'a str, content: impl Into<Element<'a, Message>>) -> Element<'a, Message> {
+    row![
+        text(label)
+            .style(theme::text::transparent)
+            .width(LABEL_WIDTH),
+        content.into()
+    ]
+    .spacing(8)
+    .align_items(iced::Alignment::Center)
+    .into()
+}
+
+fn choice<'a>(label: impl ToString, selected: bool, message: Message) -> Element<'a, Message> {
+    button(text(label.to_string()))
+        .padding([4, 8])
+        .on_press(message)
+        .style(move |theme, status| theme::button::tertiary(theme, status, selected))
+        .into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sasl {
+    None,
+    Plain,
+    Scram,
+    External,
+}
+
+impl Sasl {
+    const ALL: [Sasl; 4] = [Sasl::None, Sasl::Plain, Sasl::Scram, Sasl::External];
+}
+
+impl fmt::Display for Sasl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sasl::None => write!(f, "None"),
+            Sasl::Plain => write!(f, "PLAIN"),
+            Sasl::Scram => write!(f, "SCRAM"),
+            Sasl::External => write!(f, "External"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+enum Status {
+    #[default]
+    Idle,
+    Saving,
+    Saved,
+    Failed(String),
+}
+
+/// Adds a server to the config file, or edits one of its servers
+#[derive(Debug, Clone)]
+pub struct ServerEditor {
+    is_new: bool,
+    name: String,
+    nickname: String,
+    server: String,
+    port: String,
+    use_tls: bool,
+    sasl: Sasl,
+    sasl_username: String,
+    sasl_password: String,
+    channels: String,
+    status: Status,
+    name_id: text_input::Id,
+}
+
+impl ServerEditor {
+    pub fn new() -> Self {
+        Self {
+            is_new: true,
+            name: String::new(),
+            nickname: String::new(),
+            server: String::new(),
+            port: "6697".to_string(),
+            use_tls: true,
+            sasl: Sasl::None,
+            sasl_username: String::new(),
+            sasl_password: String::new(),
+            channels: String::new(),
+            status: Status::default(),
+            name_id: text_input::Id::unique(),
+        }
+    }
+
+    pub fn edit(server: &data::Server, config: &config::Server) -> Self {
+        let edit = edit::Server::new(server, config);
+
+        let (sasl, sasl_username, sasl_password) = match edit.sasl {
+            None => (Sasl::None, String::new(), String::new()),
+            Some(edit::Sasl::Plain { username, password }) => (Sasl::Plain, username, password),
+            Some(edit::Sasl::Scram { username, password }) => (Sasl::Scram, username, password),
+            Some(edit::Sasl::External) => (Sasl::External, String::new(), String::new()),
+        };
+
+        Self {
+            is_new: false,
+            name: edit.name,
+            nickname: edit.nickname,
+            server: edit.server,
+            port: edit.port.to_string(),
+            use_tls: edit.use_tls,
+            sasl,
+            sasl_username,
+            sasl_password,
+            channels: edit.channels.join(", "),
+            status: Status::default(),
+            name_id: text_input::Id::unique(),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        if self.is_new {
+            "Add server".to_string()
+        } else {
+            format!("Edit {}", self.name)
+        }
+    }
+
+    pub fn update(&mut self, message: Message) -> (Command<Message>, Option<Event>) {
+        match message {
+            Message::Name(name) => self.name = name,
+            Message::Nickname(nickname) => self.nickname = nickname,
+            Message::Server(server) => self.server = server,
+            Message::Port(port) => self.port = port,
+            Message::UseTls(use_tls) => self.use_tls = use_tls,
+            Message::Sasl(sasl) => self.sasl = sasl,
+            Message::SaslUsername(username) => self.sasl_username = username,
+            Message::SaslPassword(password) => self.sasl_password = password,
+            Message::Channels(channels) => self.channels = channels,
+            Message::Save => match self.to_edit() {
+                Ok(edit) => {
+                    self.status = Status::Saving;
+
+                    return (
+                        Command::perform(edit::save(edit, self.is_new), |result| {
+                            Message::Saved(result.map(Box::new).map_err(|error| error.to_string()))
+                        }),
+                        None,
+                    );
+                }
+                Err(error) => self.status = Status::Failed(error),
+            },
+            Message::Saved(Ok(config)) => {
+                let event = Event::Saved {
+                    server: data::Server::from(self.name.trim()),
+                    config,
+                    is_new: self.is_new,
+                };
+
+                // Saved again as an edit of the added server
+                self.is_new = false;
+                self.status = Status::Saved;
+
+                return (Command::none(), Some(event));
+            }
+            Message::Saved(Err(error)) => {
+                log::warn!("failed to save server: {error}");
+                self.status = Status::Failed(error);
+            }
+        }
+
+        (Command::none(), None)
+    }
+
+    pub fn focus(&self) -> Command<Message> {
+        text_input::focus(self.name_id.clone())
+    }
+
+    fn to_edit(&self) -> Result<edit::Server, String> {
+        let port = self
+            .port
+            .trim()
+            .parse::<u16>()
+            .map_err(|_| "port must be a number from 0 to 65535".to_string())?;
+
+        let sasl = match self.sasl {
+            Sasl::None => None,
+            Sasl::Plain => Some(edit::Sasl::Plain {
+                username: self.sasl_username.trim().to_string(),
+                password: self.sasl_password.clone(),
+            }),
+            Sasl::Scram => Some(edit::Sasl::Scram {
+                username: self.sasl_username.trim().to_string(),
+                password: self.sasl_password.clone(),
+            }),
+            Sasl::External => Some(edit::Sasl::External),
+        };
+
+        Ok(edit::Server {
+            name: self.name.trim().to_string(),
+            nickname: self.nickname.clone(),
+            server: self.server.clone(),
+            port,
+            use_tls: self.use_tls,
+            sasl,
+            // Channel names can't contain commas or spaces
+            channels: self
+                .channels
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|channel| !channel.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,98 @@
+//! One-shot commands, run instead of the application
+
+use std::future::Future;
+use std::path::PathBuf;
+
+use data::history::{self, import};
+use data::isupport::DEFAULT_CHANTYPES;
+use data::user::Nick;
+use data::{Config, Server};
+
+const IMPORT_USAGE: &str = "usage: halloy --import-logs <server> <channel or nick> <log files>...";
+
+/// Runs `command` w/ the config, printing its output, and exits on failure
+pub fn run<F>(command: impl FnOnce(Config) -> F)
+where
+    F: Future<Output = Result<String, String>>,
+{
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("failed to load config: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("build runtime");
+
+    match runtime.block_on(command(config)) {
+        Ok(output) => println!("{output}"),
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Writes plain-text logs of all stored history, as configured by
+/// `[history.text_log]`
+pub async fn export_logs(config: Config) -> Result<String, String> {
+    let text_log = config.history.text_log;
+    let servers = config
+        .servers
+        .entries()
+        .map(|entry| entry.server)
+        .collect::<Vec<_>>();
+
+    let export = history::text::export(text_log.clone(), &servers)
+        .await
+        .map_err(|error| format!("failed to export logs: {error}"))?;
+
+    let mut output = format!(
+        "exported {} log files to {}",
+        export.written,
+        text_log.directory().display()
+    );
+
+    if export.skipped > 0 {
+        output.push_str(&format!(
+            "\nskipped {} histories whose buffer is unknown, such as those of servers no longer in the config",
+            export.skipped
+        ));
+    }
+
+    Ok(output)
+}
+
+/// Imports the logs of other clients into the history of a channel or query
+pub async fn import_logs(config: Config, args: Vec<String>) -> Result<String, String> {
+    let [server, buffer, files @ ..] = args.as_slice() else {
+        return Err(IMPORT_USAGE.to_string());
+    };
+
+    if files.is_empty() {
+        return Err(IMPORT_USAGE.to_string());
+    }
+
+    let server = Server::from(server.as_str());
+
+    if !config.servers.entries().any(|entry| entry.server == server) {
+        return Err(format!("no server named {server} in the config"));
+    }
+
+    let kind = if buffer.starts_with(DEFAULT_CHANTYPES) {
+        history::Kind::Channel(buffer.as_str().into())
+    } else {
+        history::Kind::Query(Nick::from(buffer.as_str()))
+    };
+
+    let imported = import::import(&server, &kind, files.iter().map(PathBuf::from).collect())
+        .await
+        .map_err(|error| format!("failed to import logs: {error}"))?;
+
+    Ok(format!(
+        "imported {imported} new messages into {kind} on {server}"
+    ))
+}
@@ -54,6 +54,10 @@ pub fn file_transfer<'a>() -> Text<'a> {
     to_text('\u{f1c7}')
 }
 
+pub fn export<'a>() -> Text<'a> {
+    to_text('\u{f30a}')
+}
+
 fn to_text<'a>(unicode: char) -> Text<'a> {
     text(unicode.to_string())
         .style(theme::text::primary)
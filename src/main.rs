@@ -2,6 +2,7 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod buffer;
+mod cli;
 mod event;
 mod font;
 mod icon;
@@ -21,7 +22,7 @@ use data::version::Version;
 use data::{environment, server, version, User};
 use iced::advanced::Application;
 use iced::widget::container;
-use iced::{executor, Command, Length, Renderer, Subscription};
+use iced::{executor, subscription, Command, Length, Renderer, Subscription};
 use screen::{dashboard, help, migration, welcome};
 
 use self::event::{events, Event};
@@ -32,17 +33,29 @@ pub fn main() -> iced::Result {
     let mut args = env::args();
     args.next();
 
-    let version = args
-        .next()
-        .map(|s| s == "--version" || s == "-V")
-        .unwrap_or_default();
+    let arg = args.next();
 
-    if version {
+    if matches!(arg.as_deref(), Some("--version" | "-V")) {
         println!("halloy {}", environment::formatted_version());
 
         return Ok(());
     }
 
+    match arg.as_deref() {
+        Some("--export-logs") => {
+            cli::run(cli::export_logs);
+
+            return Ok(());
+        }
+        Some("--import-logs") => {
+            let args = args.collect();
+            cli::run(|config| cli::import_logs(config, args));
+
+            return Ok(());
+        }
+        _ => {}
+    }
+
     #[cfg(debug_assertions)]
     let is_debug = true;
     #[cfg(not(debug_assertions))]
@@ -189,6 +202,7 @@ pub enum Message {
     Event(Event),
     Tick(Instant),
     Version(Option<String>),
+    ConfigReloaded(Result<Config, config::Error>),
 }
 
 impl Application for Halloy {
@@ -296,6 +310,11 @@ impl Application for Halloy {
                 Command::none()
             }
             Message::Stream(update) => match update {
+                stream::Update::Controller(server, controller) => {
+                    self.clients.set_controller(server, controller);
+
+                    Command::none()
+                }
                 stream::Update::Disconnected {
                     server,
                     is_initial,
@@ -327,6 +346,7 @@ impl Application for Halloy {
                     server,
                     client: connection,
                     is_initial,
+                    address,
                     sent_time,
                 } => {
                     self.clients.ready(server.clone(), connection);
@@ -342,7 +362,7 @@ impl Application for Halloy {
                             notification::show("Connected", &server, notification.sound());
                         }
 
-                        dashboard.broadcast_connected(&server, &self.config, sent_time);
+                        dashboard.broadcast_connected(&server, address, &self.config, sent_time);
                     } else {
                         let notification = &self.config.notifications.reconnected;
 
@@ -350,13 +370,34 @@ impl Application for Halloy {
                             notification::show("Reconnected", &server, notification.sound());
                         }
 
-                        dashboard.broadcast_reconnected(&server, &self.config, sent_time);
+                        dashboard.broadcast_reconnected(&server, address, &self.config, sent_time);
                     }
 
                     Command::none()
                 }
+                stream::Update::Offline(server) => {
+                    self.clients.offline(server);
+
+                    Command::none()
+                }
+                stream::Update::Pinged(server) => {
+                    self.clients.pinged(&server);
+
+                    Command::none()
+                }
+                stream::Update::Ponged(server, round_trip) => {
+                    self.clients.ponged(&server, round_trip);
+
+                    Command::none()
+                }
+                stream::Update::Queued(server, queued) => {
+                    self.clients.queued(&server, queued);
+
+                    Command::none()
+                }
                 stream::Update::ConnectionFailed {
                     server,
+                    address,
                     error,
                     sent_time,
                 } => {
@@ -364,7 +405,13 @@ impl Application for Halloy {
                         return Command::none();
                     };
 
-                    dashboard.broadcast_connection_failed(&server, error, &self.config, sent_time);
+                    dashboard.broadcast_connection_failed(
+                        &server,
+                        address,
+                        error,
+                        &self.config,
+                        sent_time,
+                    );
 
                     Command::none()
                 }
@@ -379,6 +426,9 @@ impl Application for Halloy {
                             let mut commands = vec![];
 
                             for event in self.clients.receive(&server, message) {
+                                let chantypes = self.clients.get_chantypes(&server);
+                                let casemapping = self.clients.get_casemapping(&server);
+
                                 // Resolve a user using client state which stores attributes
                                 let resolve_user_attributes = |user: &User, channel: &str| {
                                     self.clients
@@ -392,6 +442,8 @@ impl Application for Halloy {
                                             encoded,
                                             our_nick,
                                             &self.config,
+                                            chantypes,
+                                            casemapping,
                                             resolve_user_attributes,
                                         ) {
                                             dashboard.record_message(&server, message);
@@ -402,6 +454,8 @@ impl Application for Halloy {
                                             encoded,
                                             our_nick,
                                             &self.config,
+                                            chantypes,
+                                            casemapping,
                                             resolve_user_attributes,
                                         ) {
                                             dashboard.record_message(
@@ -462,6 +516,19 @@ impl Application for Halloy {
                                                 sent_time,
                                             );
                                         }
+                                        data::client::Broadcast::SaslFailed {
+                                            error,
+                                            disconnect,
+                                            sent_time,
+                                        } => {
+                                            dashboard.broadcast_sasl_failed(
+                                                &server,
+                                                error,
+                                                disconnect,
+                                                &self.config,
+                                                sent_time,
+                                            );
+                                        }
                                     },
                                     data::client::Event::Notification(
                                         encoded,
@@ -472,6 +539,8 @@ impl Application for Halloy {
                                             encoded,
                                             our_nick,
                                             &self.config,
+                                            chantypes,
+                                            casemapping,
                                             resolve_user_attributes,
                                         ) {
                                             dashboard.record_message(&server, message);
@@ -507,6 +576,37 @@ impl Application for Halloy {
                                             commands.push(command.map(Message::Dashboard));
                                         }
                                     }
+                                    data::client::Event::ChatHistory(encoded, our_nick) => {
+                                        let messages = encoded
+                                            .into_iter()
+                                            .filter_map(|encoded| {
+                                                data::Message::received(
+                                                    encoded,
+                                                    our_nick.clone(),
+                                                    &self.config,
+                                                    chantypes,
+                                                    casemapping,
+                                                    &resolve_user_attributes,
+                                                )
+                                            })
+                                            .collect();
+
+                                        dashboard.record_chathistory(&server, messages);
+                                    }
+                                    data::client::Event::ChatHistoryRequest(kind) => {
+                                        commands.push(
+                                            dashboard
+                                                .request_chathistory(&server, kind)
+                                                .map(Message::Dashboard),
+                                        );
+                                    }
+                                    data::client::Event::ChatHistoryQueries => {
+                                        commands.push(
+                                            dashboard
+                                                .request_queries_chathistory(&server)
+                                                .map(Message::Dashboard),
+                                        );
+                                    }
                                 }
                             }
 
@@ -538,6 +638,41 @@ impl Application for Halloy {
                     Command::none()
                 }
             }
+            Message::ConfigReloaded(Ok(config)) => {
+                let Screen::Dashboard(dashboard) = &mut self.screen else {
+                    // Config is valid now, leave the help or welcome screen
+                    let (halloy, command) = Halloy::load_from_state(Ok(config));
+                    *self = halloy;
+
+                    return command;
+                };
+
+                log::info!("config reloaded");
+
+                // Keep the theme switched to from the command bar
+                if config.themes.default.name != self.config.themes.default.name {
+                    self.theme = config.themes.default.clone().into();
+                }
+
+                if config.font.family != self.config.font.family
+                    || config.font.size != self.config.font.size
+                {
+                    log::warn!("font changes apply once halloy is restarted");
+                }
+
+                let changes = self.config.servers.changes(&config.servers);
+                let command =
+                    dashboard.reload_config(changes, &mut self.clients, &mut self.servers, &config);
+
+                self.config = config;
+
+                command.map(Message::Dashboard)
+            }
+            Message::ConfigReloaded(Err(error)) => {
+                log::warn!("failed to reload config: {error}");
+
+                Command::none()
+            }
             Message::Tick(now) => {
                 self.clients.tick(now);
 
@@ -581,6 +716,8 @@ impl Application for Halloy {
         let streams =
             Subscription::batch(self.servers.entries().map(stream::run)).map(Message::Stream);
 
-        Subscription::batch(vec![tick, streams, events().map(Message::Event)])
+        let config = subscription::channel("config", 1, config::watch).map(Message::ConfigReloaded);
+
+        Subscription::batch(vec![tick, streams, config, events().map(Message::Event)])
     }
 }
@@ -19,6 +19,8 @@ use self::command_bar::CommandBar;
 use self::pane::Pane;
 use self::sidebar::Sidebar;
 use crate::buffer::file_transfers::FileTransfers;
+use crate::buffer::search::Search;
+use crate::buffer::server_editor::ServerEditor;
 use crate::buffer::{self, Buffer};
 use crate::widget::{anchored_overlay, selectable_text, shortcut, Element};
 use crate::{event, notification, theme, Theme};
@@ -49,6 +51,10 @@ pub enum Message {
     Shortcut(shortcut::Command),
     FileTransfer(file_transfer::task::Update),
     SendFileSelected(Server, Nick, Option<PathBuf>),
+    RequestChatHistory(Server, history::Kind, Option<DateTime<Utc>>),
+    ImportLogsSelected(data::Buffer, Option<Vec<PathBuf>>),
+    LogsRead(data::Buffer, Result<Vec<data::Message>, String>),
+    ExportSelected(data::Buffer, history::export::Range, Option<PathBuf>),
 }
 
 impl Dashboard {
@@ -59,13 +65,13 @@ impl Dashboard {
             panes,
             focus: None,
             side_menu: Sidebar::new(),
-            history: history::Manager::default(),
+            history: history::Manager::new(&config.history),
             last_changed: None,
             command_bar: None,
             file_transfers: file_transfer::Manager::new(config.file_transfer.clone()),
         };
 
-        let command = dashboard.track();
+        let command = Command::batch(vec![dashboard.track(), dashboard.prune_history(config)]);
 
         (dashboard, command)
     }
@@ -74,9 +80,13 @@ impl Dashboard {
         let mut dashboard = Dashboard::from_data(dashboard, config);
 
         let command = if let Some((pane, _)) = dashboard.panes.panes.iter().next() {
-            Command::batch(vec![dashboard.focus_pane(*pane), dashboard.track()])
+            Command::batch(vec![
+                dashboard.focus_pane(*pane),
+                dashboard.track(),
+                dashboard.prune_history(config),
+            ])
         } else {
-            dashboard.track()
+            Command::batch(vec![dashboard.track(), dashboard.prune_history(config)])
         };
 
         (dashboard, command)
@@ -123,6 +133,60 @@ impl Dashboard {
                             config,
                         );
 
+                        if let Some(buffer::Event::OpenChannel(channel)) = &event {
+                            if let Some(buffer) = pane.buffer.data() {
+                                let server = buffer.server().clone();
+
+                                return self.join_channel(server, channel.clone(), clients, config);
+                            }
+                        }
+
+                        if let Some(buffer::Event::LoadOlder) = &event {
+                            if let Some(task) = pane
+                                .resource()
+                                .and_then(|resource| self.history.load_older(&resource))
+                            {
+                                return Command::batch(vec![
+                                    command.map(move |message| {
+                                        Message::Pane(pane::Message::Buffer(id, message))
+                                    }),
+                                    Command::perform(task, Message::History),
+                                ]);
+                            }
+                        }
+
+                        if let Some(buffer::Event::OpenMessage(buffer, server_time)) = &event {
+                            return Command::batch(vec![
+                                command.map(move |message| {
+                                    Message::Pane(pane::Message::Buffer(id, message))
+                                }),
+                                self.open_message(buffer.clone(), *server_time, config),
+                            ]);
+                        }
+
+                        if let Some(buffer::Event::ServerSaved {
+                            server,
+                            config,
+                            is_new,
+                        }) = event
+                        {
+                            log::info!(
+                                "[{server}] {} in config file",
+                                if is_new { "added" } else { "edited" }
+                            );
+
+                            // Running servers reconnect w/ their new config,
+                            // others start a new stream once inserted
+                            if servers.get(&server).is_some() {
+                                clients.configure(&server, (*config).clone());
+                            }
+                            servers.insert(server, *config);
+
+                            return command.map(move |message| {
+                                Message::Pane(pane::Message::Buffer(id, message))
+                            });
+                        }
+
                         if let Some(buffer::Event::UserContext(event)) = event {
                             match event {
                                 buffer::user_context::Event::ToggleAccessLevel(nick, mode) => {
@@ -141,7 +205,7 @@ impl Dashboard {
                                     );
                                     let input = data::Input::command(buffer.clone(), command);
 
-                                    if let Some(encoded) = input.encoded() {
+                                    for encoded in input.encoded() {
                                         clients.send(input.buffer(), encoded);
                                     }
                                 }
@@ -151,7 +215,7 @@ impl Dashboard {
 
                                         let input = data::Input::command(buffer.clone(), command);
 
-                                        if let Some(encoded) = input.encoded() {
+                                        for encoded in input.encoded() {
                                             clients.send(input.buffer(), encoded);
                                         }
 
@@ -162,13 +226,19 @@ impl Dashboard {
                                             if let data::Buffer::Channel(server, channel) = &buffer
                                             {
                                                 if let Some(user_with_attributes) = clients
-                                                    .resolve_user_attributes(server, channel, &user)
+                                                    .resolve_user_attributes(
+                                                        server,
+                                                        channel.as_str(),
+                                                        &user,
+                                                    )
                                                 {
                                                     user = user_with_attributes.clone();
                                                 }
                                             }
 
-                                            if let Some(message) = input.message(user) {
+                                            let chantypes = clients.get_chantypes(buffer.server());
+
+                                            for message in input.messages(user, chantypes) {
                                                 self.history
                                                     .record_message(input.server(), message);
                                             }
@@ -237,6 +307,7 @@ impl Dashboard {
                     }
                 }
                 pane::Message::MaximizePane => self.maximize_pane(),
+                pane::Message::ExportHistory(range) => return self.export_history(range),
             },
             Message::Sidebar(message) => {
                 let event = self.side_menu.update(message);
@@ -270,58 +341,18 @@ impl Dashboard {
                         return self.focus_pane(from);
                     }
                     sidebar::Event::Leave(buffer) => {
-                        let pane = self.panes.iter().find_map(|(pane, state)| {
-                            (state.buffer.data().as_ref() == Some(&buffer)).then_some(*pane)
-                        });
-
-                        // Close pane
-                        if let Some(pane) = pane {
-                            if self.panes.close(pane).is_none() {
-                                if let Some(state) = self.panes.get_mut(pane) {
-                                    state.buffer = Buffer::Empty;
-                                }
-                            }
-                            self.last_changed = Some(Instant::now());
-
-                            if self.focus == Some(pane) {
-                                self.focus = None;
-                            }
-                        }
+                        self.close_buffer_pane(&buffer);
 
                         match buffer.clone() {
                             data::Buffer::Server(server) => {
-                                // Remove server connection
-
-                                // Removing from servers kills stream subscription
-                                servers.remove(&server);
-
-                                // Remove from clients pool to fully drop it
-                                let _server = server.clone();
-                                let quit = clients
-                                    .remove(&server)
-                                    .map(move |connection| async move {
-                                        connection.quit().await;
-
-                                        log::info!("[{_server}] quit");
-                                    })
-                                    .map(|task| Command::perform(task, |_| Message::QuitServer))
-                                    .unwrap_or_else(Command::none);
-
-                                // Close history for server
-                                let close_history = self
-                                    .history
-                                    .close_server(server)
-                                    .map(|task| Command::perform(task, |_| Message::CloseHistory))
-                                    .unwrap_or_else(Command::none);
-
-                                return Command::batch(vec![quit, close_history]);
+                                return self.quit_server(server, clients, servers);
                             }
                             data::Buffer::Channel(server, channel) => {
                                 // Send part & close history file
-                                let command = data::Command::Part(channel.clone(), None);
+                                let command = data::Command::Part(channel.to_string(), None);
                                 let input = data::Input::command(buffer.clone(), command);
 
-                                if let Some(encoded) = input.encoded() {
+                                for encoded in input.encoded() {
                                     clients.send(&buffer, encoded);
                                 }
 
@@ -341,16 +372,20 @@ impl Dashboard {
                             }
                         }
                     }
+                    sidebar::Event::Connect(server) => {
+                        clients.connect(&server);
+                    }
+                    sidebar::Event::Disconnect(server) => {
+                        clients.disconnect(&server);
+                    }
+                    sidebar::Event::Edit(server) => {
+                        return self.edit_server(&server, servers, config);
+                    }
                     sidebar::Event::ToggleFileTransfers => {
                         return self.toggle_file_transfers(config);
                     }
                     sidebar::Event::ToggleCommandBar => {
-                        return self.toggle_command_bar(
-                            &closed_buffers(self, clients),
-                            version,
-                            config,
-                            theme,
-                        );
+                        return self.toggle_command_bar(clients, version, config, theme);
                     }
                 }
             }
@@ -388,6 +423,9 @@ impl Dashboard {
                 log::warn!("error saving dashboard: {error}");
             }
             Message::CloseHistory => {}
+            Message::RequestChatHistory(server, kind, since) => {
+                clients.request_chathistory(&server, &kind, since);
+            }
             Message::QuitServer => {}
             Message::Command(message) => {
                 let Some(command_bar) = &mut self.command_bar else {
@@ -442,6 +480,31 @@ impl Dashboard {
                                 command_bar::Buffer::ToggleFileTransfers => {
                                     self.toggle_file_transfers(config)
                                 }
+                                command_bar::Buffer::ToggleSearch => self.toggle_search(config),
+                                command_bar::Buffer::ImportLogs => self.import_logs(),
+                                command_bar::Buffer::ExportHistory => {
+                                    self.export_history(history::export::Range::default())
+                                }
+                            },
+                            command_bar::Command::Server(command) => match command {
+                                command_bar::Server::Add => {
+                                    self.open_server_editor(ServerEditor::new(), config)
+                                }
+                                command_bar::Server::Edit(server) => {
+                                    self.edit_server(&server, servers, config)
+                                }
+                                command_bar::Server::Connect(server) => {
+                                    clients.connect(&server);
+                                    Command::none()
+                                }
+                                command_bar::Server::Disconnect(server) => {
+                                    clients.disconnect(&server);
+                                    Command::none()
+                                }
+                                command_bar::Server::Quit(server) => {
+                                    self.close_buffer_pane(&data::Buffer::Server(server.clone()));
+                                    self.quit_server(server, clients, servers)
+                                }
                             },
                             command_bar::Command::Configuration(command) => match command {
                                 command_bar::Configuration::OpenDirectory => {
@@ -469,21 +532,11 @@ impl Dashboard {
 
                         return Command::batch(vec![
                             command,
-                            self.toggle_command_bar(
-                                &closed_buffers(self, clients),
-                                version,
-                                config,
-                                theme,
-                            ),
+                            self.toggle_command_bar(clients, version, config, theme),
                         ]);
                     }
                     Some(command_bar::Event::Unfocused) => {
-                        return self.toggle_command_bar(
-                            &closed_buffers(self, clients),
-                            version,
-                            config,
-                            theme,
-                        );
+                        return self.toggle_command_bar(clients, version, config, theme);
                     }
                     None => {}
                 }
@@ -562,12 +615,19 @@ impl Dashboard {
                         }
                     }
                     CommandBar => {
-                        return self.toggle_command_bar(
-                            &closed_buffers(self, clients),
-                            version,
-                            config,
-                            theme,
-                        );
+                        return self.toggle_command_bar(clients, version, config, theme);
+                    }
+                    Format(modifier) => {
+                        // The markup would be sent as is
+                        if !config.buffer.text_input.markup {
+                            return Command::none();
+                        }
+
+                        if let Some((id, pane)) = self.get_focused_mut() {
+                            return pane.buffer.insert_markup_to_input(modifier).map(
+                                move |message| Message::Pane(pane::Message::Buffer(id, message)),
+                            );
+                        }
                     }
                 }
             }
@@ -582,12 +642,61 @@ impl Dashboard {
                             path,
                             server: server.clone(),
                             server_handle: server_handle.clone(),
+                            proxy: clients.get_server_proxy(&server).cloned(),
                         }) {
                             return self.handle_file_transfer_event(&server, event);
                         }
                     }
                 }
             }
+            Message::ImportLogsSelected(buffer, Some(paths)) => {
+                let kind = history::Resource::from(buffer.clone()).kind;
+
+                return Command::perform(history::import::read(paths, kind), move |result| {
+                    Message::LogsRead(buffer, result.map_err(|error| error.to_string()))
+                });
+            }
+            Message::ImportLogsSelected(_, None) => {}
+            Message::LogsRead(buffer, result) => {
+                let resource = history::Resource::from(buffer);
+
+                match result {
+                    Ok(messages) => {
+                        log::info!(
+                            "importing {} messages into {} on {}",
+                            messages.len(),
+                            resource.kind,
+                            resource.server
+                        );
+
+                        return Command::perform(
+                            self.history.import(resource, messages),
+                            Message::History,
+                        );
+                    }
+                    Err(error) => {
+                        log::warn!(
+                            "failed to import logs into {} on {}: {error}",
+                            resource.kind,
+                            resource.server
+                        );
+                    }
+                }
+            }
+            Message::ExportSelected(buffer, range, Some(path)) => {
+                let style = history::export::Style {
+                    text_log: config.history.text_log.clone(),
+                    nickname: config.buffer.nickname.clone(),
+                    colors: theme.colors().clone(),
+                };
+
+                return Command::perform(
+                    self.history
+                        .export(history::Resource::from(buffer), range, path, style),
+                    Message::History,
+                );
+            }
+            Message::ExportSelected(_, _, None) => {}
         }
 
         Command::none()
@@ -705,12 +814,7 @@ impl Dashboard {
                 // - Restore maximized pane
                 // - Unfocus
                 if self.command_bar.is_some() {
-                    return self.toggle_command_bar(
-                        &closed_buffers(self, clients),
-                        version,
-                        config,
-                        theme,
-                    );
+                    return self.toggle_command_bar(clients, version, config, theme);
                 } else if self.is_pane_maximized() {
                     self.panes.restore();
                 } else {
@@ -762,13 +866,55 @@ impl Dashboard {
         }
     }
 
-    // TODO: Perhaps rewrite this, i just did this quickly.
     fn toggle_file_transfers(&mut self, config: &Config) -> Command<Message> {
+        self.toggle_internal_buffer(Buffer::FileTransfers(FileTransfers::new()), config)
+    }
+
+    fn toggle_search(&mut self, config: &Config) -> Command<Message> {
+        self.toggle_internal_buffer(Buffer::Search(Search::new()), config)
+    }
+
+    fn edit_server(
+        &mut self,
+        server: &Server,
+        servers: &server::Map,
+        config: &Config,
+    ) -> Command<Message> {
+        match servers.get(server) {
+            Some(server_config) => {
+                self.open_server_editor(ServerEditor::edit(server, server_config), config)
+            }
+            None => Command::none(),
+        }
+    }
+
+    /// Opens the server editor, replacing the one already open
+    fn open_server_editor(&mut self, editor: ServerEditor, config: &Config) -> Command<Message> {
+        let open = self
+            .panes
+            .iter()
+            .find_map(|(id, pane)| matches!(pane.buffer, Buffer::ServerEditor(_)).then_some(*id));
+
+        match open {
+            Some(id) => {
+                if let Some(pane) = self.panes.get_mut(id) {
+                    pane.buffer = Buffer::ServerEditor(editor);
+                }
+
+                self.focus_pane(id)
+            }
+            None => self.toggle_internal_buffer(Buffer::ServerEditor(editor), config),
+        }
+    }
+
+    /// Opens a buffer which isn't backed by a server, such as file
+    /// transfers, or closes it if it's already open
+    fn toggle_internal_buffer(&mut self, buffer: Buffer, config: &Config) -> Command<Message> {
         let panes = self.panes.clone();
 
-        // If file transfers already is open, we close it.
+        // If the buffer already is open, we close it.
         for (id, pane) in panes.iter() {
-            if let Buffer::FileTransfers(_) = pane.buffer {
+            if std::mem::discriminant(&pane.buffer) == std::mem::discriminant(&buffer) {
                 return self.close_pane(*id);
             }
         }
@@ -777,9 +923,10 @@ impl Dashboard {
         if self.panes.len() == 1 {
             for (id, pane) in panes.iter() {
                 if let Buffer::Empty = &pane.buffer {
-                    self.panes.panes.entry(*id).and_modify(|p| {
-                        *p = Pane::new(Buffer::FileTransfers(FileTransfers::new()), config)
-                    });
+                    self.panes
+                        .panes
+                        .entry(*id)
+                        .and_modify(|p| *p = Pane::new(buffer, config));
                     self.last_changed = Some(Instant::now());
 
                     return self.focus_pane(*id);
@@ -792,7 +939,7 @@ impl Dashboard {
 
         if let Some(pane) = self.focus.take() {
             if let Some(state) = self.panes.get_mut(pane) {
-                state.buffer = Buffer::FileTransfers(FileTransfers::new());
+                state.buffer = buffer;
                 self.last_changed = Some(Instant::now());
 
                 commands.extend(vec![self.reset_pane(pane), self.focus_pane(pane)]);
@@ -802,6 +949,41 @@ impl Dashboard {
         Command::batch(commands)
     }
 
+    /// Focuses the channel's buffer, joining it first if needed
+    fn join_channel(
+        &mut self,
+        server: Server,
+        channel: String,
+        clients: &mut client::Map,
+        config: &Config,
+    ) -> Command<Message> {
+        let casemapping = clients.get_casemapping(&server);
+        let normalized = casemapping.normalize(&channel);
+
+        let joined = clients
+            .get_channels(&server)
+            .iter()
+            .find(|joined| casemapping.normalize(joined) == normalized)
+            .cloned();
+
+        let channel = match joined {
+            Some(channel) => channel,
+            None => {
+                let buffer = data::Buffer::Server(server.clone());
+                let input =
+                    data::Input::command(buffer, data::Command::Join(channel.clone(), None));
+
+                for encoded in input.encoded() {
+                    clients.send(input.buffer(), encoded);
+                }
+
+                channel
+            }
+        };
+
+        self.open_buffer(data::Buffer::Channel(server, channel.into()), config)
+    }
+
     fn open_buffer(&mut self, kind: data::Buffer, config: &Config) -> Command<Message> {
         let panes = self.panes.clone();
 
@@ -854,10 +1036,70 @@ impl Dashboard {
         Command::none()
     }
 
+    /// Opens the buffer scrolled to the message sent at `server_time`,
+    /// loading its history back to the message if needed
+    fn open_message(
+        &mut self,
+        buffer: data::Buffer,
+        server_time: DateTime<Utc>,
+        config: &Config,
+    ) -> Command<Message> {
+        let load = self
+            .history
+            .load_since(
+                &history::Resource::from(buffer.clone()),
+                server_time.date_naive(),
+            )
+            .map(|task| Command::perform(task, Message::History))
+            .unwrap_or_else(Command::none);
+
+        let open = self.open_buffer(buffer.clone(), config);
+
+        let scroll = self
+            .panes
+            .iter_mut()
+            .find(|(_, pane)| pane.buffer.data().as_ref() == Some(&buffer))
+            .map(|(id, pane)| {
+                let id = *id;
+
+                pane.buffer
+                    .scroll_to_message(server_time)
+                    .map(move |message| Message::Pane(pane::Message::Buffer(id, message)))
+            })
+            .unwrap_or_else(Command::none);
+
+        Command::batch(vec![load, open, scroll])
+    }
+
     pub fn record_message(&mut self, server: &Server, message: data::Message) {
         self.history.record_message(server, message);
     }
 
+    pub fn record_chathistory(&mut self, server: &Server, messages: Vec<data::Message>) {
+        self.history.record_chathistory(server, messages);
+    }
+
+    /// Requests playback of the messages sent since the latest one we have
+    pub fn request_chathistory(&self, server: &Server, kind: history::Kind) -> Command<Message> {
+        let server = server.clone();
+
+        Command::perform(
+            self.history.latest_server_time(&server, &kind),
+            move |since| Message::RequestChatHistory(server, kind, since),
+        )
+    }
+
+    pub fn request_queries_chathistory(&self, server: &Server) -> Command<Message> {
+        let commands = self
+            .history
+            .get_unique_queries(server)
+            .into_iter()
+            .map(|nick| self.request_chathistory(server, history::Kind::Query(nick.clone())))
+            .collect::<Vec<_>>();
+
+        Command::batch(commands)
+    }
+
     pub fn broadcast_quit(
         &mut self,
         server: &Server,
@@ -936,11 +1178,28 @@ impl Dashboard {
     pub fn broadcast_connected(
         &mut self,
         server: &Server,
+        address: String,
         config: &Config,
         sent_time: DateTime<Utc>,
     ) {
         self.history
-            .broadcast(server, Broadcast::Connected, config, sent_time);
+            .broadcast(server, Broadcast::Connected { address }, config, sent_time);
+    }
+
+    pub fn broadcast_sasl_failed(
+        &mut self,
+        server: &Server,
+        error: String,
+        disconnect: bool,
+        config: &Config,
+        sent_time: DateTime<Utc>,
+    ) {
+        self.history.broadcast(
+            server,
+            Broadcast::SaslFailed { error, disconnect },
+            config,
+            sent_time,
+        );
     }
 
     pub fn broadcast_disconnected(
@@ -957,28 +1216,99 @@ impl Dashboard {
     pub fn broadcast_reconnected(
         &mut self,
         server: &Server,
+        address: String,
         config: &Config,
         sent_time: DateTime<Utc>,
     ) {
-        self.history
-            .broadcast(server, Broadcast::Reconnected, config, sent_time);
+        self.history.broadcast(
+            server,
+            Broadcast::Reconnected { address },
+            config,
+            sent_time,
+        );
     }
 
     pub fn broadcast_connection_failed(
         &mut self,
         server: &Server,
+        address: String,
         error: String,
         config: &Config,
         sent_time: DateTime<Utc>,
     ) {
         self.history.broadcast(
             server,
-            Broadcast::ConnectionFailed { error },
+            Broadcast::ConnectionFailed { address, error },
             config,
             sent_time,
         );
     }
 
+    /// Prompts for logs of other clients to import into the focused buffer
+    fn import_logs(&self) -> Command<Message> {
+        let Some(buffer) = self
+            .focus
+            .and_then(|pane| self.panes.get(pane))
+            .and_then(|pane| pane.buffer.data())
+        else {
+            return Command::none();
+        };
+
+        Command::perform(
+            async move {
+                rfd::AsyncFileDialog::new()
+                    .set_title("Import logs")
+                    .pick_files()
+                    .await
+                    .map(|handles| {
+                        handles
+                            .into_iter()
+                            .map(|handle| handle.path().to_path_buf())
+                            .collect()
+                    })
+            },
+            move |paths| Message::ImportLogsSelected(buffer, paths),
+        )
+    }
+
+    /// Prompts for a file to export the history of the focused buffer to,
+    /// in the format of its extension
+    fn export_history(&self, range: history::export::Range) -> Command<Message> {
+        let Some(buffer) = self
+            .focus
+            .and_then(|pane| self.panes.get(pane))
+            .and_then(|pane| pane.buffer.data())
+        else {
+            return Command::none();
+        };
+
+        let file_name = match &buffer {
+            data::Buffer::Server(server) => server.to_string(),
+            data::Buffer::Channel(server, channel) => format!("{server}-{channel}"),
+            data::Buffer::Query(server, nick) => format!("{server}-{nick}"),
+        };
+
+        Command::perform(
+            async move {
+                let dialog = history::export::Format::ALL.into_iter().fold(
+                    rfd::AsyncFileDialog::new()
+                        .set_title("Export history")
+                        .set_file_name(format!(
+                            "{file_name}.{}",
+                            history::export::Format::Html.extension()
+                        )),
+                    |dialog, format| dialog.add_filter(format.to_string(), &[format.extension()]),
+                );
+
+                dialog
+                    .save_file()
+                    .await
+                    .map(|handle| handle.path().to_path_buf())
+            },
+            move |path| Message::ExportSelected(buffer, range, path),
+        )
+    }
+
     fn get_focused_mut(&mut self) -> Option<(pane_grid::Pane, &mut Pane)> {
         let pane = self.focus?;
         self.panes.get_mut(pane).map(|state| (pane, state))
@@ -1109,6 +1439,13 @@ impl Dashboard {
         )
     }
 
+    fn prune_history(&self, config: &Config) -> Command<Message> {
+        self.history
+            .prune(&config.history)
+            .map(|task| Command::perform(task, Message::History))
+            .unwrap_or_else(Command::none)
+    }
+
     pub fn tick(&mut self, now: Instant) -> Command<Message> {
         let history = Command::batch(
             self.history
@@ -1136,7 +1473,7 @@ impl Dashboard {
 
     pub fn toggle_command_bar(
         &mut self,
-        buffers: &[data::Buffer],
+        clients: &client::Map,
         version: &Version,
         config: &Config,
         theme: &mut Theme,
@@ -1152,14 +1489,15 @@ impl Dashboard {
                 .map(|pane| self.focus_pane(pane))
                 .unwrap_or(Command::none())
         } else {
-            self.open_command_bar(buffers, version, config);
+            self.open_command_bar(clients, version, config);
             Command::none()
         }
     }
 
-    fn open_command_bar(&mut self, buffers: &[data::Buffer], version: &Version, config: &Config) {
+    fn open_command_bar(&mut self, clients: &client::Map, version: &Version, config: &Config) {
         self.command_bar = Some(CommandBar::new(
-            buffers,
+            &closed_buffers(self, clients),
+            clients,
             version,
             config,
             self.focus.is_some(),
@@ -1167,6 +1505,110 @@ impl Dashboard {
         ));
     }
 
+    /// Applies the `changes` to servers of a reloaded config, reconnecting
+    /// only the servers whose connection settings changed
+    pub fn reload_config(
+        &mut self,
+        changes: Vec<server::Change>,
+        clients: &mut client::Map,
+        servers: &mut server::Map,
+        config: &Config,
+    ) -> Command<Message> {
+        self.history.configure(&config.history);
+        self.file_transfers.configure(config.file_transfer.clone());
+
+        let mut commands = vec![];
+
+        for change in changes {
+            match change {
+                server::Change::Added(server, new) => {
+                    if servers.get(&server).is_some() {
+                        reconfigure_server(server, new, clients, servers);
+                    } else {
+                        log::info!("[{server}] added to config");
+
+                        // Inserting starts its stream
+                        servers.insert(server, new);
+                    }
+                }
+                // Servers quit by the user stay quit
+                server::Change::Edited(server, new) => {
+                    if servers.get(&server).is_some() {
+                        reconfigure_server(server, new, clients, servers);
+                    }
+                }
+                server::Change::Removed(server) => {
+                    log::info!("[{server}] removed from config");
+
+                    let buffers = self
+                        .panes
+                        .iter()
+                        .filter_map(|(_, state)| state.buffer.data())
+                        .filter(|buffer| buffer.server() == &server)
+                        .collect::<Vec<_>>();
+
+                    for buffer in &buffers {
+                        self.close_buffer_pane(buffer);
+                    }
+                    commands.push(self.quit_server(server, clients, servers));
+                }
+            }
+        }
+
+        Command::batch(commands)
+    }
+
+    /// Closes the pane showing `buffer`, or empties it if it's the last pane
+    fn close_buffer_pane(&mut self, buffer: &data::Buffer) {
+        let pane = self.panes.iter().find_map(|(pane, state)| {
+            (state.buffer.data().as_ref() == Some(buffer)).then_some(*pane)
+        });
+
+        if let Some(pane) = pane {
+            if self.panes.close(pane).is_none() {
+                if let Some(state) = self.panes.get_mut(pane) {
+                    state.buffer = Buffer::Empty;
+                }
+            }
+            self.last_changed = Some(Instant::now());
+
+            if self.focus == Some(pane) {
+                self.focus = None;
+            }
+        }
+    }
+
+    fn quit_server(
+        &mut self,
+        server: Server,
+        clients: &mut client::Map,
+        servers: &mut server::Map,
+    ) -> Command<Message> {
+        // Removing from servers kills stream subscription
+        servers.remove(&server);
+
+        // Remove from clients pool to fully drop it
+        let _server = server.clone();
+        let quit = clients
+            .remove(&server)
+            .map(move |connection| async move {
+                connection.quit().await;
+
+                log::info!("[{_server}] quit");
+            })
+            .map(|task| Command::perform(task, |_| Message::QuitServer))
+            .unwrap_or_else(Command::none);
+
+        // Close history for server
+        let close_history = self
+            .history
+            .close_server(server)
+            .map(|task| Command::perform(task, |_| Message::CloseHistory))
+            .unwrap_or_else(Command::none);
+
+        Command::batch(vec![quit, close_history])
+    }
+
     fn close_command_bar(&mut self) {
         self.command_bar = None;
     }
@@ -1255,6 +1697,10 @@ impl Dashboard {
                     Buffer::FileTransfers(FileTransfers::new()),
                     buffer::Settings::default(),
                 )),
+                data::Pane::Search => Configuration::Pane(Pane::with_settings(
+                    Buffer::Search(Search::new()),
+                    buffer::Settings::default(),
+                )),
             }
         }
 
@@ -1262,7 +1708,7 @@ impl Dashboard {
             panes: pane_grid::State::with_configuration(configuration(dashboard.pane)),
             focus: None,
             side_menu: Sidebar::new(),
-            history: history::Manager::default(),
+            history: history::Manager::new(&config.history),
             last_changed: None,
             command_bar: None,
             file_transfers: file_transfer::Manager::new(config.file_transfer.clone()),
@@ -1309,10 +1755,9 @@ fn all_buffers(clients: &client::Map, history: &history::Manager) -> Vec<data::B
         .flat_map(|server| {
             std::iter::once(data::Buffer::Server(server.clone()))
                 .chain(
-                    clients
-                        .get_channels(server)
-                        .iter()
-                        .map(|channel| data::Buffer::Channel(server.clone(), channel.clone())),
+                    clients.get_channels(server).iter().map(|channel| {
+                        data::Buffer::Channel(server.clone(), channel.as_str().into())
+                    }),
                 )
                 .chain(
                     history
@@ -1373,3 +1818,29 @@ fn cycle_previous_buffer(
 
     previous().or_else(|| all.last()).cloned()
 }
+
+/// Applies the reloaded config of a running server
+fn reconfigure_server(
+    server: Server,
+    config: data::config::Server,
+    clients: &mut client::Map,
+    servers: &mut server::Map,
+) {
+    let Some(current) = servers.get(&server) else {
+        return;
+    };
+
+    // Already applied when saved from the server editor
+    if current == &config {
+        return;
+    }
+
+    if current.is_connection_changed(&config) {
+        log::info!("[{server}] reconnecting w/ reloaded config");
+        clients.configure(&server, config.clone());
+    } else {
+        clients.update_config(&server, config.clone());
+    }
+
+    servers.insert(server, config);
+}
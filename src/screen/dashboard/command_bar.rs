@@ -21,6 +21,7 @@ pub enum Message {
 impl CommandBar {
     pub fn new(
         buffers: &[data::Buffer],
+        clients: &data::client::Map,
         version: &data::Version,
         config: &Config,
         is_focused_buffer: bool,
@@ -28,6 +29,7 @@ impl CommandBar {
     ) -> Self {
         let state = combo_box::State::new(Command::list(
             buffers,
+            clients,
             config,
             is_focused_buffer,
             resize_buffer,
@@ -108,6 +110,7 @@ pub enum Event {
 pub enum Command {
     Version(Version),
     Buffer(Buffer),
+    Server(Server),
     Configuration(Configuration),
     UI(Ui),
     Theme(Theme),
@@ -125,6 +128,18 @@ pub enum Buffer {
     Close,
     Replace(data::Buffer),
     ToggleFileTransfers,
+    ToggleSearch,
+    ImportLogs,
+    ExportHistory,
+}
+
+#[derive(Debug, Clone)]
+pub enum Server {
+    Add,
+    Edit(data::Server),
+    Connect(data::Server),
+    Disconnect(data::Server),
+    Quit(data::Server),
 }
 
 #[derive(Debug, Clone)]
@@ -146,6 +161,7 @@ pub enum Theme {
 impl Command {
     pub fn list(
         buffers: &[data::Buffer],
+        clients: &data::client::Map,
         config: &Config,
         is_focused_buffer: bool,
         resize_buffer: data::buffer::Resize,
@@ -155,6 +171,8 @@ impl Command {
             .into_iter()
             .map(Command::Buffer);
 
+        let servers = Server::list(clients).into_iter().map(Command::Server);
+
         let configs = Configuration::list()
             .into_iter()
             .map(Command::Configuration);
@@ -167,6 +185,7 @@ impl Command {
 
         version
             .chain(buffers)
+            .chain(servers)
             .chain(configs)
             .chain(themes)
             .chain(uis)
@@ -178,6 +197,7 @@ impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Command::Buffer(buffer) => write!(f, "Buffer: {}", buffer),
+            Command::Server(server) => write!(f, "Server: {}", server),
             Command::Configuration(config) => write!(f, "Configuration: {}", config),
             Command::UI(ui) => write!(f, "UI: {}", ui),
             Command::Theme(theme) => write!(f, "Theme: {}", theme),
@@ -192,10 +212,16 @@ impl Buffer {
         is_focused_buffer: bool,
         resize_buffer: data::buffer::Resize,
     ) -> Vec<Self> {
-        let mut list = vec![Buffer::New, Buffer::ToggleFileTransfers];
+        let mut list = vec![
+            Buffer::New,
+            Buffer::ToggleFileTransfers,
+            Buffer::ToggleSearch,
+        ];
 
         if is_focused_buffer {
             list.push(Buffer::Close);
+            list.push(Buffer::ImportLogs);
+            list.push(Buffer::ExportHistory);
 
             match resize_buffer {
                 data::buffer::Resize::Maximize => list.push(Buffer::Maximize(true)),
@@ -216,6 +242,27 @@ impl Version {
     }
 }
 
+impl Server {
+    fn list(clients: &data::client::Map) -> Vec<Self> {
+        let servers = clients.iter().flat_map(|(server, state)| match state {
+            data::client::State::Offline => {
+                vec![
+                    Server::Connect(server.clone()),
+                    Server::Edit(server.clone()),
+                    Server::Quit(server.clone()),
+                ]
+            }
+            data::client::State::Disconnected | data::client::State::Ready(_) => vec![
+                Server::Disconnect(server.clone()),
+                Server::Edit(server.clone()),
+                Server::Quit(server.clone()),
+            ],
+        });
+
+        std::iter::once(Server::Add).chain(servers).collect()
+    }
+}
+
 impl Configuration {
     fn list() -> Vec<Self> {
         vec![Configuration::OpenDirectory, Configuration::OpenWebsite]
@@ -281,6 +328,21 @@ impl std::fmt::Display for Buffer {
                 data::Buffer::Query(_, nick) => write!(f, "Change to {}", nick),
             },
             Buffer::ToggleFileTransfers => write!(f, "Toggle File Transfers"),
+            Buffer::ToggleSearch => write!(f, "Toggle Search"),
+            Buffer::ImportLogs => write!(f, "Import logs into buffer"),
+            Buffer::ExportHistory => write!(f, "Export buffer history"),
+        }
+    }
+}
+
+impl std::fmt::Display for Server {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Server::Add => write!(f, "Add server"),
+            Server::Edit(server) => write!(f, "Edit {}", server),
+            Server::Connect(server) => write!(f, "Connect to {}", server),
+            Server::Disconnect(server) => write!(f, "Disconnect from {}", server),
+            Server::Quit(server) => write!(f, "Quit {}", server),
         }
     }
 }
@@ -1,10 +1,11 @@
+use data::history::export;
 use data::{file_transfer, history, Config};
 use iced::widget::{button, container, pane_grid, row, text};
 use iced::Length;
 use uuid::Uuid;
 
 use crate::buffer::{self, Buffer};
-use crate::widget::tooltip;
+use crate::widget::{context_menu, tooltip};
 use crate::{icon, theme, widget};
 
 #[derive(Debug, Clone)]
@@ -18,6 +19,7 @@ pub enum Message {
     MaximizePane,
     ToggleShowUserList,
     ToggleShowTopic,
+    ExportHistory(export::Range),
 }
 
 #[derive(Clone)]
@@ -75,6 +77,8 @@ impl Pane {
                 format!("{nick} @ {server}")
             }
             Buffer::FileTransfers(_) => "File Transfers".to_string(),
+            Buffer::Search(_) => "Search".to_string(),
+            Buffer::ServerEditor(state) => state.title(),
         };
 
         let title_bar = self.title_bar.view(
@@ -115,7 +119,7 @@ impl Pane {
             Buffer::Empty => None,
             Buffer::Channel(channel) => Some(history::Resource {
                 server: channel.server.clone(),
-                kind: history::Kind::Channel(channel.channel.clone()),
+                kind: history::Kind::Channel(channel.channel.as_str().into()),
             }),
             Buffer::Server(server) => Some(history::Resource {
                 server: server.server.clone(),
@@ -125,7 +129,7 @@ impl Pane {
                 server: query.server.clone(),
                 kind: history::Kind::Query(query.nick.clone()),
             }),
-            Buffer::FileTransfers(_) => None,
+            Buffer::FileTransfers(_) | Buffer::Search(_) | Buffer::ServerEditor(_) => None,
         }
     }
 
@@ -203,6 +207,47 @@ impl TitleBar {
             controls = controls.push(nicklist_button_with_tooltip);
         }
 
+        // Export history of buffers w/ history, all of it on click or the
+        // last days from the context menu
+        if matches!(
+            buffer,
+            Buffer::Channel(_) | Buffer::Server(_) | Buffer::Query(_)
+        ) {
+            let export_button = button(
+                container(icon::export())
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+                    .center_x()
+                    .center_y(),
+            )
+            .padding(5)
+            .width(22)
+            .height(22)
+            .on_press(Message::ExportHistory(export::Range::default()))
+            .style(|theme, status| theme::button::tertiary(theme, status, false));
+
+            let export_button_with_tooltip = tooltip(
+                export_button,
+                show_tooltips.then_some("Export History"),
+                tooltip::Position::Bottom,
+            );
+
+            let export_menu = context_menu(
+                export_button_with_tooltip,
+                ExportEntry::ALL.to_vec(),
+                |entry, length| {
+                    button(text(entry.to_string()).style(theme::text::primary))
+                        .width(length)
+                        .padding(5)
+                        .style(theme::button::context)
+                        .on_press(Message::ExportHistory(entry.range()))
+                        .into()
+                },
+            );
+
+            controls = controls.push(export_menu);
+        }
+
         // If we have more than one pane open, show maximize button.
         if panes > 1 {
             let maximize_button = button(
@@ -261,23 +306,82 @@ impl TitleBar {
             controls = controls.push(close_button_with_tooltip);
         }
 
-        let title = container(text(value).style(theme::text::transparent))
-            .height(22)
-            .padding([0, 4])
-            .align_y(iced::alignment::Vertical::Center);
+        let (lag, queued) = match buffer {
+            Buffer::Server(state) => (clients.lag(&state.server), clients.queue_len(&state.server)),
+            _ => (None, 0),
+        };
+
+        let title = container(
+            row![text(value).style(theme::text::transparent)]
+                .push_maybe(lag.map(|lag| {
+                    text(format!("{lag} lag")).style(if lag.is_degraded {
+                        theme::text::alert
+                    } else {
+                        theme::text::transparent
+                    })
+                }))
+                .push_maybe(
+                    (queued > 0)
+                        .then(|| text(format!("{queued} queued")).style(theme::text::transparent)),
+                )
+                .spacing(8),
+        )
+        .height(22)
+        .padding([0, 4])
+        .align_y(iced::alignment::Vertical::Center);
 
         widget::TitleBar::new(title).controls(controls).padding(6)
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+enum ExportEntry {
+    Today,
+    LastWeek,
+    LastMonth,
+    All,
+}
+
+impl ExportEntry {
+    const ALL: [ExportEntry; 4] = [
+        ExportEntry::Today,
+        ExportEntry::LastWeek,
+        ExportEntry::LastMonth,
+        ExportEntry::All,
+    ];
+
+    fn range(self) -> export::Range {
+        match self {
+            ExportEntry::Today => export::Range::last_days(1),
+            ExportEntry::LastWeek => export::Range::last_days(7),
+            ExportEntry::LastMonth => export::Range::last_days(30),
+            ExportEntry::All => export::Range::default(),
+        }
+    }
+}
+
+impl std::fmt::Display for ExportEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportEntry::Today => write!(f, "Export today"),
+            ExportEntry::LastWeek => write!(f, "Export last 7 days"),
+            ExportEntry::LastMonth => write!(f, "Export last 30 days"),
+            ExportEntry::All => write!(f, "Export all history"),
+        }
+    }
+}
+
 impl From<Pane> for data::Pane {
     fn from(pane: Pane) -> Self {
         let buffer = match pane.buffer {
             Buffer::Empty => return data::Pane::Empty,
-            Buffer::Channel(state) => data::Buffer::Channel(state.server, state.channel),
+            Buffer::Channel(state) => data::Buffer::Channel(state.server, state.channel.into()),
             Buffer::Server(state) => data::Buffer::Server(state.server),
             Buffer::Query(state) => data::Buffer::Query(state.server, state.nick),
             Buffer::FileTransfers(_) => return data::Pane::FileTransfers,
+            Buffer::Search(_) => return data::Pane::Search,
+            // Edits aren't kept across restarts
+            Buffer::ServerEditor(_) => return data::Pane::Empty,
         };
 
         data::Pane::Buffer {
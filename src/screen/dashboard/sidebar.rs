@@ -1,5 +1,6 @@
+use data::client::{Lag, Status};
 use data::dashboard::DefaultAction;
-use data::{file_transfer, history, Buffer};
+use data::{file_transfer, history, Buffer, Server};
 use iced::widget::{
     button, column, container, horizontal_space, pane_grid, row, scrollable, text, vertical_space,
     Scrollable,
@@ -17,6 +18,9 @@ pub enum Message {
     Close(pane_grid::Pane),
     Swap(pane_grid::Pane, pane_grid::Pane),
     Leave(Buffer),
+    Connect(Server),
+    Disconnect(Server),
+    Edit(Server),
     ToggleFileTransfers,
     ToggleCommandBar,
 }
@@ -28,6 +32,9 @@ pub enum Event {
     Close(pane_grid::Pane),
     Swap(pane_grid::Pane, pane_grid::Pane),
     Leave(Buffer),
+    Connect(Server),
+    Disconnect(Server),
+    Edit(Server),
     ToggleFileTransfers,
     ToggleCommandBar,
 }
@@ -53,6 +60,9 @@ impl Sidebar {
             Message::Close(pane) => Event::Close(pane),
             Message::Swap(from, to) => Event::Swap(from, to),
             Message::Leave(buffer) => Event::Leave(buffer),
+            Message::Connect(server) => Event::Connect(server),
+            Message::Disconnect(server) => Event::Disconnect(server),
+            Message::Edit(server) => Event::Edit(server),
             Message::ToggleFileTransfers => Event::ToggleFileTransfers,
             Message::ToggleCommandBar => Event::ToggleCommandBar,
         }
@@ -81,7 +91,21 @@ impl Sidebar {
                         panes,
                         focus,
                         Buffer::Server(server.clone()),
+                        Status::Disconnected,
+                        None,
+                        0,
                         false,
+                        config.default_action,
+                    ));
+                }
+                data::client::State::Offline => {
+                    column = column.push(buffer_button(
+                        panes,
+                        focus,
+                        Buffer::Server(server.clone()),
+                        Status::Offline,
+                        None,
+                        0,
                         false,
                         config.default_action,
                     ));
@@ -91,7 +115,9 @@ impl Sidebar {
                         panes,
                         focus,
                         Buffer::Server(server.clone()),
-                        true,
+                        Status::Connected,
+                        connection.lag(),
+                        connection.queue_len(),
                         false,
                         config.default_action,
                     ));
@@ -100,9 +126,14 @@ impl Sidebar {
                         column = column.push(buffer_button(
                             panes,
                             focus,
-                            Buffer::Channel(server.clone(), channel.clone()),
-                            true,
-                            history.has_unread(server, &history::Kind::Channel(channel.clone())),
+                            Buffer::Channel(server.clone(), channel.as_str().into()),
+                            Status::Connected,
+                            None,
+                            0,
+                            history.has_unread(
+                                server,
+                                &history::Kind::Channel(channel.as_str().into()),
+                            ),
                             config.default_action,
                         ));
                     }
@@ -113,7 +144,9 @@ impl Sidebar {
                             panes,
                             focus,
                             Buffer::Query(server.clone(), user.clone()),
-                            true,
+                            Status::Connected,
+                            None,
+                            0,
                             history.has_unread(server, &history::Kind::Query(user.clone())),
                             config.default_action,
                         ));
@@ -212,6 +245,10 @@ enum Entry {
     Replace(pane_grid::Pane),
     Close(pane_grid::Pane),
     Swap(pane_grid::Pane, pane_grid::Pane),
+    Connect,
+    Reconnect,
+    Disconnect,
+    Edit,
     Leave,
 }
 
@@ -243,7 +280,9 @@ fn buffer_button<'a>(
     panes: &pane_grid::State<Pane>,
     focus: Option<pane_grid::Pane>,
     buffer: Buffer,
-    connected: bool,
+    status: Status,
+    lag: Option<Lag>,
+    queued: usize,
     has_unread: bool,
     default_action: DefaultAction,
 ) -> Element<'a, Message> {
@@ -253,20 +292,34 @@ fn buffer_button<'a>(
 
     let row = match &buffer {
         Buffer::Server(server) => row![
-            if connected {
+            if status.connected() {
                 icon::globe()
             } else {
                 icon::wifi_off()
             },
-            text(server.to_string()).style(theme::text::primary)
+            text(server.to_string()).style(if let Status::Offline = status {
+                theme::text::transparent
+            } else {
+                theme::text::primary
+            })
         ]
+        .push_maybe(lag.map(|lag| {
+            text(lag.to_string()).style(if lag.is_degraded {
+                theme::text::alert
+            } else {
+                theme::text::transparent
+            })
+        }))
+        .push_maybe(
+            (queued > 0).then(|| text(format!("{queued} queued")).style(theme::text::transparent)),
+        )
         .spacing(8)
         .align_items(iced::Alignment::Center),
         Buffer::Channel(_, channel) => row![]
             .push(horizontal_space().width(3))
             .push_maybe(has_unread.then_some(icon::dot().size(6).style(theme::text::info)))
             .push(horizontal_space().width(if has_unread { 10 } else { 16 }))
-            .push(text(channel.clone()).style(theme::text::primary))
+            .push(text(channel.to_string()).style(theme::text::primary))
             .align_items(iced::Alignment::Center),
         Buffer::Query(_, nick) => row![]
             .push(horizontal_space().width(3))
@@ -292,9 +345,24 @@ fn buffer_button<'a>(
             },
         });
 
-    let entries = Entry::list(panes.len(), open, focus);
+    let mut entries = match (&buffer, status) {
+        (_, Status::Connected) => Entry::list(panes.len(), open, focus),
+        (Buffer::Server(_), _) => vec![Entry::Leave],
+        _ => vec![],
+    };
+
+    // The connection is controlled from the server, even while disconnected
+    if let Buffer::Server(_) = &buffer {
+        let connection = match status {
+            Status::Offline => vec![Entry::Connect, Entry::Edit],
+            _ => vec![Entry::Reconnect, Entry::Disconnect, Entry::Edit],
+        };
+        let before_leave = entries.len().saturating_sub(1);
+
+        entries.splice(before_leave..before_leave, connection);
+    }
 
-    if entries.is_empty() || !connected {
+    if entries.is_empty() {
         base.into()
     } else {
         context_menu(base, entries, move |entry, length| {
@@ -306,6 +374,10 @@ fn buffer_button<'a>(
                 ),
                 Entry::Close(pane) => ("Close pane", Message::Close(pane)),
                 Entry::Swap(from, to) => ("Swap with current pane", Message::Swap(from, to)),
+                Entry::Connect => ("Connect", Message::Connect(buffer.server().clone())),
+                Entry::Reconnect => ("Reconnect", Message::Connect(buffer.server().clone())),
+                Entry::Disconnect => ("Disconnect", Message::Disconnect(buffer.server().clone())),
+                Entry::Edit => ("Edit server", Message::Edit(buffer.server().clone())),
                 Entry::Leave => (
                     match &buffer {
                         Buffer::Server(_) => "Leave server",
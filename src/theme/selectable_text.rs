@@ -1,3 +1,4 @@
+use data::formatting::Formatting;
 use data::message;
 
 use crate::widget::selectable_text::{Catalog, Style, StyleFn};
@@ -8,10 +9,7 @@ impl Catalog for Theme {
     type Class<'a> = StyleFn<'a, Self>;
 
     fn default<'a>() -> Self::Class<'a> {
-        Box::new(|theme| Style {
-            color: None,
-            selection_color: theme.colors().accent.high_alpha,
-        })
+        Box::new(none)
     }
 
     fn style(&self, class: &Self::Class<'_>) -> Style {
@@ -19,6 +17,13 @@ impl Catalog for Theme {
     }
 }
 
+pub fn none(theme: &Theme) -> Style {
+    Style {
+        color: None,
+        selection_color: theme.colors().accent.high_alpha,
+    }
+}
+
 pub fn transparent(theme: &Theme) -> Style {
     let color = text::transparent(theme).color;
 
@@ -46,6 +51,13 @@ pub fn accent(theme: &Theme) -> Style {
     }
 }
 
+pub fn link(theme: &Theme) -> Style {
+    Style {
+        color: Some(theme.colors().action.base),
+        selection_color: theme.colors().accent.high_alpha,
+    }
+}
+
 pub fn nickname(theme: &Theme, seed: Option<String>, transparent: bool) -> Style {
     let color = text::nickname(theme, seed, transparent).color;
 
@@ -66,3 +78,12 @@ pub fn status(theme: &Theme, status: message::source::Status) -> Style {
         selection_color: theme.colors().accent.high_alpha,
     }
 }
+
+pub fn formatted(theme: &Theme, formatting: &Formatting, base: Style) -> Style {
+    let (color, _) = data::theme::formatting_colors(formatting, theme.colors());
+
+    Style {
+        color: color.or(base.color),
+        ..base
+    }
+}
@@ -19,6 +19,7 @@ mod double_pass;
 pub mod hover;
 pub mod input;
 pub mod key_press;
+pub mod message_content;
 pub mod selectable_text;
 pub mod shortcut;
 pub mod tooltip;
@@ -11,6 +11,22 @@ pub fn context_menu<'a, T, Message>(
     entries: Vec<T>,
     view: impl Fn(T, Length) -> Element<'a, Message> + 'a,
 ) -> Element<'a, Message>
+where
+    Message: 'a,
+    T: 'a + Copy,
+{
+    ContextMenu {
+        base: base.into(),
+        menu: menu(entries, view),
+    }
+    .into()
+}
+
+/// A menu of `entries`, as large as its widest entry
+pub(super) fn menu<'a, T, Message>(
+    entries: Vec<T>,
+    view: impl Fn(T, Length) -> Element<'a, Message> + 'a,
+) -> Element<'a, Message>
 where
     Message: 'a,
     T: 'a + Copy,
@@ -23,16 +39,26 @@ where
         .style(theme::container::context)
     };
 
-    let menu = double_pass(
+    double_pass(
         build_menu(Length::Shrink, &view),
         build_menu(Length::Fill, &view),
-    );
+    )
+}
 
-    ContextMenu {
-        base: base.into(),
-        menu,
-    }
-    .into()
+/// Shows `menu` at `position` until an entry is pressed or a click lands
+/// outside of it
+pub(super) fn overlay<'a, 'b, Message>(
+    menu: &'b mut Element<'a, Message>,
+    tree: &'b mut widget::Tree,
+    state: &'b mut State,
+    position: Point,
+) -> overlay::Element<'b, Message, Theme, Renderer> {
+    overlay::Element::new(Box::new(Overlay {
+        content: menu,
+        tree,
+        state,
+        position,
+    }))
 }
 
 struct ContextMenu<'a, Message> {
@@ -40,14 +66,15 @@ struct ContextMenu<'a, Message> {
     menu: Element<'a, Message>,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum State {
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) enum State {
+    #[default]
     Closed,
     Open(Point),
 }
 
 impl State {
-    fn open(self) -> Option<Point> {
+    pub(super) fn open(self) -> Option<Point> {
         match self {
             State::Closed => None,
             State::Open(point) => Some(point),
@@ -186,12 +213,12 @@ impl<'a, Message> Widget<Message, Theme, Renderer> for ContextMenu<'a, Message>
             .overlay(&mut first[0], layout, renderer, translation);
 
         let overlay = state.open().map(|position| {
-            overlay::Element::new(Box::new(Overlay {
-                content: &mut self.menu,
-                tree: &mut second[0],
+            self::overlay(
+                &mut self.menu,
+                &mut second[0],
                 state,
-                position: position + translation,
-            }))
+                position + translation,
+            )
         });
 
         Some(overlay::Group::with_children(base.into_iter().chain(overlay).collect()).overlay())
@@ -1,15 +1,18 @@
+use data::isupport::ISupport;
 use data::user::User;
 use data::{input, Buffer, Command};
 use iced::advanced::widget::{self, Operation};
-pub use iced::widget::text_input::{focus, move_cursor_to_end};
+pub use iced::widget::text_input::{focus, move_cursor_to, move_cursor_to_end};
 use iced::widget::{component, container, row, text, text_input, Component};
 use iced::Rectangle;
 
 use self::completion::Completion;
-use super::{anchored_overlay, key_press, Element, Renderer};
+pub use self::cursor::get as get_cursor;
+use super::{anchored_overlay, key_press, message_content, Element, Renderer};
 use crate::theme::{self, Theme};
 
 mod completion;
+mod cursor;
 
 pub type Id = text_input::Id;
 
@@ -20,6 +23,9 @@ pub fn input<'a, Message>(
     history: &'a [String],
     users: &'a [User],
     channels: &'a [String],
+    isupport: Option<&'a ISupport>,
+    our_user: Option<User>,
+    markup: bool,
     buffer_focused: bool,
     disabled: bool,
     on_input: impl Fn(input::Draft) -> Message + 'a,
@@ -36,6 +42,9 @@ where
         users,
         channels,
         history,
+        isupport,
+        our_user,
+        markup,
         buffer_focused,
         disabled,
         on_input: Box::new(on_input),
@@ -67,6 +76,9 @@ pub struct Input<'a, Message> {
     users: &'a [User],
     channels: &'a [String],
     history: &'a [String],
+    isupport: Option<&'a ISupport>,
+    our_user: Option<User>,
+    markup: bool,
     buffer_focused: bool,
     disabled: bool,
     on_input: Box<dyn Fn(data::input::Draft) -> Message + 'a>,
@@ -119,8 +131,15 @@ where
                 } else if !self.input.is_empty() {
                     state.completion.reset();
 
-                    // Parse input
-                    let input = match input::parse(self.buffer.clone(), self.input) {
+                    // Parse input, w/o any server limits if we aren't connected
+                    let default = ISupport::default();
+                    let input = match input::parse(
+                        self.buffer.clone(),
+                        self.input,
+                        self.our_user.as_ref(),
+                        self.isupport.unwrap_or(&default),
+                        self.markup,
+                    ) {
                         Ok(input) => input,
                         Err(error) => {
                             state.error = Some(error.to_string());
@@ -218,7 +237,7 @@ where
 
         // Add tab support
         let mut input = key_press(
-            text_input,
+            cursor::track(text_input, self.id.clone(), self.input),
             key_press::Key::Named(key_press::Named::Tab),
             key_press::Modifiers::default(),
             Event::Tab,
@@ -244,6 +263,12 @@ where
             .as_deref()
             .map(error)
             .or_else(|| state.completion.view(self.input))
+            .or_else(|| {
+                self.markup
+                    .then(|| input::preview(&self.buffer, self.input))
+                    .flatten()
+                    .map(preview)
+            })
             .unwrap_or_else(|| row![].into());
 
         anchored_overlay(input, overlay, anchored_overlay::Anchor::AboveTop, 4.0)
@@ -262,6 +287,17 @@ fn error<'a, 'b, Message: 'a>(error: &'b str) -> Element<'a, Message> {
         .into()
 }
 
+fn preview<'a, Message: 'a>(text: String) -> Element<'a, Message> {
+    container(message_content::formatted(
+        &text,
+        theme::selectable_text::none,
+    ))
+    .center_y()
+    .padding(8)
+    .style(theme::container::context)
+    .into()
+}
+
 impl<'a, Message> From<Input<'a, Message>> for Element<'a, Message>
 where
     Message: 'a + Clone,
@@ -426,6 +426,36 @@ static COMMAND_LIST: Lazy<Vec<Command>> = Lazy::new(|| {
                 },
             ],
         },
+        Command {
+            title: "NOTICE",
+            args: vec![
+                Arg {
+                    text: "target",
+                    optional: false,
+                },
+                Arg {
+                    text: "text",
+                    optional: false,
+                },
+            ],
+        },
+        Command {
+            title: "CTCP",
+            args: vec![
+                Arg {
+                    text: "target",
+                    optional: false,
+                },
+                Arg {
+                    text: "command",
+                    optional: false,
+                },
+                Arg {
+                    text: "params",
+                    optional: true,
+                },
+            ],
+        },
         Command {
             title: "WHOIS",
             args: vec![Arg {
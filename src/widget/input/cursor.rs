@@ -0,0 +1,194 @@
+use data::input::Cursor;
+use iced::advanced::text;
+use iced::advanced::widget::{operation, tree, Operation};
+use iced::advanced::{layout, overlay, renderer, widget, Clipboard, Layout, Shell, Widget};
+use iced::widget::text_input::{self, cursor, Value};
+use iced::{event, mouse, Command, Event, Length, Rectangle, Size, Vector};
+
+use super::Id;
+use crate::widget::{Element, Renderer};
+use crate::Theme;
+
+type Paragraph = <Renderer as text::Renderer>::Paragraph;
+
+/// Exposes the cursor of `text_input` to [`get`], which iced's text input
+/// doesn't do on its own
+pub fn track<'a, Message: 'a>(
+    text_input: text_input::TextInput<'a, Message, Theme, Renderer>,
+    id: Id,
+    value: &'a str,
+) -> Element<'a, Message> {
+    Track {
+        content: text_input.into(),
+        id,
+        value,
+    }
+    .into()
+}
+
+/// Produces the cursor of the tracked text input w/ `id`, or `None` if
+/// it isn't shown
+pub fn get<Message: 'static>(
+    id: Id,
+    f: impl Fn(Option<Cursor>) -> Message + 'static,
+) -> Command<Message> {
+    struct Get<T> {
+        id: widget::Id,
+        cursor: Option<Cursor>,
+        f: Box<dyn Fn(Option<Cursor>) -> T>,
+    }
+
+    impl<T> Operation<T> for Get<T> {
+        fn container(
+            &mut self,
+            _id: Option<&widget::Id>,
+            _bounds: Rectangle,
+            operate_on_children: &mut dyn FnMut(&mut dyn Operation<T>),
+        ) {
+            operate_on_children(self)
+        }
+
+        fn custom(&mut self, state: &mut dyn std::any::Any, id: Option<&widget::Id>) {
+            if Some(&self.id) == id {
+                if let Some(cursor) = state.downcast_ref::<Cursor>() {
+                    self.cursor = Some(*cursor);
+                }
+            }
+        }
+
+        fn finish(&self) -> operation::Outcome<T> {
+            operation::Outcome::Some((self.f)(self.cursor))
+        }
+    }
+
+    Command::widget(Get {
+        id: id.into(),
+        cursor: None,
+        f: Box::new(f),
+    })
+}
+
+struct Track<'a, Message> {
+    content: Element<'a, Message>,
+    id: Id,
+    value: &'a str,
+}
+
+impl<'a, Message> Widget<Message, Theme, Renderer> for Track<'a, Message> {
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut widget::Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        self.content.as_widget().layout(tree, renderer, limits)
+    }
+
+    fn draw(
+        &self,
+        tree: &widget::Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content
+            .as_widget()
+            .draw(tree, renderer, theme, style, layout, cursor, viewport)
+    }
+
+    fn tag(&self) -> tree::Tag {
+        self.content.as_widget().tag()
+    }
+
+    fn state(&self) -> tree::State {
+        self.content.as_widget().state()
+    }
+
+    fn children(&self) -> Vec<widget::Tree> {
+        self.content.as_widget().children()
+    }
+
+    fn diff(&self, tree: &mut widget::Tree) {
+        self.content.as_widget().diff(tree);
+    }
+
+    fn operate(
+        &self,
+        tree: &mut widget::Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn widget::Operation<Message>,
+    ) {
+        // The tree is the text input's, as its tag & state are delegated
+        let state = tree.state.downcast_ref::<text_input::State<Paragraph>>();
+
+        let mut cursor = match state.cursor().state(&Value::new(self.value)) {
+            cursor::State::Index(index) => Cursor::Index(index),
+            cursor::State::Selection { start, end } => Cursor::Selection { start, end },
+        };
+
+        operation.custom(&mut cursor, Some(&self.id.clone().into()));
+
+        self.content
+            .as_widget()
+            .operate(tree, layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut widget::Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        self.content.as_widget_mut().on_event(
+            tree, event, layout, cursor, renderer, clipboard, shell, viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &widget::Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content
+            .as_widget()
+            .mouse_interaction(tree, layout, cursor, viewport, renderer)
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut widget::Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        self.content
+            .as_widget_mut()
+            .overlay(tree, layout, renderer, translation)
+    }
+}
+
+impl<'a, Message: 'a> From<Track<'a, Message>> for Element<'a, Message> {
+    fn from(track: Track<'a, Message>) -> Self {
+        Element::new(track)
+    }
+}
@@ -0,0 +1,108 @@
+use data::buffer::TextFormatting;
+use data::formatting::{self, Formatting};
+use data::message::link::Link;
+use data::Config;
+use iced::widget::{button, text};
+
+use crate::widget::{context_menu, selectable_text, Element};
+use crate::{theme, Theme};
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Open(Link),
+    Copy(Link),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Entry {
+    Open,
+    Copy,
+}
+
+/// Message text w/ its IRC formatting rendered or stripped, based on
+/// config, and its URLs & channels made clickable
+pub fn view<'a>(
+    message: &'a data::Message,
+    chantypes: &[char],
+    config: &Config,
+    style: fn(&Theme) -> selectable_text::Style,
+) -> Element<'a, Message> {
+    let fragments = message.fragments(chantypes);
+
+    if !formatting::is_formatted(&message.text)
+        && fragments.iter().all(|fragment| fragment.link.is_none())
+    {
+        return selectable_text(&message.text).style(style).into();
+    }
+
+    let render = config.buffer.text_formatting == TextFormatting::Render;
+
+    let spans = fragments
+        .iter()
+        .map(|fragment| {
+            let formatting = if render {
+                fragment.formatting
+            } else {
+                Formatting::default()
+            };
+
+            selectable_text::Span {
+                text: fragment.text.as_str().into(),
+                formatting: Formatting {
+                    underline: formatting.underline || fragment.link.is_some(),
+                    ..formatting
+                },
+                link: fragment.link.clone(),
+            }
+        })
+        .collect();
+
+    selectable_text::rich(spans)
+        .style(style)
+        .on_link(Message::Open)
+        .link_menu(link_menu)
+        .into()
+}
+
+/// Message text w/ its IRC formatting rendered
+pub fn formatted<'a, M: 'a>(
+    content: &str,
+    style: fn(&Theme) -> selectable_text::Style,
+) -> Element<'a, M> {
+    selectable_text::rich(
+        formatting::parse(content)
+            .into_iter()
+            .map(|span| selectable_text::Span {
+                text: span.text.into(),
+                formatting: span.formatting,
+                link: None,
+            })
+            .collect(),
+    )
+    .style(style)
+    .into()
+}
+
+fn link_menu<'a>(link: &Link) -> Element<'a, Message> {
+    let link = link.clone();
+
+    context_menu::menu(vec![Entry::Open, Entry::Copy], move |entry, length| {
+        let (content, message) = match entry {
+            Entry::Open => match link {
+                Link::Url(_) => ("Open Link", Message::Open(link.clone())),
+                Link::Channel(_) => ("Join Channel", Message::Open(link.clone())),
+            },
+            Entry::Copy => match link {
+                Link::Url(_) => ("Copy Link", Message::Copy(link.clone())),
+                Link::Channel(_) => ("Copy Channel", Message::Copy(link.clone())),
+            },
+        };
+
+        button(text(content).style(theme::text::primary))
+            .padding(5)
+            .width(length)
+            .style(theme::button::context)
+            .on_press(message)
+            .into()
+    })
+}
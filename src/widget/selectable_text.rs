@@ -9,9 +9,11 @@ use iced::{
     Shadow, Size,
 };
 
+pub use self::rich::{rich, Rich, Span};
 use self::selection::selection;
 pub use self::text::{LineHeight, Shaping};
 
+mod rich;
 mod selection;
 
 pub fn selectable_text<'a, Theme, Renderer>(
@@ -183,37 +185,7 @@ where
     ) -> event::Status {
         let state = tree.state.downcast_mut::<State<Renderer::Paragraph>>();
 
-        match event {
-            iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
-            | iced::Event::Touch(touch::Event::FingerPressed { .. }) => {
-                if let Some(cursor) = cursor.position() {
-                    state.interaction = Interaction::Selecting(selection::Raw {
-                        start: cursor,
-                        end: cursor,
-                    });
-                } else {
-                    state.interaction = Interaction::Idle;
-                }
-            }
-            iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
-            | iced::Event::Touch(touch::Event::FingerLifted { .. })
-            | iced::Event::Touch(touch::Event::FingerLost { .. }) => {
-                if let Interaction::Selecting(raw) = state.interaction {
-                    state.interaction = Interaction::Selected(raw);
-                } else {
-                    state.interaction = Interaction::Idle;
-                }
-            }
-            iced::Event::Mouse(mouse::Event::CursorMoved { .. })
-            | iced::Event::Touch(touch::Event::FingerMoved { .. }) => {
-                if let Some(cursor) = cursor.position() {
-                    if let Interaction::Selecting(raw) = &mut state.interaction {
-                        raw.end = cursor;
-                    }
-                }
-            }
-            _ => {}
-        }
+        state.interaction.update(&event, cursor);
 
         event::Status::Ignored
     }
@@ -238,56 +210,21 @@ where
 
         let state = tree.state.downcast_ref::<State<Renderer::Paragraph>>();
 
-        if let Some(selection) = state
-            .interaction
-            .selection()
-            .and_then(|raw| raw.resolve(bounds))
-        {
-            let line_height = f32::from(
-                self.line_height.to_absolute(
-                    self.size
-                        .map(Pixels::from)
-                        .unwrap_or_else(|| renderer.default_size()),
-                ),
-            );
-
-            let baseline_y =
-                bounds.y + ((selection.start.y - bounds.y) / line_height).floor() * line_height;
-
-            let height = selection.end.y - baseline_y - 0.5;
-            let rows = (height / line_height).ceil() as usize;
-
-            for row in 0..rows {
-                let (x, width) = if row == 0 {
-                    (
-                        selection.start.x,
-                        if rows == 1 {
-                            f32::min(selection.end.x, bounds.x + bounds.width) - selection.start.x
-                        } else {
-                            bounds.x + bounds.width - selection.start.x
-                        },
-                    )
-                } else if row == rows - 1 {
-                    (bounds.x, selection.end.x - bounds.x)
-                } else {
-                    (bounds.x, bounds.width)
-                };
-                let y = baseline_y + row as f32 * line_height;
-
-                renderer.fill_quad(
-                    Quad {
-                        bounds: Rectangle::new(Point::new(x, y), Size::new(width, line_height)),
-                        border: Border {
-                            radius: 0.0.into(),
-                            width: 0.0,
-                            color: Color::TRANSPARENT,
-                        },
-                        shadow: Shadow::default(),
-                    },
-                    appearance.selection_color,
-                );
-            }
-        }
+        let line_height = f32::from(
+            self.line_height.to_absolute(
+                self.size
+                    .map(Pixels::from)
+                    .unwrap_or_else(|| renderer.default_size()),
+            ),
+        );
+
+        draw_selection(
+            renderer,
+            state.interaction,
+            bounds,
+            line_height,
+            appearance.selection_color,
+        );
 
         // TODO: This method is better for ensuring whole letters are visually selected,
         // but breaks down once wrapping comes to play.
@@ -430,6 +367,58 @@ fn draw<Renderer>(
     );
 }
 
+/// Highlights the rows of `bounds` covered by the selection, if any
+fn draw_selection<Renderer>(
+    renderer: &mut Renderer,
+    interaction: Interaction,
+    bounds: Rectangle,
+    line_height: f32,
+    color: Color,
+) where
+    Renderer: text::Renderer,
+{
+    let Some(selection) = interaction.selection().and_then(|raw| raw.resolve(bounds)) else {
+        return;
+    };
+
+    let baseline_y =
+        bounds.y + ((selection.start.y - bounds.y) / line_height).floor() * line_height;
+
+    let height = selection.end.y - baseline_y - 0.5;
+    let rows = (height / line_height).ceil() as usize;
+
+    for row in 0..rows {
+        let (x, width) = if row == 0 {
+            (
+                selection.start.x,
+                if rows == 1 {
+                    f32::min(selection.end.x, bounds.x + bounds.width) - selection.start.x
+                } else {
+                    bounds.x + bounds.width - selection.start.x
+                },
+            )
+        } else if row == rows - 1 {
+            (bounds.x, selection.end.x - bounds.x)
+        } else {
+            (bounds.x, bounds.width)
+        };
+        let y = baseline_y + row as f32 * line_height;
+
+        renderer.fill_quad(
+            Quad {
+                bounds: Rectangle::new(Point::new(x, y), Size::new(width, line_height)),
+                border: Border {
+                    radius: 0.0.into(),
+                    width: 0.0,
+                    color: Color::TRANSPARENT,
+                },
+                shadow: Shadow::default(),
+            },
+            color,
+        );
+    }
+}
+
 impl<'a, Message, Theme, Renderer> From<Text<'a, Theme, Renderer>>
     for Element<'a, Message, Theme, Renderer>
 where
@@ -462,6 +451,40 @@ impl Interaction {
             Interaction::Selecting(raw) | Interaction::Selected(raw) => Some(*raw),
         }
     }
+
+    fn update(&mut self, event: &iced::Event, cursor: mouse::Cursor) {
+        match event {
+            iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))
+            | iced::Event::Touch(touch::Event::FingerPressed { .. }) => {
+                if let Some(cursor) = cursor.position() {
+                    *self = Interaction::Selecting(selection::Raw {
+                        start: cursor,
+                        end: cursor,
+                    });
+                } else {
+                    *self = Interaction::Idle;
+                }
+            }
+            iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))
+            | iced::Event::Touch(touch::Event::FingerLifted { .. })
+            | iced::Event::Touch(touch::Event::FingerLost { .. }) => {
+                if let Interaction::Selecting(raw) = *self {
+                    *self = Interaction::Selected(raw);
+                } else {
+                    *self = Interaction::Idle;
+                }
+            }
+            iced::Event::Mouse(mouse::Event::CursorMoved { .. })
+            | iced::Event::Touch(touch::Event::FingerMoved { .. }) => {
+                if let Some(cursor) = cursor.position() {
+                    if let Interaction::Selecting(raw) = self {
+                        raw.end = cursor;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 // fn measure<Renderer>(
@@ -0,0 +1,522 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use data::formatting::Formatting;
+use data::message::link::Link;
+use iced::advanced::renderer::Quad;
+use iced::advanced::text::{self, Paragraph as _, Renderer as _};
+use iced::advanced::widget::{tree, Operation, Tree};
+use iced::advanced::{layout, mouse, overlay, renderer, Layout, Renderer as _, Widget};
+use iced::widget::text_input::Value;
+use iced::{
+    alignment, event, Border, Color, Length, Pixels, Point, Rectangle, Shadow, Size, Vector,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{draw_selection, selection, Interaction, LineHeight, Shaping, Style, StyleFn};
+use crate::widget::{context_menu, Element, Renderer};
+use crate::{font, theme, Theme};
+
+type Paragraph = <Renderer as text::Renderer>::Paragraph;
+
+/// Text made of spans w/ their own IRC formatting, laid out as a single
+/// paragraph so it wraps and is selected as a whole
+pub fn rich<'a, Message>(spans: Vec<Span<'a>>) -> Rich<'a, Message> {
+    Rich::new(spans)
+}
+
+/// A run of text sharing the same formatting, which may be a link
+#[derive(Debug, Clone)]
+pub struct Span<'a> {
+    pub text: Cow<'a, str>,
+    pub formatting: Formatting,
+    pub link: Option<Link>,
+}
+
+pub struct Rich<'a, Message> {
+    spans: Vec<Span<'a>>,
+    shaping: Shaping,
+    style: StyleFn<'a, Theme>,
+    on_link: Option<Box<dyn Fn(Link) -> Message + 'a>>,
+    /// A menu for each link in `spans`, in order
+    menus: Vec<Element<'a, Message>>,
+}
+
+impl<'a, Message> Rich<'a, Message> {
+    pub fn new(spans: Vec<Span<'a>>) -> Self {
+        Self {
+            spans,
+            #[cfg(debug_assertions)]
+            shaping: Shaping::Basic,
+            #[cfg(not(debug_assertions))]
+            shaping: Shaping::Advanced,
+            style: Box::new(theme::selectable_text::none),
+            on_link: None,
+            menus: vec![],
+        }
+    }
+
+    pub fn style(mut self, style: impl Fn(&Theme) -> Style + 'a) -> Self {
+        self.style = Box::new(style);
+        self
+    }
+
+    /// Produces a message when a link is clicked, unless the click
+    /// selected text
+    pub fn on_link(mut self, on_link: impl Fn(Link) -> Message + 'a) -> Self {
+        self.on_link = Some(Box::new(on_link));
+        self
+    }
+
+    /// Shows the menu built by `menu` when a link is right clicked
+    pub fn link_menu(mut self, menu: impl Fn(&Link) -> Element<'a, Message>) -> Self {
+        self.menus = self
+            .spans
+            .iter()
+            .filter_map(|span| span.link.as_ref())
+            .map(menu)
+            .collect();
+        self
+    }
+
+    /// The span w/ a link under `position`, relative to the widget
+    fn link_at(&self, state: &State, position: Point) -> Option<usize> {
+        self.spans
+            .iter()
+            .zip(&state.bounds)
+            .position(|(span, bounds)| {
+                span.link.is_some() && bounds.iter().any(|bounds| bounds.contains(position))
+            })
+    }
+}
+
+impl<'a, Message> Widget<Message, Theme, Renderer> for Rich<'a, Message> {
+    fn size(&self) -> Size<Length> {
+        Size {
+            width: Length::Shrink,
+            height: Length::Shrink,
+        }
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        self.menus.iter().map(Tree::new).collect()
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(&self.menus);
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let state = tree.state.downcast_mut::<State>();
+
+        if !state.is_content(&self.spans) {
+            state.set_content(&self.spans);
+        }
+
+        layout::sized(limits, Length::Shrink, Length::Shrink, |limits| {
+            let size = renderer.default_size();
+            let line_height = line_height(size);
+
+            let content = state.content.as_str();
+            let text = |font| text::Text {
+                content,
+                size,
+                line_height: LineHeight::default(),
+                bounds: limits.max(),
+                font,
+                horizontal_alignment: alignment::Horizontal::Left,
+                vertical_alignment: alignment::Vertical::Top,
+                shaping: self.shaping,
+            };
+
+            state.paragraph.update(text(font::MONO.clone().into()));
+
+            // Every font is monospaced, so the text wraps the same in each
+            let fonts = self
+                .spans
+                .iter()
+                .map(|span| span_font(&span.formatting))
+                .filter(|font| *font != iced::Font::from(font::MONO.clone()))
+                .collect::<Vec<_>>();
+
+            state.styled.retain(|(font, _)| fonts.contains(font));
+
+            for font in fonts {
+                match state.styled.iter_mut().find(|(styled, _)| *styled == font) {
+                    Some((_, paragraph)) => paragraph.update(text(font)),
+                    None => state.styled.push((font, Paragraph::with_text(text(font)))),
+                }
+            }
+
+            let rows = rows(&state.paragraph, line_height);
+
+            state.bounds = state
+                .ranges
+                .iter()
+                .map(|range| {
+                    span_bounds(&state.paragraph, content, range.clone(), &rows, line_height)
+                })
+                .collect();
+
+            state.paragraph.min_bounds()
+        })
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: iced::Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn iced::advanced::Clipboard,
+        shell: &mut iced::advanced::Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let state = tree.state.downcast_mut::<State>();
+
+        let bounds = layout.bounds();
+        let link = cursor
+            .position_in(bounds)
+            .and_then(|position| self.link_at(state, position));
+
+        match &event {
+            iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                state.pressed = link;
+            }
+            iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => {
+                if let (Some(index), Some(position)) = (link, cursor.position()) {
+                    // Menus are built for links only, in the order of the spans
+                    let menu = self.spans[..index]
+                        .iter()
+                        .filter(|span| span.link.is_some())
+                        .count();
+
+                    if menu < self.menus.len() {
+                        state.menu = Some(menu);
+                        state.menu_state = context_menu::State::Open(position);
+
+                        return event::Status::Captured;
+                    }
+                }
+            }
+            iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let pressed = state.pressed.take();
+                let is_click = matches!(
+                    state.interaction,
+                    Interaction::Selecting(raw) if raw.start == raw.end
+                );
+
+                if let Some(on_link) = &self.on_link {
+                    if let Some(link) = link
+                        .filter(|index| is_click && pressed == Some(*index))
+                        .and_then(|index| self.spans[index].link.clone())
+                    {
+                        shell.publish(on_link(link));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        state.interaction.update(&event, cursor);
+
+        event::Status::Ignored
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+
+        if viewport.intersection(&bounds).is_none() {
+            return;
+        }
+
+        let state = tree.state.downcast_ref::<State>();
+        let appearance = (self.style)(theme);
+        let link_appearance = theme::selectable_text::link(theme);
+        let line_height = line_height(renderer.default_size());
+        let offset = Vector::new(bounds.x, bounds.y);
+
+        for (span, span_bounds) in self.spans.iter().zip(&state.bounds) {
+            let (_, background) = data::theme::formatting_colors(&span.formatting, theme.colors());
+
+            if let Some(background) = background {
+                for span_bounds in span_bounds {
+                    fill(renderer, *span_bounds + offset, background);
+                }
+            }
+        }
+
+        draw_selection(
+            renderer,
+            state.interaction,
+            bounds,
+            line_height,
+            appearance.selection_color,
+        );
+
+        for (span, span_bounds) in self.spans.iter().zip(&state.bounds) {
+            let paragraph = state.paragraph(span_font(&span.formatting));
+            let appearance = if span.link.is_some() {
+                link_appearance
+            } else {
+                appearance
+            };
+            let color = theme::selectable_text::formatted(theme, &span.formatting, appearance)
+                .color
+                .unwrap_or(style.text_color);
+
+            for span_bounds in span_bounds {
+                let span_bounds = *span_bounds + offset;
+
+                // Each span is drawn from the whole paragraph, clipped to
+                // where the span was laid out
+                if let Some(clip_bounds) = viewport.intersection(&span_bounds) {
+                    renderer.fill_paragraph(paragraph, bounds.position(), color, clip_bounds);
+                }
+
+                let decorations = [
+                    span.formatting.underline.then_some(line_height - 1.0),
+                    span.formatting
+                        .strikethrough
+                        .then_some((line_height / 2.0).floor()),
+                ];
+
+                for y in decorations.into_iter().flatten() {
+                    fill(
+                        renderer,
+                        Rectangle::new(
+                            Point::new(span_bounds.x, span_bounds.y + y),
+                            Size::new(span_bounds.width, 1.0),
+                        ),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+
+        match cursor.position_in(layout.bounds()) {
+            Some(position) if self.link_at(state, position).is_some() => {
+                mouse::Interaction::Pointer
+            }
+            Some(_) => mouse::Interaction::Text,
+            None => mouse::Interaction::default(),
+        }
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        _renderer: &Renderer,
+        operation: &mut dyn Operation<Message>,
+    ) {
+        let state = tree.state.downcast_ref::<State>();
+
+        let bounds = layout.bounds();
+        let value = Value::new(&state.content);
+        if let Some(selection) = state
+            .interaction
+            .selection()
+            .and_then(|raw| selection::selection(raw, bounds, &state.paragraph, &value))
+        {
+            let content = value.select(selection.start, selection.end).to_string();
+            operation.custom(&mut (bounds.y, content), None);
+        }
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        _layout: Layout<'_>,
+        _renderer: &Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        let state = tree.state.downcast_mut::<State>();
+
+        let position = state.menu_state.open()?;
+        let menu = self.menus.get_mut(state.menu?)?;
+        let menu_tree = tree.children.get_mut(state.menu?)?;
+
+        Some(context_menu::overlay(
+            menu,
+            menu_tree,
+            &mut state.menu_state,
+            position + translation,
+        ))
+    }
+}
+
+impl<'a, Message: 'a> From<Rich<'a, Message>> for Element<'a, Message> {
+    fn from(rich: Rich<'a, Message>) -> Self {
+        Element::new(rich)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// The text of every span
+    content: String,
+    /// Byte range of each span in `content`
+    ranges: Vec<Range<usize>>,
+    /// `content` laid out in the regular font, used to measure & hit test
+    paragraph: Paragraph,
+    /// `content` laid out in every other font used by a span
+    styled: Vec<(iced::Font, Paragraph)>,
+    /// Where each span was laid out, w/ a rectangle per row it's on
+    bounds: Vec<Vec<Rectangle>>,
+    interaction: Interaction,
+    /// The link span pressed, which is opened if released over it
+    pressed: Option<usize>,
+    /// Which link's menu is shown, as an index in the menus
+    menu: Option<usize>,
+    menu_state: context_menu::State,
+}
+
+impl State {
+    fn is_content(&self, spans: &[Span<'_>]) -> bool {
+        self.ranges.len() == spans.len()
+            && spans
+                .iter()
+                .zip(&self.ranges)
+                .all(|(span, range)| self.content.get(range.clone()) == Some(span.text.as_ref()))
+    }
+
+    fn set_content(&mut self, spans: &[Span<'_>]) {
+        self.content.clear();
+        self.ranges.clear();
+
+        for span in spans {
+            let start = self.content.len();
+            self.content.push_str(&span.text);
+            self.ranges.push(start..self.content.len());
+        }
+    }
+
+    fn paragraph(&self, font: iced::Font) -> &Paragraph {
+        self.styled
+            .iter()
+            .find_map(|(styled, paragraph)| (*styled == font).then_some(paragraph))
+            .unwrap_or(&self.paragraph)
+    }
+}
+
+fn span_font(formatting: &Formatting) -> iced::Font {
+    // Monospace is a no-op, as the font is already monospaced
+    match (formatting.bold, formatting.italic) {
+        (false, false) => font::MONO.clone(),
+        (true, false) => font::MONO_BOLD.clone(),
+        (false, true) => font::MONO_ITALIC.clone(),
+        (true, true) => font::MONO_BOLD_ITALIC.clone(),
+    }
+    .into()
+}
+
+fn line_height(size: Pixels) -> f32 {
+    f32::from(LineHeight::default().to_absolute(size))
+}
+
+/// Byte range of the text on each row of `paragraph`. Message text is a
+/// single line, so rows only come from wrapping.
+fn rows(paragraph: &Paragraph, line_height: f32) -> Vec<Range<usize>> {
+    let Size { width, height } = paragraph.min_bounds();
+    let count = (height / line_height).round() as usize;
+
+    (0..count)
+        .filter_map(|row| {
+            let y = (row as f32 + 0.5) * line_height;
+
+            let start = paragraph.hit_test(Point::new(0.0, y))?.cursor();
+            let end = paragraph.hit_test(Point::new(width + 1.0, y))?.cursor();
+
+            Some(start..end)
+        })
+        .collect()
+}
+
+/// Bounds of the text in `range` on each row it's laid out on, relative to
+/// the position of `paragraph`
+fn span_bounds(
+    paragraph: &Paragraph,
+    content: &str,
+    range: Range<usize>,
+    rows: &[Range<usize>],
+    line_height: f32,
+) -> Vec<Rectangle> {
+    rows.iter()
+        .enumerate()
+        .filter_map(|(row, row_range)| {
+            let start = range.start.max(row_range.start);
+            let end = range.end.min(row_range.end);
+
+            if start >= end {
+                return None;
+            }
+
+            let x = |offset: usize| {
+                let graphemes = content
+                    .get(row_range.start..offset)
+                    .map_or(0, |text| text.graphemes(true).count());
+
+                paragraph
+                    .grapheme_position(row, graphemes)
+                    .map_or(0.0, |position| position.x)
+            };
+
+            let (start_x, end_x) = (x(start), x(end));
+
+            Some(Rectangle::new(
+                Point::new(start_x, row as f32 * line_height),
+                Size::new(end_x - start_x, line_height),
+            ))
+        })
+        .collect()
+}
+
+fn fill(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        Quad {
+            bounds,
+            border: Border {
+                radius: 0.0.into(),
+                width: 0.0,
+                color: Color::TRANSPARENT,
+            },
+            shadow: Shadow::default(),
+        },
+        color,
+    );
+}